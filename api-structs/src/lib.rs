@@ -3,6 +3,29 @@ use serde::{Deserialize, Serialize};
 pub const FRONTEND_PUBLIC_URL_PATH_NO_TRAILING_SLASH: &str =
     env!("FRONTEND_PUBLIC_URL_PATH_NO_TRAILING_SLASH");
 
+/// Marks the start of a word matching the event search in [ApiTraceGridRow::event]
+pub const HIGHLIGHT_START: char = '\u{2}';
+/// Marks the end of a word matching the event search in [ApiTraceGridRow::event]
+pub const HIGHLIGHT_END: char = '\u{3}';
+
+/// One page of the traces grid
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ApiTraceGrid {
+    pub rows: Vec<ApiTraceGridRow>,
+    /// How many traces match the search in total, only computed for the first page (no cursor)
+    pub total_count: Option<u64>,
+    /// Pass it back in [SearchFor::cursor] to get the next page, None if this is the last one
+    pub next_cursor: Option<TraceGridCursor>,
+}
+
+/// Position after the last row of a page: the value of the column being sorted on and the trace id
+/// as a tie breaker. Only meaningful with the same search and [OrderBy] that returned it.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TraceGridCursor {
+    pub sort_value: u64,
+    pub id: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiTraceGridRow {
     pub id: u64,
@@ -14,8 +37,14 @@ pub struct ApiTraceGridRow {
     pub key: Option<String>,
     pub value: Option<String>,
     pub span: Option<String>,
+    /// Slowest span matching the span filters, highlighted when the trace is opened from the grid
+    pub span_id: Option<u64>,
+    /// Snippet of the event best matching the event search, matched words are wrapped in
+    /// [HIGHLIGHT_START] and [HIGHLIGHT_END]
     pub event: Option<String>,
     pub timestamp: u64,
+    /// Uploaded from a file instead of received by the collector
+    pub imported: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -24,6 +53,17 @@ pub struct KeySpans {
     pub top_level_spans: Vec<String>,
     pub spans: Vec<String>,
     pub keys: Vec<String>,
+    /// Most frequent first
+    pub event_names: Vec<String>,
+}
+
+/// Asks for the values observed for `key` in the traces matching `search_for`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttributeValuesRequest {
+    pub search_for: SearchFor,
+    pub key: String,
+    /// Only values containing it, ignoring case
+    pub value: String,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -42,6 +82,137 @@ pub struct Span {
     pub events: Vec<Events>,
 }
 
+/// Formats `/api/trace` can export a trace as, besides its own
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// OTLP/HTTP JSON encoding of an `ExportTraceServiceRequest`
+    OtlpJson,
+    /// Jaeger query API response, importable in the Jaeger UI
+    JaegerJson,
+    /// Chrome Trace Event format, loadable in Perfetto and chrome://tracing
+    ChromeTrace,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 3] = [
+        ExportFormat::OtlpJson,
+        ExportFormat::JaegerJson,
+        ExportFormat::ChromeTrace,
+    ];
+
+    /// Value of the `format` query parameter
+    pub fn query_value(&self) -> &'static str {
+        match self {
+            ExportFormat::OtlpJson => "otlp_json",
+            ExportFormat::JaegerJson => "jaeger_json",
+            ExportFormat::ChromeTrace => "chrome_trace",
+        }
+    }
+
+    /// Can be sent in the Accept header instead of the query parameter
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ExportFormat::OtlpJson => "application/vnd.otlp+json",
+            ExportFormat::JaegerJson => "application/vnd.jaeger+json",
+            ExportFormat::ChromeTrace => "application/vnd.chrome-trace+json",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::OtlpJson => "OTLP JSON",
+            ExportFormat::JaegerJson => "Jaeger JSON",
+            ExportFormat::ChromeTrace => "Chrome Trace",
+        }
+    }
+}
+
+/// Span on the critical path of a trace: the chain of spans that determined its duration
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CriticalPathSpan {
+    pub span_id: u64,
+    /// Nanoseconds of the trace duration spent in the span itself, not waiting on a child.
+    /// The contributions of the whole path add up to the trace duration.
+    pub contribution: u64,
+}
+
+/// Spans of a single trace grouped by name
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpanSelfTime {
+    pub name: String,
+    pub count: u64,
+    pub total_duration: u64,
+    /// Time in the spans while none of their children was running
+    pub self_duration: u64,
+    /// Self duration over the trace duration, above 1 when concurrent spans add up to more
+    /// than the trace duration
+    pub trace_fraction: f64,
+    /// Self duration over the self duration of all the spans of the trace, between 0 and 1
+    pub self_time_fraction: f64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CompareTracesRequest {
+    pub left_trace_id: i64,
+    pub right_trace_id: i64,
+}
+
+/// Spans of two traces matched by their name path from the root, in depth first order. Spans
+/// with the same name under the same parent are matched in the order they started.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TraceComparison {
+    pub left_duration: u64,
+    pub right_duration: u64,
+    pub spans: Vec<SpanComparison>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpanComparison {
+    pub name: String,
+    pub depth: u32,
+    /// None when the span is only in the right trace
+    pub left: Option<ComparedSpan>,
+    /// None when the span is only in the left trace
+    pub right: Option<ComparedSpan>,
+    /// Right duration minus left duration, when the span is in both
+    pub duration_delta: Option<i64>,
+    /// User generated attributes with a different value (or missing) on one side
+    pub attribute_differences: Vec<AttributeDifference>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ComparedSpan {
+    pub span_id: u64,
+    pub duration: u64,
+    pub event_count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttributeDifference {
+    pub key: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+/// Outcome of uploading a trace file to `/api/import`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ImportResult {
+    pub imported_trace_ids: Vec<i64>,
+    /// Valid traces that could not be stored, the backend logs have the details
+    pub failed_to_store: usize,
+    pub rejected: Vec<RejectedTrace>,
+}
+
+/// A trace from the file that failed the same validation live traces go through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedTrace {
+    /// None when the resource had no service name, so its traces could not be told apart
+    pub service_name: Option<String>,
+    pub otel_trace_id: Option<String>,
+    pub reason: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct KeyValue {
     pub key: String,
@@ -49,7 +220,7 @@ pub struct KeyValue {
     pub value: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Severity {
     #[serde(alias = "trace")]
     Trace,
@@ -63,6 +234,28 @@ pub enum Severity {
     Error,
 }
 
+impl Severity {
+    /// Least to most severe, same order as the DB severity_level enum
+    pub const ALL: [Severity; 5] = [
+        Severity::Trace,
+        Severity::Debug,
+        Severity::Info,
+        Severity::Warn,
+        Severity::Error,
+    ];
+
+    /// Name in the DB severity_level enum
+    pub fn db_name(&self) -> &'static str {
+        match self {
+            Severity::Trace => "trace",
+            Severity::Debug => "debug",
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Events {
     pub name: String,
@@ -71,20 +264,236 @@ pub struct Events {
     pub key_values: Vec<KeyValue>,
 }
 
+/// Search over events on their own, as log lines. Newest first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogSearchRequest {
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+    /// Empty for any
+    pub service_name: String,
+    /// Only events at least this severe, None for all
+    pub min_severity: Option<Severity>,
+    /// Text the message contains, empty for any
+    pub message: String,
+    /// All must match attributes of the event itself
+    pub attributes: Vec<AttributeFilter>,
+    /// None for the first page
+    pub cursor: Option<LogCursor>,
+}
+
+/// Position after the last line of a page, only meaningful with the search that returned it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LogCursor {
+    pub timestamp: u64,
+    pub trace_id: u64,
+    pub span_id: u64,
+    pub event_id: u64,
+}
+
+/// An event with the span and trace it was logged in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLine {
+    pub trace_id: u64,
+    pub span_id: u64,
+    pub timestamp: u64,
+    pub severity: Severity,
+    pub message: String,
+    pub key_values: Vec<KeyValue>,
+    pub service_name: String,
+    pub top_level_span_name: String,
+    pub span_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct LogPage {
+    pub lines: Vec<LogLine>,
+    /// Pass it back in [LogSearchRequest::cursor] to get older lines, None if there are none
+    pub next_cursor: Option<LogCursor>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErrorGroupsRequest {
+    /// Resolved groups are left out unless set
+    pub include_resolved: bool,
+    /// Empty for any
+    pub service_name: String,
+}
+
+/// Error events and error status spans with the same service, span name and message once
+/// numbers, ids and quoted values are normalized
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErrorGroup {
+    pub fingerprint: String,
+    pub service_name: String,
+    pub span_name: String,
+    /// Normalized message, like "Query took <num>ms for '*'"
+    pub message: String,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub occurrences: u64,
+    /// Most recent first, might already be deleted by the trace retention
+    pub sample_trace_ids: Vec<i64>,
+    /// None while open, new occurrences reopen the group
+    pub resolved_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ResolveErrorGroupRequest {
+    pub fingerprint: String,
+    /// False reopens it
+    pub resolved: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Summary {
     pub service_name: String,
     pub top_level_span_name: String,
     pub total_traces: i64,
     pub total_traces_with_error: i64,
+    /// Between 0 and 1
+    pub error_rate: f64,
+    pub total_warnings: i64,
+    pub traces_with_warnings: i64,
+    /// Traces per minute over the requested window
+    pub throughput_per_minute: f64,
+    pub p50_duration: u64,
+    pub p90_duration: u64,
+    pub p99_duration: u64,
     pub longest_trace_id: u64,
     pub longest_trace_duration: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct SummaryRequest {
-    pub from_date_unix_micros: u64,
-    pub to_date_unix_micros: u64,
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StorageUsageRequest {
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+}
+
+/// Rows and estimated bytes stored for a service and top level span
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StorageUsage {
+    pub service_name: String,
+    pub top_level_span_name: String,
+    pub traces: i64,
+    pub spans: i64,
+    pub events: i64,
+    pub attributes: i64,
+    pub estimated_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StorageUsageTrendPoint {
+    pub service_name: String,
+    pub bucket_start_unix: u64,
+    pub traces: i64,
+    pub spans: i64,
+    pub events: i64,
+    pub attributes: i64,
+    pub estimated_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct StorageUsageReport {
+    pub bucket_size_nanos: u64,
+    pub usage: Vec<StorageUsage>,
+    pub trend: Vec<StorageUsageTrendPoint>,
+}
+
+/// Traces matching a [SearchFor] counted per time bucket and log scaled duration bucket,
+/// only cells with traces are sent
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Heatmap {
+    pub first_time_bucket_unix: u64,
+    pub time_bucket_size_nanos: u64,
+    pub time_buckets: u32,
+    /// Bucket `i` holds durations in `duration_bucket_bounds[i]..duration_bucket_bounds[i + 1]`
+    pub duration_bucket_bounds: Vec<u64>,
+    pub cells: Vec<HeatmapCell>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HeatmapCell {
+    pub time_bucket: u32,
+    pub duration_bucket: u32,
+    pub count: u64,
+}
+
+/// Series for a whole trace (service + top level span) or, when `span_name` is set, for the
+/// spans with that name inside of it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimeSeriesRequest {
+    pub service_name: String,
+    pub top_level_span_name: String,
+    pub span_name: Option<String>,
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimeSeriesPoint {
+    pub bucket_start_unix: u64,
+    pub count: i64,
+    pub error_count: i64,
+    pub warning_count: i64,
+    pub p50_duration: u64,
+    pub p90_duration: u64,
+    pub p99_duration: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TimeSeries {
+    /// Picked by the server from the window size
+    pub bucket_size_nanos: u64,
+    /// Every bucket in the window, including the empty ones
+    pub points: Vec<TimeSeriesPoint>,
+    /// Span names seen in the window for the service and top level span
+    pub span_names: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProfileRequest {
+    pub service_name: String,
+    pub top_level_span_name: String,
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+}
+
+/// Spans of many traces of one operation merged by their name path from the root
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct Profile {
+    /// Traces in the window, only the most recent `traces_profiled` of them are merged, as many
+    /// as fit the limits on the traces and spans loaded
+    pub traces_in_window: u64,
+    pub traces_profiled: u64,
+    /// Depth first, children sorted by [ProfileNode::total_duration_per_trace] descending
+    pub nodes: Vec<ProfileNode>,
+}
+
+/// Durations are in nanoseconds, the averages and percentiles are per call
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProfileNode {
+    pub name: String,
+    /// Index of the parent in [Profile::nodes], None for the root
+    pub parent: Option<u32>,
+    pub depth: u32,
+    pub calls: u64,
+    /// Profiled traces with at least one call
+    pub traces: u64,
+    pub avg_total_duration: u64,
+    pub p50_total_duration: u64,
+    pub p90_total_duration: u64,
+    pub avg_self_duration: u64,
+    pub p50_self_duration: u64,
+    pub p90_self_duration: u64,
+    /// Total duration of all the calls divided by the profiled traces, the typical time a trace
+    /// spends here. Can be more than the parent's when calls run concurrently.
+    pub total_duration_per_trace: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -92,20 +501,279 @@ pub struct SearchFor {
     pub from_date_unix: u64,
     pub to_date_unix: u64,
     pub service_name: String,
+    pub service_name_match: TextMatch,
     pub top_level_span: String,
-    pub span: String,
+    pub top_level_span_match: TextMatch,
     pub min_duration: u64,
     pub max_duration: Option<u64>,
     pub min_warns: u32,
-    pub key: String,
-    pub value: String,
-    pub event_name: String,
     pub only_errors: bool,
+    /// All of them must match
+    pub filters: Vec<Filter>,
+    /// How [Filter::SpanName] filters compare the span name
+    pub span_name_match: TextMatch,
+    /// How [Filter::EventName] filters compare the event message, None for the word search over
+    /// messages and string attribute values
+    pub event_match: Option<TextMatch>,
+    /// How attribute filters using [ValueComparison::Contains] compare the value
+    pub attribute_value_match: TextMatch,
+    pub order_by: OrderBy,
+    /// None for the first page
+    pub cursor: Option<TraceGridCursor>,
+}
+
+/// Time range of a grid search. Relative ranges are resolved when the search is opened, so a
+/// shared link keeps showing the latest traces.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum TimeRange {
+    /// From this many nanoseconds ago
+    Last(u64),
+    Between {
+        from_date_unix: u64,
+        to_date_unix: u64,
+    },
+}
+
+impl TimeRange {
+    /// `(from, to)`, relative ranges end a day after `now_unix` so traces arriving later still match
+    pub fn resolve(&self, now_unix: u64) -> (u64, u64) {
+        match *self {
+            TimeRange::Last(duration) => (
+                now_unix.saturating_sub(duration),
+                now_unix + 24 * 3600 * 1_000_000_000,
+            ),
+            TimeRange::Between {
+                from_date_unix,
+                to_date_unix,
+            } => (from_date_unix, to_date_unix),
+        }
+    }
+}
+
+/// Everything needed to reopen the same trace grid, kept by saved searches and encoded in links.
+/// The dates of [SearchFor] are replaced by the resolved [GridSearchState::time_range].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GridSearchState {
+    pub search_for: SearchFor,
+    pub time_range: TimeRange,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub owner: String,
+    pub description: Option<String>,
+    pub state: GridSearchState,
+    pub updated_at: u64,
+}
+
+/// Saving with the name of an existing search of the same owner replaces it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewSavedSearch {
+    pub name: String,
+    pub owner: String,
+    pub description: Option<String>,
+    pub state: GridSearchState,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeleteSavedSearchRequest {
+    pub id: i64,
+}
+
+/// Notifies when newly stored traces match a saved search, ignoring its time range
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SearchSubscription {
+    pub id: i64,
+    pub saved_search_id: i64,
+    pub saved_search_name: String,
+    pub owner: String,
+    /// At most one notification per interval, matches in between are sent with the next one
+    pub min_interval_seconds: u64,
+    pub created_at: u64,
+}
+
+/// Subscribing to an already subscribed search updates its interval
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewSearchSubscription {
+    pub saved_search_id: i64,
+    pub min_interval_seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeleteSearchSubscriptionRequest {
+    pub id: i64,
+}
+
+/// Search written in the trace query language, see the backend `query_language` module for the grammar
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TraceQueryRequest {
+    pub query: String,
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+    /// None for the first page
+    pub cursor: Option<TraceGridCursor>,
+}
+
+/// Returned with a 400 when the query can't be parsed, `start` and `end` are char offsets
+/// into the query pointing at the offending part
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuerySyntaxError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Condition on the spans, events or attributes of a trace
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum Filter {
+    /// Trace has a span or event attribute matching it
+    Attribute(AttributeFilter),
+    /// Trace has a span with this name, compared using [SearchFor::span_name_match]
+    SpanName(String),
+    /// Trace has a span matching all of it, like `db_query` taking more than 500ms
+    Span(SpanFilter),
+    /// Trace has an event whose message or string attribute values contain all these words.
+    /// `"quoted words"` must appear in that order and `word*` matches words starting with it.
+    /// With [SearchFor::event_match] set the message is compared using it instead.
+    EventName(String),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Attribute, span and event conditions not under a `Not`, these are the ones
+    /// that can be shown as the reason a trace matched
+    pub fn positive_conditions(&self) -> Vec<&Filter> {
+        match self {
+            Filter::Attribute(_) | Filter::SpanName(_) | Filter::Span(_) | Filter::EventName(_) => {
+                vec![self]
+            }
+            Filter::And(filters) | Filter::Or(filters) => filters
+                .iter()
+                .flat_map(|f| f.positive_conditions())
+                .collect(),
+            Filter::Not(_) => vec![],
+        }
+    }
+}
+
+/// Conditions on a single span, at least one must be set
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SpanFilter {
+    /// Compared using [SearchFor::span_name_match], empty for any span
+    pub name: String,
+    /// Nanoseconds
+    pub min_duration: Option<u64>,
+    /// Nanoseconds
+    pub max_duration: Option<u64>,
+    /// All must match attributes of the span itself
+    pub attributes: Vec<AttributeFilter>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AttributeFilter {
+    pub key: String,
+    pub comparison: ValueComparison,
+    pub value: String,
+}
+
+/// How a searched text is compared with the stored one. Exact and Regex (postgres syntax) are
+/// case sensitive, Contains and Prefix are not.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum TextMatch {
+    #[default]
+    Exact,
+    Contains,
+    Prefix,
+    Regex,
+}
+
+impl TextMatch {
+    pub const ALL: [TextMatch; 4] = [
+        TextMatch::Exact,
+        TextMatch::Contains,
+        TextMatch::Prefix,
+        TextMatch::Regex,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TextMatch::Exact => "exact",
+            TextMatch::Contains => "contains",
+            TextMatch::Prefix => "prefix",
+            TextMatch::Regex => "regex",
+        }
+    }
+}
+
+/// How the searched value is compared with the stored attribute value.
+/// Anything other than `Contains` takes the attribute `value_type` into account,
+/// so `> 500` only matches i64 and f64 attributes and `= false` only bool ones.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ValueComparison {
+    #[default]
+    Contains,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+}
+
+impl ValueComparison {
+    pub const ALL: [ValueComparison; 7] = [
+        ValueComparison::Contains,
+        ValueComparison::Equal,
+        ValueComparison::NotEqual,
+        ValueComparison::GreaterThan,
+        ValueComparison::GreaterThanOrEqual,
+        ValueComparison::LessThan,
+        ValueComparison::LessThanOrEqual,
+    ];
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ValueComparison::Contains => "contains",
+            ValueComparison::Equal => "=",
+            ValueComparison::NotEqual => "!=",
+            ValueComparison::GreaterThan => ">",
+            ValueComparison::GreaterThanOrEqual => ">=",
+            ValueComparison::LessThan => "<",
+            ValueComparison::LessThanOrEqual => "<=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
 pub enum OrderBy {
+    #[default]
     DateDesc,
+    DateAsc,
     DurationAsc,
     DurationDesc,
+    /// Best matching event first, traces are equally relevant without an event search
+    RelevanceDesc,
+}
+
+impl OrderBy {
+    pub const ALL: [OrderBy; 5] = [
+        OrderBy::DateDesc,
+        OrderBy::DateAsc,
+        OrderBy::DurationDesc,
+        OrderBy::DurationAsc,
+        OrderBy::RelevanceDesc,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            OrderBy::DateDesc => "Newest first",
+            OrderBy::DateAsc => "Oldest first",
+            OrderBy::DurationDesc => "Slowest first",
+            OrderBy::DurationAsc => "Fastest first",
+            OrderBy::RelevanceDesc => "Most relevant first",
+        }
+    }
 }
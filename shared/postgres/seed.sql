@@ -38,9 +38,14 @@ create table trace
     top_level_span_name identifier not null,
     duration            ubigint    not null,
     warning_count       ubigint    not null,
-    has_errors          boolean    not null
+    has_errors          boolean    not null,
+    imported            boolean    not null default false,
+    -- in nanoseconds, imported traces have their own retention counted from their import
+    imported_at         ubigint
 );
 create unique index on trace (timestamp, duration, service_name, top_level_span_name, id);
+create index trace_by_duration_with_id on trace (duration, id);
+comment on index trace_by_duration_with_id is 'Allows paginating the traces sorted by duration';
 create index on trace (warning_count);
 create index on trace (has_errors);
 
@@ -63,6 +68,16 @@ comment on index span_by_name_and_trace_with_id is 'Allows filtering spans by na
 CREATE TYPE value_type AS ENUM ('string', 'i64', 'f64', 'bool');
 CREATE TYPE severity_level AS ENUM ('trace', 'debug', 'info', 'warn', 'error');
 
+create function numeric_value(value_type value_type, value text) returns numeric
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select case when value_type in ('i64', 'f64') then value::numeric end
+$$;
+comment on function numeric_value is 'Numeric value of i64 and f64 attributes, null for other types';
+
 create table span_key_value
 (
     trace_id       ubigint    not null,
@@ -75,6 +90,8 @@ create table span_key_value
     primary key (trace_id, key, span_id) include (value)
 );
 create index on span_key_value (key, trace_id);
+create index on span_key_value (key, numeric_value(value_type, value)) where value_type in ('i64', 'f64');
+create index on span_key_value (key, value) where value_type = 'bool';
 
 
 create table event
@@ -90,6 +107,10 @@ create table event
 );
 CREATE EXTENSION btree_gin;
 CREATE INDEX ON event USING gin (name, trace_id);
+create index event_by_timestamp on event (timestamp);
+comment on index event_by_timestamp is 'Allows searching and paginating events as log lines';
+create index event_name_full_text on event using gin (to_tsvector('simple', name));
+comment on index event_name_full_text is 'Allows the word, phrase and prefix search over event messages';
 
 
 
@@ -106,3 +127,100 @@ create table event_key_value
     primary key (trace_id, span_id, key, event_id) include (value)
 );
 create index on event_key_value (key, trace_id);
+create index on event_key_value (key, numeric_value(value_type, value)) where value_type in ('i64', 'f64');
+create index on event_key_value (key, value) where value_type = 'bool';
+create index event_key_value_full_text on event_key_value using gin (to_tsvector('simple', value)) where value_type = 'string';
+comment on index event_key_value_full_text is 'Allows the event search to match string attribute values';
+
+
+
+create function add_histograms(a bigint[], b bigint[]) returns bigint[]
+    language sql
+    immutable
+    parallel safe
+as
+$$
+select coalesce(array_agg(coalesce(a[i], 0) + coalesce(b[i], 0) order by i), '{}')
+from generate_series(1, greatest(cardinality(a), cardinality(b))) as i
+$$;
+comment on function add_histograms is 'Element-wise sum of two histograms, the shorter one is padded with zeros';
+
+create aggregate sum_histograms(bigint[])
+(
+    sfunc = add_histograms,
+    stype = bigint[],
+    initcond = '{}'
+);
+
+create table trace_rollup_minute
+(
+    minute              ubigint    not null,
+    service_name        identifier not null,
+    top_level_span_name identifier not null,
+    count               ubigint    not null,
+    error_count         ubigint    not null,
+    warning_count       ubigint    not null,
+    count_with_warnings ubigint    not null,
+    max_duration        ubigint    not null,
+    longest_trace_id    ubigint    not null,
+    duration_histogram  bigint[]   not null,
+    primary key (service_name, top_level_span_name, minute)
+);
+comment on table trace_rollup_minute is 'Per minute trace statistics maintained on insertion, kept for longer than the raw traces';
+create index on trace_rollup_minute (minute);
+
+create table span_rollup_minute
+(
+    minute              ubigint    not null,
+    service_name        identifier not null,
+    top_level_span_name identifier not null,
+    span_name           identifier not null,
+    count               ubigint    not null,
+    error_count         ubigint    not null,
+    warning_count       ubigint    not null,
+    count_with_warnings ubigint    not null,
+    max_duration        ubigint    not null,
+    longest_trace_id    ubigint    not null,
+    duration_histogram  bigint[]   not null,
+    primary key (service_name, top_level_span_name, span_name, minute)
+);
+comment on table span_rollup_minute is 'Per minute span statistics maintained on insertion, kept for longer than the raw traces';
+create index on span_rollup_minute (minute);
+
+create table error_group
+(
+    fingerprint      text       primary key,
+    service_name     identifier not null,
+    span_name        identifier not null,
+    message          text_value not null,
+    first_seen       ubigint    not null,
+    last_seen        ubigint    not null,
+    occurrences      ubigint    not null,
+    sample_trace_ids bigint[]   not null,
+    resolved_at      ubigint
+);
+comment on table error_group is 'Error events and error status spans grouped by service, span name and normalized message, maintained on insertion';
+comment on column error_group.resolved_at is 'Null while open, an occurrence after it reopens the group';
+create index on error_group (last_seen);
+
+create table saved_search
+(
+    id          bigserial primary key,
+    name        identifier not null,
+    owner       identifier not null,
+    description text_value,
+    state       jsonb      not null,
+    updated_at  ubigint    not null,
+    unique (owner, name)
+);
+comment on column saved_search.state is 'GridSearchState from api-structs, everything needed to reopen the same trace grid';
+
+create table search_subscription
+(
+    id                   bigserial primary key,
+    saved_search_id      bigint  not null unique references saved_search (id) on delete cascade,
+    min_interval_seconds ubigint not null,
+    created_at           ubigint not null
+);
+comment on table search_subscription is 'Saved searches evaluated against newly stored traces, matches are sent by the notifier';
+comment on column search_subscription.min_interval_seconds is 'At most one notification per interval, matches in between are sent with the next one';
@@ -1,12 +1,18 @@
+use crate::otel_trace_processing::rollup::{histogram_percentile, NANOS_IN_1_MINUTE};
+use crate::otel_trace_processing::LiveTailSender;
 use crate::BYTES_IN_1MB;
-use api_structs::{ApiTraceGridRow, SearchFor, Span, Summary, SummaryRequest};
+use api_structs::{
+    ApiTraceGrid, ApiTraceGridRow, AttributeValuesRequest, Filter, OrderBy, SearchFor, Span,
+    Summary, SummaryRequest, TextMatch, TraceGridCursor,
+};
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use chrono::NaiveDateTime;
+use search_filter::{FilterTextMatches, QueryReadyFilter, TextCondition};
 use serde::{Deserialize, Serialize};
 use sqlx::types::JsonValue;
-use sqlx::{Error, FromRow, PgPool};
+use sqlx::{Error, FromRow, PgPool, Postgres, QueryBuilder};
 use std::collections::{HashMap, HashSet};
 use std::io::Read;
 use std::ops::Deref;
@@ -14,67 +20,105 @@ use tokio::task::JoinHandle;
 use tracing::instrument::Instrumented;
 use tracing::{error, info, info_span, instrument, Instrument};
 
+mod errors;
+mod export;
+mod heatmap;
+mod import;
+mod live_tail;
+mod logs;
+mod profile;
+mod query_language;
+mod saved_searches;
+mod search_filter;
+mod storage_usage;
+mod subscriptions;
+mod text_search;
+mod time_series;
+mod trace_analysis;
+
+pub use subscriptions::start_subscriptions_task;
+
 #[derive(Debug, Clone, Serialize)]
 struct RawDbSummary {
     service_name: String,
     top_level_span_name: String,
     total_traces: i64,
     total_traces_with_error: i64,
+    total_warnings: i64,
+    traces_with_warnings: i64,
     longest_trace_id: i64,
     longest_trace_duration: i64,
-    longest_trace_duration_service_name: String,
+    duration_histogram: Vec<i64>,
 }
 
+/// Answered from the per minute rollups, so the window is rounded out to whole minutes and the
+/// percentiles are approximated from the duration histogram
 #[instrument(skip_all)]
 async fn traces_summary(
     axum::extract::State(con): axum::extract::State<PgPool>,
-    _summary_request: Json<SummaryRequest>,
+    Json(summary_request): Json<SummaryRequest>,
 ) -> Result<Json<Vec<Summary>>, ApiError> {
+    let from = u64_to_naive_date_time(summary_request.from_date_unix)?.timestamp_nanos();
+    let to = u64_to_naive_date_time(summary_request.to_date_unix)?.timestamp_nanos();
+    if from > to {
+        return Err(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: "Start date must be before the end date".to_string(),
+        });
+    }
     let summary_data = sqlx::query_as!(
         RawDbSummary,
-        "with trace_services_summary as (select trace.service_name,
-                                       trace.top_level_span_name,
-                                       COUNT(trace.timestamp)        as total_traces,
-                                       SUM((has_errors = true)::INT) as total_traces_with_error,
-                                       MAX(duration)
-                                                                     as longest_trace_duration
-                                from trace
-                                group by trace.service_name, trace.top_level_span_name)
-select trace_services_summary.service_name,
-       trace_services_summary.top_level_span_name,
-       total_traces            as \"total_traces!\",
-       total_traces_with_error as \"total_traces_with_error!\",
-       trace.id                as \"longest_trace_id!\",
-       trace.service_name      as \"longest_trace_duration_service_name!\",
-       trace.duration          as \"longest_trace_duration!\"
-from trace_services_summary
-         join lateral (select id, trace.service_name, duration
-                       from trace
-                       where trace.service_name = trace_services_summary.service_name
-                         and trace.top_level_span_name = trace_services_summary.top_level_span_name
-                         and trace.duration = trace_services_summary.longest_trace_duration
-                       limit 1) trace on true
-order by service_name, total_traces_with_error desc, total_traces desc;"
+        "select service_name::TEXT                                            as \"service_name!\",
+       top_level_span_name::TEXT                                             as \"top_level_span_name!\",
+       SUM(count)::BIGINT                                                    as \"total_traces!\",
+       SUM(error_count)::BIGINT                                              as \"total_traces_with_error!\",
+       SUM(warning_count)::BIGINT                                            as \"total_warnings!\",
+       SUM(count_with_warnings)::BIGINT                                      as \"traces_with_warnings!\",
+       (array_agg(longest_trace_id order by max_duration desc))[1]::BIGINT  as \"longest_trace_id!\",
+       MAX(max_duration)::BIGINT                                             as \"longest_trace_duration!\",
+       sum_histograms(duration_histogram)                                    as \"duration_histogram!\"
+from trace_rollup_minute
+where minute > $1::BIGINT - $3::BIGINT
+  and minute <= $2::BIGINT
+group by service_name, top_level_span_name
+order by service_name, \"total_traces_with_error!\" desc, \"total_traces!\" desc;",
+        from,
+        to,
+        NANOS_IN_1_MINUTE
     )
     .fetch_all(&con)
     .await?;
+    let window_minutes = ((to - from) as f64 / 60_000_000_000.).max(1. / 60.);
     let summary_data: Vec<Summary> = summary_data
         .into_iter()
-        .map(|s| Summary {
-            service_name: s.service_name,
-            top_level_span_name: s.top_level_span_name,
-            total_traces: s.total_traces,
-            total_traces_with_error: s.total_traces_with_error,
-            longest_trace_id: u64::try_from(s.longest_trace_id).expect("trace_id to fit u64"),
-            longest_trace_duration: u64::try_from(s.longest_trace_duration)
-                .expect("trace duration to fit u64"),
+        .map(|s| {
+            let longest_trace_duration =
+                u64::try_from(s.longest_trace_duration).expect("trace duration to fit u64");
+            let percentile = |percentile: f64| {
+                histogram_percentile(&s.duration_histogram, percentile).min(longest_trace_duration)
+            };
+            Summary {
+                p50_duration: percentile(0.5),
+                p90_duration: percentile(0.9),
+                p99_duration: percentile(0.99),
+                service_name: s.service_name,
+                top_level_span_name: s.top_level_span_name,
+                total_traces: s.total_traces,
+                total_traces_with_error: s.total_traces_with_error,
+                error_rate: s.total_traces_with_error as f64 / s.total_traces.max(1) as f64,
+                total_warnings: s.total_warnings,
+                traces_with_warnings: s.traces_with_warnings,
+                throughput_per_minute: s.total_traces as f64 / window_minutes,
+                longest_trace_id: u64::try_from(s.longest_trace_id).expect("trace_id to fit u64"),
+                longest_trace_duration,
+            }
         })
         .collect();
     Ok(Json(summary_data))
 }
 
 #[instrument(skip_all)]
-pub fn start(con: PgPool, api_port: u16) -> JoinHandle<()> {
+pub fn start(con: PgPool, api_port: u16, live_tail: LiveTailSender) -> JoinHandle<()> {
     info!("Starting API");
     if std::fs::read("./tracer-ui/dist/index.html").is_err() {
         panic!("Failed to read ./tracer-ui/dist/index.html");
@@ -89,13 +133,73 @@ pub fn start(con: PgPool, api_port: u16) -> JoinHandle<()> {
             "/api/traces-grid",
             axum::routing::post(traces_grid_with_search),
         )
+        .route("/api/live-tail", axum::routing::get(live_tail::live_tail))
         .route("/api/summary", axum::routing::post(traces_summary))
+        .route(
+            "/api/query",
+            axum::routing::post(query_language::traces_query),
+        )
+        .route(
+            "/api/storage-usage",
+            axum::routing::post(storage_usage::storage_usage),
+        )
+        .route("/api/heatmap", axum::routing::post(heatmap::heatmap))
+        .route("/api/profile", axum::routing::post(profile::profile))
+        .route("/api/logs", axum::routing::post(logs::search_logs))
+        .route("/api/errors", axum::routing::post(errors::error_groups))
+        .route(
+            "/api/errors/resolve",
+            axum::routing::post(errors::resolve_error_group),
+        )
+        .route(
+            "/api/saved-searches",
+            axum::routing::get(saved_searches::saved_searches).post(saved_searches::save_search),
+        )
+        .route(
+            "/api/saved-searches/delete",
+            axum::routing::post(saved_searches::delete_saved_search),
+        )
+        .route(
+            "/api/subscriptions",
+            axum::routing::get(subscriptions::search_subscriptions).post(subscriptions::subscribe),
+        )
+        .route(
+            "/api/subscriptions/delete",
+            axum::routing::post(subscriptions::unsubscribe),
+        )
+        .route(
+            "/api/import",
+            axum::routing::post(import::import).layer(axum::extract::DefaultBodyLimit::max(
+                import::MAX_IMPORT_FILE_SIZE_BYTES,
+            )),
+        )
+        .route(
+            "/api/time-series",
+            axum::routing::post(time_series::time_series),
+        )
         .route("/api/trace", axum::routing::get(get_single_trace))
+        .route(
+            "/api/trace/critical-path",
+            axum::routing::get(trace_analysis::get_critical_path),
+        )
+        .route(
+            "/api/trace/compare",
+            axum::routing::get(trace_analysis::get_comparison),
+        )
+        .route(
+            "/api/trace/self-time",
+            axum::routing::get(trace_analysis::get_self_time),
+        )
         .route(
             "/api/autocomplete-data",
             axum::routing::post(get_autocomplete_data),
         )
+        .route(
+            "/api/autocomplete-data/values",
+            axum::routing::post(get_attribute_values_autocomplete_data),
+        )
         .with_state(con)
+        .layer(axum::Extension(live_tail))
         .fallback_service(serve_ui)
         .layer(tower_http::cors::CorsLayer::very_permissive());
     tokio::spawn(async move {
@@ -128,9 +232,15 @@ struct GridErrorSample {
     event_timestamp_unix_ms: i64,
 }
 
+/// Escapes the `like` wildcards and the escape character itself, so the text matches literally
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
 fn into_escaped_like_search(search_term: &str) -> String {
-    let search_term = search_term.replace('%', "\\%");
-    format!("%{}%", search_term)
+    format!("%{}%", escape_like(search_term))
 }
 
 const MAX_GRID_COL_LEN: usize = 30;
@@ -156,6 +266,13 @@ fn cut_matching_text_part(text: String, searched_term: String) -> String {
     }
 }
 
+#[cfg(test)]
+#[test]
+fn like_wildcards_are_escaped() {
+    assert_eq!(escape_like(r"50%_off\now"), r"50\%\_off\\now");
+    assert_eq!(into_escaped_like_search("a_b"), r"%a\_b%");
+}
+
 #[cfg(test)]
 #[test]
 fn get_matching_text_part_works() {
@@ -168,6 +285,20 @@ fn get_matching_text_part_works() {
     );
 }
 
+/// Span without attributes or events, for the tests of the trace analyses and exports
+#[cfg(test)]
+fn test_span(id: u64, parent_id: Option<u64>, name: &str, timestamp: u64, duration: u64) -> Span {
+    Span {
+        id,
+        timestamp,
+        duration,
+        parent_id,
+        name: name.to_string(),
+        key_values: vec![],
+        events: vec![],
+    }
+}
+
 fn trim_and_highlight_search_term(
     specific_searched_term: &str,
     generic_search: &str,
@@ -201,16 +332,19 @@ struct QueryReadyParameters {
     max_duration: Option<i64>,
     min_warn_count: Option<i64>,
     only_errors: Option<bool>,
-    top_level_span: Option<String>,
-    span_name: Option<String>,
-    key: Option<String>,
-    value: Option<String>,
-    event_name: Option<String>,
-    service_name: Option<String>,
+    top_level_span: Option<TextCondition>,
+    filter: Option<QueryReadyFilter>,
+    service_name: Option<TextCondition>,
+    order_by: OrderBy,
+    /// Sort value and trace id of the last row already returned
+    cursor: Option<(i64, i64)>,
+    /// Only these traces, used to check which newly stored traces match a search
+    trace_ids: Option<Vec<i64>>,
 }
 
 impl QueryReadyParameters {
-    pub fn from_search(search: SearchFor) -> Result<Self, ApiError> {
+    /// Also checks the search regexes against the DB, see [search_filter::validate_regexes]
+    pub async fn from_search(con: &PgPool, search: SearchFor) -> Result<Self, ApiError> {
         let from = u64_to_naive_date_time(search.from_date_unix)?;
         let to = u64_to_naive_date_time(search.to_date_unix)?;
         let min_duration_micros = i64::try_from(search.min_duration).map_err(|_| ApiError {
@@ -226,48 +360,39 @@ impl QueryReadyParameters {
                 })
             })
             .transpose()?;
-        let service_name = if search.service_name.is_empty() {
-            None
-        } else {
-            Some(search.service_name)
-        };
-        let top_level_span = if search.top_level_span.is_empty() {
-            None
-        } else {
-            Some(search.top_level_span)
-        };
+        let service_name = TextCondition::new(search.service_name_match, search.service_name);
+        let top_level_span = TextCondition::new(search.top_level_span_match, search.top_level_span);
         let min_warns = if search.min_warns > 0 {
             Some(search.min_warns as i64)
         } else {
             None
         };
         let only_errors = if search.only_errors { Some(true) } else { None };
-        let span_name = if search.span.is_empty() {
-            None
-        } else {
-            Some(search.span)
-        };
-        let event_name = if search.event_name.is_empty() {
-            None
-        } else {
-            Some(into_escaped_like_search(&search.event_name))
-        };
-        let key = if search.key.is_empty() {
-            None
-        } else {
-            Some(search.key)
-        };
-        let value = if search.value.is_empty() {
-            None
-        } else {
-            Some(into_escaped_like_search(&search.value))
-        };
-        Ok(QueryReadyParameters {
-            key,
-            value,
+        let filter = QueryReadyFilter::from_filters(
+            search.filters,
+            FilterTextMatches {
+                span_name: search.span_name_match,
+                event: search.event_match,
+                attribute_value: search.attribute_value_match,
+            },
+        )?;
+        let cursor = search
+            .cursor
+            .map(
+                |cursor| match (i64::try_from(cursor.sort_value), i64::try_from(cursor.id)) {
+                    (Ok(sort_value), Ok(id)) => Ok((sort_value, id)),
+                    _ => Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Invalid cursor".to_string(),
+                    }),
+                },
+            )
+            .transpose()?;
+        let query_params = QueryReadyParameters {
+            filter,
+            order_by: search.order_by,
+            cursor,
             top_level_span,
-            span_name,
-            event_name,
             from,
             to,
             min_duration: min_duration_micros,
@@ -275,7 +400,67 @@ impl QueryReadyParameters {
             min_warn_count: min_warns,
             service_name,
             only_errors,
-        })
+            trace_ids: None,
+        };
+        let mut regexes = vec![];
+        if let Some(regex) = query_params
+            .service_name
+            .as_ref()
+            .and_then(TextCondition::regex)
+        {
+            regexes.push(("service name", regex));
+        }
+        if let Some(regex) = query_params
+            .top_level_span
+            .as_ref()
+            .and_then(TextCondition::regex)
+        {
+            regexes.push(("top level span", regex));
+        }
+        if let Some(filter) = &query_params.filter {
+            regexes.extend(filter.regexes());
+        }
+        search_filter::validate_regexes(con, &regexes).await?;
+        Ok(query_params)
+    }
+
+    /// Pushes the conditions on the trace table, including the span, event and attribute filters
+    fn push_trace_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query.push("trace.timestamp >= ");
+        query.push_bind(self.from.timestamp_nanos());
+        query.push(" and trace.timestamp <= ");
+        query.push_bind(self.to.timestamp_nanos());
+        query.push(" and trace.duration >= ");
+        query.push_bind(self.min_duration);
+        if let Some(max_duration) = self.max_duration {
+            query.push(" and trace.duration <= ");
+            query.push_bind(max_duration);
+        }
+        if let Some(service_name) = &self.service_name {
+            query.push(" and ");
+            service_name.push_condition(query, "trace.service_name");
+        }
+        if let Some(only_errors) = self.only_errors {
+            query.push(" and trace.has_errors = ");
+            query.push_bind(only_errors);
+        }
+        if let Some(top_level_span) = &self.top_level_span {
+            query.push(" and ");
+            top_level_span.push_condition(query, "trace.top_level_span_name");
+        }
+        if let Some(min_warn_count) = self.min_warn_count {
+            query.push(" and trace.warning_count >= ");
+            query.push_bind(min_warn_count);
+        }
+        if let Some(filter) = &self.filter {
+            query.push(" and ");
+            filter.push_condition(query);
+        }
+        if let Some(trace_ids) = &self.trace_ids {
+            query.push(" and trace.id = any(");
+            query.push_bind(trace_ids.clone());
+            query.push(")");
+        }
     }
 }
 
@@ -291,78 +476,119 @@ pub struct RawDbTraceGrid {
     key: Option<String>,
     value: Option<String>,
     span_name: Option<String>,
+    span_id: Option<i64>,
     event_name: Option<String>,
+    /// Rank of the matched event, 0 without an event search
+    relevance: i64,
+    imported: bool,
 }
 
+impl RawDbTraceGrid {
+    fn into_api_row(self, searched_span: &str, searched_service: &str) -> ApiTraceGridRow {
+        ApiTraceGridRow {
+            id: u64::try_from(self.id).expect("trace_id to fit u64"),
+            has_errors: self.has_errors,
+            service_name: self.service_name,
+            top_level_span_name: self.top_level_span_name,
+            duration_ns: u64::try_from(self.duration).expect("duration to fit u64"),
+            timestamp: u64::try_from(self.timestamp).expect("creation timestamp to fit u64"),
+            key: self.key,
+            value: self.value,
+            span: self
+                .span_name
+                .map(|e| trim_and_highlight_search_term(searched_span, searched_service, e)),
+            span_id: self
+                .span_id
+                .map(|span_id| u64::try_from(span_id).expect("span_id to fit u64")),
+            event: self.event_name,
+            warning_count: u32::try_from(self.warning_count).expect("warning count to fit u32"),
+            imported: self.imported,
+        }
+    }
+}
+
+/// The span name the grid highlights, from the first span name filter
+fn searched_span_name(search_for: &SearchFor) -> &str {
+    search_for
+        .filters
+        .iter()
+        .flat_map(|f| f.positive_conditions())
+        .find_map(|f| match f {
+            Filter::SpanName(name) => Some(name.as_str()),
+            Filter::Span(span) if !span.name.is_empty() => Some(span.name.as_str()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+const GRID_PAGE_SIZE: usize = 100;
+
+/// Returns up to [GRID_PAGE_SIZE] + 1 rows, the extra one only tells there is a next page
 #[instrument(skip_all)]
 pub async fn get_grid_data(
     con: &PgPool,
-    search: SearchFor,
+    query_params: &QueryReadyParameters,
 ) -> Result<Vec<RawDbTraceGrid>, ApiError> {
-    let query_params = QueryReadyParameters::from_search(search)?;
     info!("Query Parameters: {:#?}", query_params);
-    let res = sqlx::query_as!(
-        RawDbTraceGrid,
-        "select distinct on (trace.timestamp, trace.id) trace.id,
-                                                   trace.timestamp,
-                                                   trace.duration,
-                                                   trace.service_name,
-                                                   trace.has_errors,
-                                                   trace.warning_count,
-                                                   trace.top_level_span_name,
-                                                   COALESCE(event_key_value.key, span_key_value.key)   as \"key?\",
-                                                   COALESCE(event_key_value.value, span_key_value.value)  as \"value?\",
-                                                   span.name            as \"span_name?\",
-                                                   event.name           as \"event_name?\"
-    from trace
-             left join span_key_value
-                       on ($1::TEXT is not null and span_key_value.key = $1::TEXT)
-                           and ($2::TEXT is null or span_key_value.value ilike $2::TEXT)
-                           and span_key_value.trace_id = trace.id
-             left join event_key_value
-                       on ($1::TEXT is not null and event_key_value.key = $1::TEXT)
-                           and ($2::TEXT is null or event_key_value.value ilike $2::TEXT)
-                           and event_key_value.trace_id = trace.id
-             left join span
-                       on ($3::TEXT is not null and span.name = $3::TEXT)
-                           and span.trace_id = trace.id
-             left join event
-                       on ($4::TEXT is not null and event.name ilike $4::TEXT)
-                           and event.trace_id = trace.id
-    where
-      -- make sure if the user provided values, we treat is as an inner join
-        ($1::TEXT is null or (span_key_value.key is not null or event_key_value.key is not null))
-      and ($3::TEXT is null or span.id is not null)
-      and ($4::TEXT is null or event.timestamp is not null)
-      -- common filters
-      and trace.timestamp >= $5::BIGINT
-      and trace.timestamp <= $6::BIGINT
-      and trace.duration >= $7::BIGINT
-      and ($8::BIGINT is null or trace.duration <= $8::BIGINT)
-      and ($9::TEXT is null or trace.service_name = $9::TEXT)
-      and ($10::BOOL is null or trace.has_errors = $10::BOOL)
-      and ($11::TEXT is null or trace.top_level_span_name = $11::TEXT)
-      and ($12::BIGINT is null or trace.warning_count >= $12::BIGINT)
-    order by trace.timestamp desc
-    limit 100;",
-        query_params.key,
-        query_params.value,
-        query_params.span_name,
-        query_params.event_name,
-        query_params.from.timestamp_nanos(),
-        query_params.to.timestamp_nanos(),
-        query_params.min_duration,
-        query_params.max_duration,
-        query_params.service_name,
-        query_params.only_errors,
-        query_params.top_level_span,
-        query_params.min_warn_count,
-    )
-    .fetch_all(con)
-    .await?;
+    let mut query = QueryBuilder::new(
+        "select trace.id,
+       trace.timestamp,
+       trace.duration,
+       trace.service_name::TEXT,
+       trace.has_errors,
+       trace.warning_count,
+       trace.top_level_span_name::TEXT,
+       matched_attribute.key,
+       matched_attribute.value,
+       matched_span.name as span_name,
+       matched_span.id as span_id,
+       matched_event.name as event_name,
+       coalesce(matched_event.relevance, 0) as relevance,
+       trace.imported
+    from trace",
+    );
+    QueryReadyFilter::push_matched_attribute_join(query_params.filter.as_ref(), &mut query);
+    QueryReadyFilter::push_matched_span_and_event_joins(query_params.filter.as_ref(), &mut query);
+    query.push(" where ");
+    query_params.push_trace_conditions(&mut query);
+    let (sort_column, direction, cursor_comparison) = match query_params.order_by {
+        OrderBy::DateDesc => ("trace.timestamp", "desc", "<"),
+        OrderBy::DateAsc => ("trace.timestamp", "asc", ">"),
+        OrderBy::DurationDesc => ("trace.duration", "desc", "<"),
+        OrderBy::DurationAsc => ("trace.duration", "asc", ">"),
+        OrderBy::RelevanceDesc => ("coalesce(matched_event.relevance, 0)", "desc", "<"),
+    };
+    if let Some((sort_value, id)) = query_params.cursor {
+        query.push(format!(
+            " and ({sort_column}, trace.id) {cursor_comparison} ("
+        ));
+        query.push_bind(sort_value);
+        query.push(", ");
+        query.push_bind(id);
+        query.push(")");
+    }
+    query.push(format!(
+        " order by {sort_column} {direction}, trace.id {direction} limit {}",
+        GRID_PAGE_SIZE + 1
+    ));
+    let res = query
+        .build_query_as::<RawDbTraceGrid>()
+        .fetch_all(con)
+        .await?;
     Ok(res)
 }
 
+#[instrument(skip_all)]
+async fn get_grid_count(
+    con: &PgPool,
+    query_params: &QueryReadyParameters,
+) -> Result<i64, ApiError> {
+    let mut query = QueryBuilder::new("select count(*) from trace where ");
+    query_params.push_trace_conditions(&mut query);
+    let (count,) = query.build_query_as::<(i64,)>().fetch_one(con).await?;
+    Ok(count)
+}
+
 #[instrument(skip_all)]
 async fn get_service_names_autocomplete_data(
     con: &PgPool,
@@ -393,7 +619,11 @@ async fn get_top_level_span_autocomplete_data(
     con: &PgPool,
     query_params: &QueryReadyParameters,
 ) -> Result<Vec<String>, ApiError> {
-    if let Some(service_name) = &query_params.service_name {
+    if let Some(service_name) = query_params
+        .service_name
+        .as_ref()
+        .and_then(TextCondition::exact)
+    {
         let top_level_spans = sqlx::query_scalar!(
             "select distinct trace.top_level_span_name
                 from trace
@@ -430,9 +660,17 @@ async fn get_span_and_keys_autocomplete_data(
     con: &PgPool,
     query_params: &QueryReadyParameters,
 ) -> Result<SpanAndKeys, ApiError> {
-    if let (Some(service_name), Some(top_level_span_name)) =
-        (&query_params.service_name, &query_params.top_level_span)
-    {
+    // only narrowed down once both are picked
+    if let (Some(service_name), Some(top_level_span_name)) = (
+        query_params
+            .service_name
+            .as_ref()
+            .and_then(TextCondition::exact),
+        query_params
+            .top_level_span
+            .as_ref()
+            .and_then(TextCondition::exact),
+    ) {
         let spans = sqlx::query_scalar!(
             "select distinct span.name
                 from trace
@@ -524,12 +762,80 @@ async fn get_span_and_keys_autocomplete_data(
     }
 }
 
+/// How many suggestions are returned for attribute values and event names
+const FREQUENT_SUGGESTIONS_LIMIT: i64 = 100;
+
+#[instrument(skip_all)]
+async fn get_event_names_autocomplete_data(
+    con: &PgPool,
+    query_params: &QueryReadyParameters,
+) -> Result<Vec<String>, ApiError> {
+    // only narrowed down once both are picked, like the spans and keys
+    let both_picked = [&query_params.service_name, &query_params.top_level_span]
+        .into_iter()
+        .all(|condition| condition.as_ref().and_then(TextCondition::exact).is_some());
+    if !both_picked {
+        return Ok(vec![]);
+    }
+    let mut query = QueryBuilder::new(
+        "select event.name from trace inner join event on event.trace_id = trace.id where ",
+    );
+    query_params.push_trace_conditions(&mut query);
+    query.push(" group by event.name order by count(*) desc, event.name limit ");
+    query.push_bind(FREQUENT_SUGGESTIONS_LIMIT);
+    let names = query.build_query_as::<(String,)>().fetch_all(con).await?;
+    Ok(names.into_iter().map(|(name,)| name).collect())
+}
+
+/// Values observed for a key in the span and event attributes of the traces matching the
+/// search, most frequent first
+#[instrument(skip_all)]
+async fn get_attribute_values_autocomplete_data(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    request: Json<AttributeValuesRequest>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    let AttributeValuesRequest {
+        search_for,
+        key,
+        value,
+    } = request.0;
+    if key.is_empty() {
+        return Ok(Json(vec![]));
+    }
+    let query_params = QueryReadyParameters::from_search(&con, search_for).await?;
+    let value = TextCondition::new(TextMatch::Contains, value);
+    let mut query = QueryBuilder::new("select value from (");
+    for (idx, table) in ["span_key_value", "event_key_value"]
+        .into_iter()
+        .enumerate()
+    {
+        if idx > 0 {
+            query.push(" union all ");
+        }
+        query.push(format!(
+            "select {table}.value from trace inner join {table} on {table}.trace_id = trace.id \
+            where {table}.user_generated = true and {table}.key = "
+        ));
+        query.push_bind(key.clone());
+        if let Some(value) = &value {
+            query.push(" and ");
+            value.push_condition(&mut query, &format!("{table}.value"));
+        }
+        query.push(" and ");
+        query_params.push_trace_conditions(&mut query);
+    }
+    query.push(") as observed group by value order by count(*) desc, value limit ");
+    query.push_bind(FREQUENT_SUGGESTIONS_LIMIT);
+    let values = query.build_query_as::<(String,)>().fetch_all(&con).await?;
+    Ok(Json(values.into_iter().map(|(value,)| value).collect()))
+}
+
 #[instrument(skip_all)]
 async fn get_autocomplete_data(
     axum::extract::State(con): axum::extract::State<PgPool>,
     search_for: Json<SearchFor>,
 ) -> Result<Json<api_structs::KeySpans>, ApiError> {
-    let query_params = QueryReadyParameters::from_search(search_for.deref().clone())?;
+    let query_params = QueryReadyParameters::from_search(&con, search_for.deref().clone()).await?;
     let closure_query_params = query_params.clone();
     let closure_con = con.clone();
     let service_names_fut: Instrumented<JoinHandle<Result<Vec<String>, ApiError>>> =
@@ -551,49 +857,78 @@ async fn get_autocomplete_data(
             get_span_and_keys_autocomplete_data(&closure_con, &closure_query_params).await
         })
         .in_current_span();
+    let closure_query_params = query_params.clone();
+    let closure_con = con.clone();
+    let event_names_fut: Instrumented<JoinHandle<Result<Vec<String>, ApiError>>> =
+        tokio::spawn(async move {
+            get_event_names_autocomplete_data(&closure_con, &closure_query_params).await
+        })
+        .in_current_span();
 
-    let (service_names, top_level_spans, spans_and_keys) =
-        tokio::try_join!(service_names_fut, top_lvl_span_fut, span_and_keys_fut).map_err(|e| {
-            error!("{:?}", e);
-            ApiError {
-                code: StatusCode::INTERNAL_SERVER_ERROR,
-                message: "Internal error!".to_string(),
-            }
-        })?;
+    let (service_names, top_level_spans, spans_and_keys, event_names) = tokio::try_join!(
+        service_names_fut,
+        top_lvl_span_fut,
+        span_and_keys_fut,
+        event_names_fut
+    )
+    .map_err(|e| {
+        error!("{:?}", e);
+        ApiError {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Internal error!".to_string(),
+        }
+    })?;
     let spans_and_keys = spans_and_keys?;
     Ok(Json(api_structs::KeySpans {
         service_names: service_names?,
         top_level_spans: top_level_spans?,
         spans: spans_and_keys.spans,
         keys: spans_and_keys.keys,
+        event_names: event_names?,
     }))
 }
 #[instrument(skip_all)]
 async fn traces_grid_with_search(
     axum::extract::State(con): axum::extract::State<PgPool>,
     search_for: Json<SearchFor>,
-) -> Result<Json<Vec<ApiTraceGridRow>>, ApiError> {
-    let resp = get_grid_data(&con, search_for.0.clone()).await?;
-
+) -> Result<Json<ApiTraceGrid>, ApiError> {
+    let query_params = QueryReadyParameters::from_search(&con, search_for.0.clone()).await?;
+    let (mut resp, total_count) = if query_params.cursor.is_none() {
+        let (resp, total_count) = tokio::try_join!(
+            get_grid_data(&con, &query_params),
+            get_grid_count(&con, &query_params)
+        )?;
+        (
+            resp,
+            Some(u64::try_from(total_count).expect("count to fit u64")),
+        )
+    } else {
+        (get_grid_data(&con, &query_params).await?, None)
+    };
+    let next_cursor = if resp.len() > GRID_PAGE_SIZE {
+        resp.truncate(GRID_PAGE_SIZE);
+        resp.last().map(|last| TraceGridCursor {
+            sort_value: u64::try_from(match query_params.order_by {
+                OrderBy::DateDesc | OrderBy::DateAsc => last.timestamp,
+                OrderBy::DurationDesc | OrderBy::DurationAsc => last.duration,
+                OrderBy::RelevanceDesc => last.relevance,
+            })
+            .expect("sort value to fit u64"),
+            id: u64::try_from(last.id).expect("trace_id to fit u64"),
+        })
+    } else {
+        None
+    };
+    let searched_span = searched_span_name(&search_for);
     let resp: Vec<ApiTraceGridRow> = resp
         .into_iter()
-        .map(|e| ApiTraceGridRow {
-            id: u64::try_from(e.id).expect("trace_id to fit u64"),
-            has_errors: e.has_errors,
-            service_name: e.service_name,
-            top_level_span_name: e.top_level_span_name,
-            duration_ns: u64::try_from(e.duration).expect("duration to fit u64"),
-            timestamp: u64::try_from(e.timestamp).expect("creation timestamp to fit u64"),
-            key: e.key,
-            value: e.value,
-            span: e.span_name.map(|e| {
-                trim_and_highlight_search_term(&search_for.span, &search_for.service_name, e)
-            }),
-            event: e.event_name,
-            warning_count: u32::try_from(e.warning_count).expect("warning count to fit u32"),
-        })
+        .map(|e| e.into_api_row(searched_span, &search_for.service_name))
         .collect();
-    Ok(Json(resp))
+    Ok(Json(ApiTraceGrid {
+        rows: resp,
+        total_count,
+        next_cursor,
+    }))
 }
 
 struct RawDbSpan {
@@ -628,12 +963,9 @@ impl From<sqlx::Error> for ApiError {
     }
 }
 
-#[instrument(skip_all, fields(trace_id=trace_id.trace_id))]
-async fn get_single_trace(
-    axum::extract::Query(trace_id): axum::extract::Query<api_structs::TraceId>,
-    axum::extract::State(con): axum::extract::State<PgPool>,
-) -> Result<impl IntoResponse, ApiError> {
-    let trace_id = trace_id.trace_id;
+/// All spans of the trace with their attributes and events, in no particular order
+#[instrument(skip_all, fields(trace_id=trace_id))]
+async fn get_trace_spans(con: &PgPool, trace_id: i64) -> Result<Vec<Span>, ApiError> {
     info!("Getting single trace: {trace_id}");
     let trace_from_db = sqlx::query_as!(RawDbSpan, "with event_kv_by_span_event as (select event_key_value.span_id,
                                                       event_key_value.event_id,
@@ -700,9 +1032,9 @@ async fn get_single_trace(
                from span_with_events;",
         trace_id,
     )
-            .fetch_all(&con)
+            .fetch_all(con)
             .await?;
-    let resp = trace_from_db
+    let spans = trace_from_db
         .into_iter()
         .map(|span| Span {
             id: u64::try_from(span.id).expect("span.id to fit u64"),
@@ -717,16 +1049,58 @@ async fn get_single_trace(
             events: serde_json::from_value(span.events).expect("db to generate valid json"),
         })
         .collect::<Vec<Span>>();
-    info!("Got it, compressing");
+    Ok(spans)
+}
+
+#[derive(Deserialize)]
+struct SingleTraceQuery {
+    trace_id: i64,
+    /// Takes precedence over the Accept header
+    format: Option<api_structs::ExportFormat>,
+}
+
+#[instrument(skip_all, fields(trace_id=query.trace_id))]
+async fn get_single_trace(
+    axum::extract::Query(query): axum::extract::Query<SingleTraceQuery>,
+    headers: axum::http::HeaderMap,
+    axum::extract::State(con): axum::extract::State<PgPool>,
+) -> Result<Response, ApiError> {
+    let spans = get_trace_spans(&con, query.trace_id).await?;
+    let Some(format) = query
+        .format
+        .or_else(|| export::format_from_accept_header(&headers))
+    else {
+        return Ok(brotli_json_response(&spans, None).into_response());
+    };
+    let service_name = sqlx::query_scalar!(
+        "select service_name::TEXT as \"service_name!\" from trace where id = $1;",
+        query.trace_id
+    )
+    .fetch_optional(&con)
+    .await?
+    .ok_or_else(|| ApiError {
+        code: StatusCode::NOT_FOUND,
+        message: format!("Trace {} not found", query.trace_id),
+    })?;
+    info!("Exporting as {format:?}");
+    let exported = export::export(format, query.trace_id, &service_name, &spans);
+    let file_name = format!("trace-{}.{}.json", query.trace_id, format.query_value());
+    Ok(brotli_json_response(&exported, Some(&file_name)).into_response())
+}
+
+/// Compressed here since responses can be tens of MBs, `attachment_file_name` makes browsers
+/// download it instead of displaying it
+fn brotli_json_response(body: &impl Serialize, attachment_file_name: Option<&str>) -> Response {
+    info!("Compressing");
     let lg_window_size = 21;
     let quality = 4;
-    let json = serde_json::to_string(&resp).expect("to be able to serialize response");
+    let json = serde_json::to_string(body).expect("to be able to serialize response");
     let mut input =
         brotli::CompressorReader::new(json.as_bytes(), 4096, quality as u32, lg_window_size as u32);
     let mut resp: Vec<u8> = Vec::with_capacity(10 * BYTES_IN_1MB);
     input.read_to_end(&mut resp).unwrap();
     info!("Compressed, sending");
-    Ok((
+    let mut response = (
         StatusCode::OK,
         [
             (
@@ -736,7 +1110,17 @@ async fn get_single_trace(
             (axum::http::header::CONTENT_ENCODING, "br"),
         ],
         resp,
-    ))
+    )
+        .into_response();
+    if let Some(file_name) = attachment_file_name {
+        let content_disposition = format!("attachment; filename=\"{file_name}\"")
+            .parse()
+            .expect("file name to be a valid header value");
+        response
+            .headers_mut()
+            .insert(axum::http::header::CONTENT_DISPOSITION, content_disposition);
+    }
+    response
 }
 
 async fn ready() -> impl IntoResponse {
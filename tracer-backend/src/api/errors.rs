@@ -0,0 +1,110 @@
+use crate::api::ApiError;
+use api_structs::{ErrorGroup, ErrorGroupsRequest, ResolveErrorGroupRequest};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::PgPool;
+use tracing::instrument;
+
+/// Enough for an inbox, the rest are older than what anyone looks at
+const MAX_ERROR_GROUPS: i64 = 500;
+
+struct RawDbErrorGroup {
+    fingerprint: String,
+    service_name: String,
+    span_name: String,
+    message: String,
+    first_seen: i64,
+    last_seen: i64,
+    occurrences: i64,
+    sample_trace_ids: Vec<i64>,
+    resolved_at: Option<i64>,
+}
+
+impl RawDbErrorGroup {
+    fn into_error_group(self) -> ErrorGroup {
+        ErrorGroup {
+            fingerprint: self.fingerprint,
+            service_name: self.service_name,
+            span_name: self.span_name,
+            message: self.message,
+            first_seen: u64::try_from(self.first_seen).expect("first_seen to fit u64"),
+            last_seen: u64::try_from(self.last_seen).expect("last_seen to fit u64"),
+            occurrences: u64::try_from(self.occurrences).expect("occurrences to fit u64"),
+            sample_trace_ids: self.sample_trace_ids,
+            resolved_at: self
+                .resolved_at
+                .map(|resolved_at| u64::try_from(resolved_at).expect("resolved_at to fit u64")),
+        }
+    }
+}
+
+/// Most recently seen first
+#[instrument(skip_all)]
+pub async fn error_groups(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<ErrorGroupsRequest>,
+) -> Result<Json<Vec<ErrorGroup>>, ApiError> {
+    let service_name = Some(request.service_name).filter(|s| !s.is_empty());
+    let groups = sqlx::query_as!(
+        RawDbErrorGroup,
+        r#"select fingerprint,
+       service_name::TEXT as "service_name!",
+       span_name::TEXT as "span_name!",
+       message::TEXT as "message!",
+       first_seen,
+       last_seen,
+       occurrences,
+       sample_trace_ids,
+       resolved_at
+from error_group
+where ($1::BOOLEAN or resolved_at is null)
+  and ($2::TEXT is null or service_name = $2::TEXT)
+order by last_seen desc
+limit $3::BIGINT"#,
+        request.include_resolved,
+        service_name,
+        MAX_ERROR_GROUPS
+    )
+    .fetch_all(&con)
+    .await?;
+    Ok(Json(
+        groups
+            .into_iter()
+            .map(RawDbErrorGroup::into_error_group)
+            .collect(),
+    ))
+}
+
+#[instrument(skip_all)]
+pub async fn resolve_error_group(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<ResolveErrorGroupRequest>,
+) -> Result<Json<ErrorGroup>, ApiError> {
+    let resolved_at = request
+        .resolved
+        .then(|| chrono::Utc::now().timestamp_nanos());
+    let group = sqlx::query_as!(
+        RawDbErrorGroup,
+        r#"update error_group
+set resolved_at = $2::BIGINT
+where fingerprint = $1::TEXT
+returning fingerprint,
+    service_name::TEXT as "service_name!",
+    span_name::TEXT as "span_name!",
+    message::TEXT as "message!",
+    first_seen,
+    last_seen,
+    occurrences,
+    sample_trace_ids,
+    resolved_at"#,
+        request.fingerprint,
+        resolved_at
+    )
+    .fetch_optional(&con)
+    .await?
+    .ok_or_else(|| ApiError {
+        code: StatusCode::NOT_FOUND,
+        message: format!("No error group with fingerprint {}", request.fingerprint),
+    })?;
+    Ok(Json(group.into_error_group()))
+}
@@ -0,0 +1,290 @@
+use api_structs::{Events, ExportFormat, KeyValue, Severity, Span};
+use axum::http::HeaderMap;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The format asked for in the Accept header, if any of the export media types is in it
+pub fn format_from_accept_header(headers: &HeaderMap) -> Option<ExportFormat> {
+    let accept = headers.get(axum::http::header::ACCEPT)?.to_str().ok()?;
+    ExportFormat::ALL
+        .into_iter()
+        .find(|format| accept.contains(format.media_type()))
+}
+
+pub fn export(format: ExportFormat, trace_id: i64, service_name: &str, spans: &[Span]) -> Value {
+    match format {
+        ExportFormat::OtlpJson => to_otlp_json(trace_id, service_name, spans),
+        ExportFormat::JaegerJson => to_jaeger_json(trace_id, service_name, spans),
+        ExportFormat::ChromeTrace => to_chrome_trace(spans),
+    }
+}
+
+/// Our trace ids are database ids, the original OTLP ones are not kept
+fn hex_trace_id(trace_id: i64) -> String {
+    format!("{trace_id:032x}")
+}
+
+fn hex_span_id(span_id: u64) -> String {
+    format!("{span_id:016x}")
+}
+
+fn severity_name(severity: &Severity) -> &'static str {
+    match severity {
+        Severity::Trace => "TRACE",
+        Severity::Debug => "DEBUG",
+        Severity::Info => "INFO",
+        Severity::Warn => "WARN",
+        Severity::Error => "ERROR",
+    }
+}
+
+fn has_errors(span: &Span) -> bool {
+    span.events
+        .iter()
+        .any(|e| matches!(e.severity, Severity::Error))
+}
+
+/// Events ingested from `tracing` already have it as an attribute
+fn has_level_attribute(event: &Events) -> bool {
+    event.key_values.iter().any(|kv| kv.key == "level")
+}
+
+fn otlp_attributes(key_values: &[KeyValue]) -> Vec<Value> {
+    key_values
+        .iter()
+        .map(|kv| json!({"key": kv.key, "value": {"stringValue": kv.value}}))
+        .collect()
+}
+
+fn otlp_event(event: &Events) -> Value {
+    let mut attributes = otlp_attributes(&event.key_values);
+    if !has_level_attribute(event) {
+        attributes.push(
+            json!({"key": "level", "value": {"stringValue": severity_name(&event.severity)}}),
+        );
+    }
+    json!({
+        "timeUnixNano": event.timestamp.to_string(),
+        "name": event.name,
+        "attributes": attributes,
+    })
+}
+
+fn to_otlp_json(trace_id: i64, service_name: &str, spans: &[Span]) -> Value {
+    let trace_id = hex_trace_id(trace_id);
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            // 1 is SPAN_KIND_INTERNAL, 2 STATUS_CODE_ERROR and 0 STATUS_CODE_UNSET
+            json!({
+                "traceId": trace_id,
+                "spanId": hex_span_id(span.id),
+                "parentSpanId": span.parent_id.map(hex_span_id).unwrap_or_default(),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": span.timestamp.to_string(),
+                "endTimeUnixNano": (span.timestamp + span.duration).to_string(),
+                "attributes": otlp_attributes(&span.key_values),
+                "events": span.events.iter().map(otlp_event).collect::<Vec<_>>(),
+                "status": {"code": if has_errors(span) { 2 } else { 0 }},
+            })
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{"key": "service.name", "value": {"stringValue": service_name}}]
+            },
+            "scopeSpans": [{
+                "scope": {"name": "tracer"},
+                "spans": spans,
+            }],
+        }]
+    })
+}
+
+fn jaeger_tags(key_values: &[KeyValue]) -> Vec<Value> {
+    key_values
+        .iter()
+        .map(|kv| json!({"key": kv.key, "type": "string", "value": kv.value}))
+        .collect()
+}
+
+fn to_jaeger_json(trace_id: i64, service_name: &str, spans: &[Span]) -> Value {
+    let trace_id = hex_trace_id(trace_id);
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let references: Vec<Value> = span
+                .parent_id
+                .map(|parent_id| {
+                    json!({"refType": "CHILD_OF", "traceID": trace_id, "spanID": hex_span_id(parent_id)})
+                })
+                .into_iter()
+                .collect();
+            let mut tags = jaeger_tags(&span.key_values);
+            if has_errors(span) {
+                tags.push(json!({"key": "error", "type": "bool", "value": true}));
+            }
+            let logs: Vec<Value> = span
+                .events
+                .iter()
+                .map(|event| {
+                    let mut fields =
+                        vec![json!({"key": "event", "type": "string", "value": event.name})];
+                    if !has_level_attribute(event) {
+                        fields.push(json!({"key": "level", "type": "string", "value": severity_name(&event.severity)}));
+                    }
+                    fields.extend(jaeger_tags(&event.key_values));
+                    json!({"timestamp": event.timestamp / 1_000, "fields": fields})
+                })
+                .collect();
+            json!({
+                "traceID": trace_id,
+                "spanID": hex_span_id(span.id),
+                "operationName": span.name,
+                "references": references,
+                "startTime": span.timestamp / 1_000,
+                "duration": span.duration / 1_000,
+                "tags": tags,
+                "logs": logs,
+                "processID": "p1",
+                "warnings": null,
+            })
+        })
+        .collect();
+    json!({
+        "data": [{
+            "traceID": trace_id,
+            "spans": spans,
+            "processes": {"p1": {"serviceName": service_name, "tags": []}},
+            "warnings": null,
+        }],
+        "total": 0,
+        "limit": 0,
+        "offset": 0,
+        "errors": null,
+    })
+}
+
+/// Complete events on the same thread must nest, so concurrent spans are spread over lanes
+/// (shown as threads). A span goes on its parent's lane when it fits there, otherwise on the
+/// first lane where it nests in whatever is still open.
+fn chrome_trace_lanes(spans: &[Span]) -> HashMap<u64, usize> {
+    let mut sorted: Vec<&Span> = spans.iter().collect();
+    sorted.sort_by_key(|span| (span.timestamp, std::cmp::Reverse(span.duration), span.id));
+    // end of the spans still open on each lane, innermost last
+    let mut lanes: Vec<Vec<u64>> = vec![];
+    let mut lane_by_span_id: HashMap<u64, usize> = HashMap::new();
+    for span in sorted {
+        let start = span.timestamp;
+        let end = span.timestamp + span.duration;
+        // spans are sorted by start, so whatever ended before this one is done for good
+        for open in lanes.iter_mut() {
+            while open.last().is_some_and(|open_end| *open_end <= start) {
+                open.pop();
+            }
+        }
+        let fits = |lane: &Vec<u64>| lane.last().map_or(true, |open_end| *open_end >= end);
+        let parent_lane = span
+            .parent_id
+            .and_then(|parent_id| lane_by_span_id.get(&parent_id).copied())
+            .filter(|lane| fits(&lanes[*lane]));
+        let lane = parent_lane
+            .or_else(|| lanes.iter().position(fits))
+            .unwrap_or_else(|| {
+                lanes.push(vec![]);
+                lanes.len() - 1
+            });
+        lanes[lane].push(end);
+        lane_by_span_id.insert(span.id, lane);
+    }
+    lane_by_span_id
+}
+
+fn to_chrome_trace(spans: &[Span]) -> Value {
+    let lanes = chrome_trace_lanes(spans);
+    let trace_start = spans.iter().map(|s| s.timestamp).min().unwrap_or(0);
+    // timestamps are in microseconds, relative to the trace start to keep the f64 precise
+    let micros = |nanos: u64| nanos.saturating_sub(trace_start) as f64 / 1_000.;
+    let args = |key_values: &[KeyValue]| -> serde_json::Map<String, Value> {
+        key_values
+            .iter()
+            .map(|kv| (kv.key.clone(), Value::String(kv.value.clone())))
+            .collect()
+    };
+    let mut trace_events = vec![];
+    for span in spans {
+        let tid = lanes.get(&span.id).copied().unwrap_or(0);
+        trace_events.push(json!({
+            "name": span.name,
+            "cat": "span",
+            "ph": "X",
+            "ts": micros(span.timestamp),
+            "dur": span.duration as f64 / 1_000.,
+            "pid": 1,
+            "tid": tid,
+            "args": args(&span.key_values),
+        }));
+        for event in &span.events {
+            trace_events.push(json!({
+                "name": event.name,
+                "cat": severity_name(&event.severity),
+                "ph": "i",
+                "s": "t",
+                "ts": micros(event.timestamp),
+                "pid": 1,
+                "tid": tid,
+                "args": args(&event.key_values),
+            }));
+        }
+    }
+    json!({
+        "traceEvents": trace_events,
+        "displayTimeUnit": "ms",
+    })
+}
+
+#[cfg(test)]
+use crate::api::test_span;
+
+#[cfg(test)]
+#[test]
+fn chrome_trace_lanes_keep_events_nested() {
+    // 2 and 3 are concurrent children of 1, 4 is a child of 3 and 5 runs after both
+    let spans = vec![
+        test_span(1, None, "span_1", 0, 100),
+        test_span(2, Some(1), "span_2", 10, 40),
+        test_span(3, Some(1), "span_3", 20, 50),
+        test_span(4, Some(3), "span_4", 30, 10),
+        test_span(5, Some(1), "span_5", 80, 10),
+    ];
+    let lanes = chrome_trace_lanes(&spans);
+    assert_eq!(lanes[&1], 0);
+    assert_eq!(lanes[&2], 0);
+    assert_eq!(lanes[&3], 1);
+    assert_eq!(lanes[&4], 1);
+    assert_eq!(lanes[&5], 0);
+}
+
+#[cfg(test)]
+#[test]
+fn otlp_export_links_spans_by_hex_ids() {
+    let mut spans = vec![
+        test_span(1, None, "span_1", 1_000, 100),
+        test_span(2, Some(1), "span_2", 1_010, 40),
+    ];
+    spans[1].events.push(Events {
+        name: "failed".to_string(),
+        severity: Severity::Error,
+        timestamp: 1_020,
+        key_values: vec![],
+    });
+    let otlp = to_otlp_json(255, "billing", &spans);
+    let exported = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"];
+    assert_eq!(exported[0]["traceId"], "000000000000000000000000000000ff");
+    assert_eq!(exported[0]["parentSpanId"], "");
+    assert_eq!(exported[1]["parentSpanId"], "0000000000000001");
+    assert_eq!(exported[1]["endTimeUnixNano"], "1050");
+    assert_eq!(exported[1]["status"]["code"], 2);
+}
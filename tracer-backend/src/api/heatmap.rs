@@ -0,0 +1,126 @@
+use crate::api::{ApiError, QueryReadyParameters};
+use api_structs::{Heatmap, HeatmapCell, SearchFor};
+use axum::Json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use tracing::instrument;
+
+const TIME_BUCKETS: i64 = 60;
+const DURATION_BUCKETS: usize = 20;
+
+#[derive(FromRow)]
+struct RawDbHeatmapRange {
+    min_timestamp: Option<i64>,
+    max_timestamp: Option<i64>,
+    min_duration: Option<i64>,
+    max_duration: Option<i64>,
+}
+
+#[derive(FromRow)]
+struct RawDbHeatmapCell {
+    time_bucket: i32,
+    duration_bucket: i32,
+    count: i64,
+}
+
+/// Log scaled lower bounds of the duration buckets between min and max, strictly increasing
+fn duration_lower_bounds(min: i64, max: i64) -> Vec<i64> {
+    let log_min = (min.max(1) as f64).ln();
+    let log_max = (max.max(1) as f64).ln();
+    let step = (log_max - log_min) / DURATION_BUCKETS as f64;
+    (1..DURATION_BUCKETS)
+        .map(|bucket| (log_min + step * bucket as f64).exp().floor() as i64)
+        .fold(vec![min], |mut bounds, bound| {
+            if bound > *bounds.last().expect("bounds to start with min") {
+                bounds.push(bound);
+            }
+            bounds
+        })
+}
+
+/// Same search as the grid but counting every matching trace, the buckets are fitted to the
+/// matching traces' timestamps and durations so the whole heatmap is used
+#[instrument(skip_all)]
+pub async fn heatmap(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(search_for): Json<SearchFor>,
+) -> Result<Json<Heatmap>, ApiError> {
+    let query_params = QueryReadyParameters::from_search(&con, search_for).await?;
+    let mut range_query: QueryBuilder<Postgres> = QueryBuilder::new(
+        "select min(trace.timestamp)::BIGINT as min_timestamp,
+       max(trace.timestamp)::BIGINT as max_timestamp,
+       min(trace.duration)::BIGINT as min_duration,
+       max(trace.duration)::BIGINT as max_duration
+    from trace where ",
+    );
+    query_params.push_trace_conditions(&mut range_query);
+    let range = range_query
+        .build_query_as::<RawDbHeatmapRange>()
+        .fetch_one(&con)
+        .await?;
+    let (Some(min_timestamp), Some(max_timestamp), Some(min_duration), Some(max_duration)) = (
+        range.min_timestamp,
+        range.max_timestamp,
+        range.min_duration,
+        range.max_duration,
+    ) else {
+        return Ok(Json(Heatmap::default()));
+    };
+    let time_bucket_size = ((max_timestamp - min_timestamp) / TIME_BUCKETS + 1).max(1);
+    let time_buckets = (max_timestamp - min_timestamp) / time_bucket_size + 1;
+    let lower_bounds = duration_lower_bounds(min_duration, max_duration);
+
+    let mut query: QueryBuilder<Postgres> = QueryBuilder::new("select ((trace.timestamp - ");
+    query.push_bind(min_timestamp);
+    query.push(") / ");
+    query.push_bind(time_bucket_size);
+    query.push(")::INT as time_bucket, (width_bucket(trace.duration::BIGINT, ");
+    query.push_bind(lower_bounds.clone());
+    query.push(
+        ") - 1)::INT as duration_bucket,
+       count(*) as count
+    from trace where ",
+    );
+    query_params.push_trace_conditions(&mut query);
+    query.push(" group by 1, 2");
+    let cells = query
+        .build_query_as::<RawDbHeatmapCell>()
+        .fetch_all(&con)
+        .await?;
+
+    let duration_bucket_bounds = lower_bounds
+        .into_iter()
+        .chain(std::iter::once(max_duration + 1))
+        .map(|bound| u64::try_from(bound).expect("duration to fit u64"))
+        .collect();
+    Ok(Json(Heatmap {
+        first_time_bucket_unix: u64::try_from(min_timestamp).expect("timestamp to fit u64"),
+        time_bucket_size_nanos: u64::try_from(time_bucket_size).expect("bucket size to fit u64"),
+        time_buckets: u32::try_from(time_buckets).expect("time buckets to fit u32"),
+        duration_bucket_bounds,
+        cells: cells
+            .into_iter()
+            .map(|cell| HeatmapCell {
+                time_bucket: u32::try_from(cell.time_bucket).expect("time bucket to fit u32"),
+                duration_bucket: u32::try_from(cell.duration_bucket)
+                    .expect("duration bucket to fit u32"),
+                count: u64::try_from(cell.count).expect("count to fit u64"),
+            })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+#[test]
+fn duration_bounds_are_log_scaled_and_start_at_min() {
+    let bounds = duration_lower_bounds(0, 10_000_000_000);
+    assert_eq!(bounds[0], 0);
+    assert!(bounds.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(*bounds.last().unwrap() < 10_000_000_000);
+    assert_eq!(duration_lower_bounds(5_000, 5_000), vec![5_000]);
+    let bounds = duration_lower_bounds(1_000, 1_000_000);
+    assert_eq!(bounds.len(), DURATION_BUCKETS);
+    // equal ratio between consecutive bounds
+    assert!(
+        (bounds[10] as f64 / bounds[9] as f64 - bounds[2] as f64 / bounds[1] as f64).abs() < 0.01
+    );
+}
@@ -0,0 +1,45 @@
+use crate::api::ApiError;
+use crate::otel_trace_processing::{import_traces, otlp_json};
+use crate::proto_generated::opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest;
+use crate::BYTES_IN_1MB;
+use api_structs::ImportResult;
+use axum::body::Bytes;
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use prost::Message;
+use sqlx::PgPool;
+use tracing::{info, instrument};
+
+pub const MAX_IMPORT_FILE_SIZE_BYTES: usize = 200 * BYTES_IN_1MB;
+
+/// Same encodings as OTLP/HTTP: protobuf when the Content-Type says so, JSON otherwise
+fn is_protobuf(headers: &HeaderMap) -> bool {
+    headers
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/x-protobuf"))
+}
+
+#[instrument(skip_all)]
+pub async fn import(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ImportResult>, ApiError> {
+    let request = if is_protobuf(&headers) {
+        ExportTraceServiceRequest::decode(body).map_err(|e| format!("Invalid OTLP protobuf: {e}"))
+    } else {
+        otlp_json::parse_export_trace_service_request(&body)
+    }
+    .map_err(|message| ApiError {
+        code: StatusCode::BAD_REQUEST,
+        message,
+    })?;
+    let result = import_traces(&con, request).await;
+    info!(
+        "Imported {} traces, rejected {}",
+        result.imported_trace_ids.len(),
+        result.rejected.len()
+    );
+    Ok(Json(result))
+}
@@ -0,0 +1,135 @@
+use crate::api::{
+    get_grid_data, searched_span_name, u64_to_naive_date_time, ApiError, QueryReadyParameters,
+    GRID_PAGE_SIZE,
+};
+use crate::otel_trace_processing::{InsertedTrace, LiveTailSender};
+use api_structs::{ApiTraceGridRow, SearchFor};
+use axum::extract::Query;
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::Extension;
+use futures::Stream;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{error, instrument, warn};
+
+#[derive(Debug, Deserialize)]
+pub struct LiveTailQuery {
+    /// JSON encoded [SearchFor], EventSource can only send GET requests
+    search: String,
+}
+
+struct Subscription {
+    receiver: tokio::sync::broadcast::Receiver<Vec<InsertedTrace>>,
+    con: PgPool,
+    query_params: QueryReadyParameters,
+    search_for: SearchFor,
+}
+
+/// Checks what we already know about the trace, the rest of the search (and regexes) need the DB
+pub fn may_match(query_params: &QueryReadyParameters, trace: &InsertedTrace) -> bool {
+    query_params
+        .service_name
+        .as_ref()
+        .map_or(true, |service_name| {
+            service_name.matches(&trace.service_name).unwrap_or(true)
+        })
+        && query_params
+            .top_level_span
+            .as_ref()
+            .map_or(true, |top_level_span| {
+                top_level_span
+                    .matches(&trace.top_level_span_name)
+                    .unwrap_or(true)
+            })
+        && query_params
+            .only_errors
+            .map_or(true, |only_errors| only_errors == trace.has_errors)
+        && query_params.min_warn_count.map_or(true, |min_warn_count| {
+            i64::from(trace.warning_count) >= min_warn_count
+        })
+}
+
+/// Parameters of a search over newly stored traces, which are always in its time window
+pub async fn new_traces_query_params(
+    con: &PgPool,
+    search_for: SearchFor,
+) -> Result<QueryReadyParameters, ApiError> {
+    let mut query_params = QueryReadyParameters::from_search(con, search_for).await?;
+    query_params.from = u64_to_naive_date_time(0)?;
+    query_params.to = u64_to_naive_date_time(i64::MAX as u64)?;
+    query_params.cursor = None;
+    Ok(query_params)
+}
+
+impl Subscription {
+    async fn matching_rows(
+        &mut self,
+        traces: Vec<InsertedTrace>,
+    ) -> Result<Vec<ApiTraceGridRow>, ApiError> {
+        let trace_ids: Vec<i64> = traces
+            .iter()
+            .filter(|trace| may_match(&self.query_params, trace))
+            .map(|trace| trace.id)
+            .collect();
+        let mut rows = vec![];
+        // get_grid_data returns a page at most
+        for trace_ids in trace_ids.chunks(GRID_PAGE_SIZE) {
+            self.query_params.trace_ids = Some(trace_ids.to_vec());
+            rows.extend(get_grid_data(&self.con, &self.query_params).await?);
+        }
+        let searched_span = searched_span_name(&self.search_for);
+        Ok(rows
+            .into_iter()
+            .map(|row| row.into_api_row(searched_span, &self.search_for.service_name))
+            .collect())
+    }
+
+    /// Waits for the next stored batch with traces matching the search, None once the storage
+    /// task is gone
+    async fn next_rows(&mut self) -> Option<Vec<ApiTraceGridRow>> {
+        loop {
+            let traces = match self.receiver.recv().await {
+                Ok(traces) => traces,
+                Err(RecvError::Lagged(skipped_batches)) => {
+                    warn!("Live tail fell behind, skipped {skipped_batches} batches");
+                    continue;
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            match self.matching_rows(traces).await {
+                Ok(rows) if rows.is_empty() => continue,
+                Ok(rows) => return Some(rows),
+                Err(e) => error!("Error matching live tail traces: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Server sent `traces` events, each with the grid rows of the newly stored traces matching
+/// the search. The search time window is ignored, new traces are always in it.
+#[instrument(skip_all)]
+pub async fn live_tail(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Extension(live_tail): Extension<LiveTailSender>,
+    Query(query): Query<LiveTailQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, serde_json::Error>>>, ApiError> {
+    let search_for: SearchFor = serde_json::from_str(&query.search).map_err(|e| ApiError {
+        code: StatusCode::BAD_REQUEST,
+        message: format!("Invalid search: {e}"),
+    })?;
+    let query_params = new_traces_query_params(&con, search_for.clone()).await?;
+    let subscription = Subscription {
+        receiver: live_tail.subscribe(),
+        con,
+        query_params,
+        search_for,
+    };
+    let stream = futures::stream::unfold(subscription, |mut subscription| async move {
+        let rows = subscription.next_rows().await?;
+        let event = Event::default().event("traces").json_data(rows);
+        Some((event, subscription))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,251 @@
+use crate::api::search_filter::{push_value_condition, AttributeValue};
+use crate::api::{into_escaped_like_search, u64_to_naive_date_time, ApiError};
+use api_structs::{LogCursor, LogLine, LogPage, LogSearchRequest, Severity, TextMatch};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::JsonValue;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use tracing::instrument;
+
+const LOG_PAGE_SIZE: usize = 200;
+/// Each one is an exists subquery, so keep it in check like the trace search
+const MAX_ATTRIBUTE_FILTERS: usize = 10;
+
+#[derive(FromRow)]
+struct RawDbLogLine {
+    trace_id: i64,
+    span_id: i64,
+    event_id: i64,
+    timestamp: i64,
+    severity: String,
+    message: String,
+    key_values: JsonValue,
+    service_name: String,
+    top_level_span_name: String,
+    span_name: String,
+}
+
+impl RawDbLogLine {
+    fn cursor(&self) -> LogCursor {
+        LogCursor {
+            timestamp: u64::try_from(self.timestamp).expect("timestamp to fit u64"),
+            trace_id: u64::try_from(self.trace_id).expect("trace_id to fit u64"),
+            span_id: u64::try_from(self.span_id).expect("span_id to fit u64"),
+            event_id: u64::try_from(self.event_id).expect("event_id to fit u64"),
+        }
+    }
+
+    fn into_log_line(self) -> LogLine {
+        LogLine {
+            trace_id: u64::try_from(self.trace_id).expect("trace_id to fit u64"),
+            span_id: u64::try_from(self.span_id).expect("span_id to fit u64"),
+            timestamp: u64::try_from(self.timestamp).expect("timestamp to fit u64"),
+            severity: Severity::ALL
+                .into_iter()
+                .find(|severity| severity.db_name() == self.severity)
+                .expect("db to have a valid severity"),
+            message: self.message,
+            key_values: serde_json::from_value(self.key_values).expect("db to generate valid json"),
+            service_name: self.service_name,
+            top_level_span_name: self.top_level_span_name,
+            span_name: self.span_name,
+        }
+    }
+}
+
+/// [LogSearchRequest] validated and converted into the values used in the SQL query
+#[derive(Debug)]
+struct QueryReadyLogSearch {
+    from: i64,
+    to: i64,
+    service_name: Option<String>,
+    min_severity: Option<Severity>,
+    /// ilike pattern
+    message: Option<String>,
+    attributes: Vec<(String, AttributeValue)>,
+    cursor: Option<(i64, i64, i64, i64)>,
+}
+
+impl QueryReadyLogSearch {
+    fn from_request(request: LogSearchRequest) -> Result<Self, ApiError> {
+        let from = u64_to_naive_date_time(request.from_date_unix)?.timestamp_nanos();
+        let to = u64_to_naive_date_time(request.to_date_unix)?.timestamp_nanos();
+        if from > to {
+            return Err(ApiError {
+                code: StatusCode::BAD_REQUEST,
+                message: "Start date must be before the end date".to_string(),
+            });
+        }
+        if request.attributes.len() > MAX_ATTRIBUTE_FILTERS {
+            return Err(ApiError {
+                code: StatusCode::BAD_REQUEST,
+                message: format!(
+                    "Search has {} attribute filters, the maximum is {MAX_ATTRIBUTE_FILTERS}",
+                    request.attributes.len()
+                ),
+            });
+        }
+        let attributes = request
+            .attributes
+            .into_iter()
+            .map(|attribute| {
+                if attribute.key.is_empty() {
+                    return Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Attribute filter is missing the key".to_string(),
+                    });
+                }
+                let value = AttributeValue::from_search(
+                    attribute.comparison,
+                    TextMatch::Contains,
+                    &attribute.value,
+                )?;
+                Ok((attribute.key, value))
+            })
+            .collect::<Result<Vec<_>, ApiError>>()?;
+        let cursor = request
+            .cursor
+            .map(|cursor| {
+                match (
+                    i64::try_from(cursor.timestamp),
+                    i64::try_from(cursor.trace_id),
+                    i64::try_from(cursor.span_id),
+                    i64::try_from(cursor.event_id),
+                ) {
+                    (Ok(timestamp), Ok(trace_id), Ok(span_id), Ok(event_id)) => {
+                        Ok((timestamp, trace_id, span_id, event_id))
+                    }
+                    _ => Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Invalid cursor".to_string(),
+                    }),
+                }
+            })
+            .transpose()?;
+        Ok(Self {
+            from,
+            to,
+            service_name: Some(request.service_name).filter(|s| !s.is_empty()),
+            min_severity: request.min_severity,
+            message: Some(request.message)
+                .filter(|m| !m.is_empty())
+                .map(|m| into_escaped_like_search(&m)),
+            attributes,
+            cursor,
+        })
+    }
+
+    /// Newest first, [LOG_PAGE_SIZE] + 1 lines so we know if there is a next page
+    fn build_query(&self) -> QueryBuilder<'_, Postgres> {
+        let mut query = QueryBuilder::new(
+            "select event.trace_id,
+       event.span_id,
+       event.id as event_id,
+       event.timestamp,
+       event.severity::TEXT as severity,
+       event.name::TEXT as message,
+       coalesce((select json_agg(json_build_object('key', event_key_value.key,
+                                                   'user_generated', event_key_value.user_generated,
+                                                   'value', event_key_value.value))
+                 from event_key_value
+                 where event_key_value.trace_id = event.trace_id
+                   and event_key_value.span_id = event.span_id
+                   and event_key_value.event_id = event.id), '[]') as key_values,
+       trace.service_name::TEXT,
+       trace.top_level_span_name::TEXT,
+       span.name::TEXT as span_name
+from event
+         join trace on trace.id = event.trace_id
+         join span on span.trace_id = event.trace_id and span.id = event.span_id
+where event.timestamp >= ",
+        );
+        query.push_bind(self.from);
+        query.push(" and event.timestamp <= ");
+        query.push_bind(self.to);
+        if let Some(service_name) = &self.service_name {
+            query.push(" and trace.service_name = ");
+            query.push_bind(service_name.clone());
+        }
+        if let Some(min_severity) = self.min_severity {
+            query.push(" and event.severity >= ");
+            query.push_bind(min_severity.db_name());
+            query.push("::severity_level");
+        }
+        if let Some(message) = &self.message {
+            query.push(" and event.name ilike ");
+            query.push_bind(message.clone());
+        }
+        for (key, value) in &self.attributes {
+            query.push(" and exists (select 1 from event_key_value where event_key_value.trace_id = event.trace_id and event_key_value.span_id = event.span_id and event_key_value.event_id = event.id and event_key_value.key = ");
+            query.push_bind(key.clone());
+            push_value_condition(&mut query, "event_key_value", value);
+            query.push(")");
+        }
+        if let Some((timestamp, trace_id, span_id, event_id)) = self.cursor {
+            query.push(" and (event.timestamp, event.trace_id, event.span_id, event.id) < (");
+            query.push_bind(timestamp);
+            query.push(", ");
+            query.push_bind(trace_id);
+            query.push(", ");
+            query.push_bind(span_id);
+            query.push(", ");
+            query.push_bind(event_id);
+            query.push(")");
+        }
+        query.push(format!(
+            " order by event.timestamp desc, event.trace_id desc, event.span_id desc, event.id desc limit {}",
+            LOG_PAGE_SIZE + 1
+        ));
+        query
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn search_logs(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<LogSearchRequest>,
+) -> Result<Json<LogPage>, ApiError> {
+    let search = QueryReadyLogSearch::from_request(request)?;
+    let mut lines = search
+        .build_query()
+        .build_query_as::<RawDbLogLine>()
+        .fetch_all(&con)
+        .await?;
+    let next_cursor = if lines.len() > LOG_PAGE_SIZE {
+        lines.truncate(LOG_PAGE_SIZE);
+        lines.last().map(RawDbLogLine::cursor)
+    } else {
+        None
+    };
+    Ok(Json(LogPage {
+        lines: lines.into_iter().map(RawDbLogLine::into_log_line).collect(),
+        next_cursor,
+    }))
+}
+
+#[cfg(test)]
+#[test]
+fn log_search_filters_on_the_event_itself() {
+    let search = QueryReadyLogSearch::from_request(LogSearchRequest {
+        from_date_unix: 1,
+        to_date_unix: 2,
+        service_name: String::new(),
+        min_severity: Some(Severity::Warn),
+        message: "50%".to_string(),
+        attributes: vec![api_structs::AttributeFilter {
+            key: "status".to_string(),
+            comparison: api_structs::ValueComparison::GreaterThanOrEqual,
+            value: "500".to_string(),
+        }],
+        cursor: None,
+    })
+    .unwrap();
+    assert_eq!(search.message.as_deref(), Some("%50\\%%"));
+    let query = search.build_query();
+    let sql = query.sql();
+    assert!(!sql.contains("trace.service_name = "));
+    assert!(sql.contains("event.severity >= $3::severity_level and event.name ilike $4"));
+    assert!(sql.contains(
+        "event_key_value.event_id = event.id and event_key_value.key = $5 and event_key_value.value_type in ('i64', 'f64')"
+    ));
+}
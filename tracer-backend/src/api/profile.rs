@@ -0,0 +1,287 @@
+use crate::api::trace_analysis::{children_by_parent_id, self_duration};
+use crate::api::{u64_to_naive_date_time, ApiError};
+use api_structs::{Profile, ProfileNode, ProfileRequest, Span};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing::{info, instrument};
+
+/// Only the most recent traces in the window are merged, up to this many traces and as many of
+/// them as fit in [MAX_PROFILED_SPANS], to bound the spans loaded in memory
+const MAX_PROFILED_TRACES: i64 = 1_000;
+/// Traces with more spans than this on their own are not profiled at all
+const MAX_PROFILED_SPANS: i64 = 500_000;
+
+struct RawDbProfileSpan {
+    trace_id: i64,
+    id: i64,
+    parent_id: Option<i64>,
+    timestamp: i64,
+    duration: i64,
+    name: String,
+}
+
+#[derive(Default)]
+struct MergedNode {
+    name: String,
+    parent: Option<usize>,
+    depth: u32,
+    children: Vec<usize>,
+    traces: u64,
+    last_trace_id: Option<i64>,
+    total_durations: Vec<u64>,
+    self_durations: Vec<u64>,
+}
+
+#[derive(Default)]
+struct MergedTree {
+    nodes: Vec<MergedNode>,
+    node_by_parent_and_name: HashMap<(Option<usize>, String), usize>,
+}
+
+impl MergedTree {
+    fn add_trace(&mut self, trace_id: i64, spans: &[Span]) {
+        let Some(root) = spans.iter().find(|s| s.parent_id.is_none()) else {
+            return;
+        };
+        let children_by_parent_id = children_by_parent_id(spans);
+        let mut to_visit: Vec<(&Span, Option<usize>)> = vec![(root, None)];
+        while let Some((span, parent)) = to_visit.pop() {
+            let children = children_by_parent_id
+                .get(&span.id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let node_idx = self.node(parent, &span.name);
+            let node = &mut self.nodes[node_idx];
+            if node.last_trace_id != Some(trace_id) {
+                node.last_trace_id = Some(trace_id);
+                node.traces += 1;
+            }
+            node.total_durations.push(span.duration);
+            node.self_durations.push(self_duration(span, children));
+            to_visit.extend(children.iter().map(|child| (*child, Some(node_idx))));
+        }
+    }
+
+    fn node(&mut self, parent: Option<usize>, name: &str) -> usize {
+        if let Some(idx) = self
+            .node_by_parent_and_name
+            .get(&(parent, name.to_string()))
+        {
+            return *idx;
+        }
+        let idx = self.nodes.len();
+        self.nodes.push(MergedNode {
+            name: name.to_string(),
+            parent,
+            depth: parent.map(|p| self.nodes[p].depth + 1).unwrap_or(0),
+            ..MergedNode::default()
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(idx);
+        }
+        self.node_by_parent_and_name
+            .insert((parent, name.to_string()), idx);
+        idx
+    }
+
+    /// Depth first with the most expensive children first, parents pointing to the new indexes
+    fn into_profile_nodes(mut self, traces_profiled: u64) -> Vec<ProfileNode> {
+        let total_per_trace =
+            |node: &MergedNode| node.total_durations.iter().sum::<u64>() / traces_profiled.max(1);
+        let mut roots: Vec<usize> = (0..self.nodes.len())
+            .filter(|idx| self.nodes[*idx].parent.is_none())
+            .collect();
+        roots.sort_by_key(|idx| std::cmp::Reverse(total_per_trace(&self.nodes[*idx])));
+        let mut profile_nodes = Vec::with_capacity(self.nodes.len());
+        let mut to_visit: Vec<(usize, Option<u32>)> =
+            roots.into_iter().rev().map(|idx| (idx, None)).collect();
+        while let Some((idx, parent)) = to_visit.pop() {
+            let mut children = std::mem::take(&mut self.nodes[idx].children);
+            children.sort_by_key(|child| std::cmp::Reverse(total_per_trace(&self.nodes[*child])));
+            let node = &mut self.nodes[idx];
+            node.total_durations.sort_unstable();
+            node.self_durations.sort_unstable();
+            let profile_idx = u32::try_from(profile_nodes.len()).expect("nodes to fit u32");
+            profile_nodes.push(ProfileNode {
+                name: std::mem::take(&mut node.name),
+                parent,
+                depth: node.depth,
+                calls: node.total_durations.len() as u64,
+                traces: node.traces,
+                avg_total_duration: average(&node.total_durations),
+                p50_total_duration: percentile(&node.total_durations, 0.5),
+                p90_total_duration: percentile(&node.total_durations, 0.9),
+                avg_self_duration: average(&node.self_durations),
+                p50_self_duration: percentile(&node.self_durations, 0.5),
+                p90_self_duration: percentile(&node.self_durations, 0.9),
+                total_duration_per_trace: total_per_trace(node),
+            });
+            to_visit.extend(
+                children
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, Some(profile_idx))),
+            );
+        }
+        profile_nodes
+    }
+}
+
+fn average(values: &[u64]) -> u64 {
+    values.iter().sum::<u64>() / (values.len() as u64).max(1)
+}
+
+/// Nearest rank percentile of the sorted values
+fn percentile(sorted_values: &[u64], percentile: f64) -> u64 {
+    if sorted_values.is_empty() {
+        return 0;
+    }
+    let rank = ((sorted_values.len() - 1) as f64 * percentile).round() as usize;
+    sorted_values[rank]
+}
+
+#[instrument(skip_all)]
+pub async fn profile(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<ProfileRequest>,
+) -> Result<Json<Profile>, ApiError> {
+    let from = u64_to_naive_date_time(request.from_date_unix)?.timestamp_nanos();
+    let to = u64_to_naive_date_time(request.to_date_unix)?.timestamp_nanos();
+    if from > to {
+        return Err(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: "Start date must be before the end date".to_string(),
+        });
+    }
+    let traces_in_window = sqlx::query_scalar!(
+        "select count(*) as \"count!\"
+from trace
+where service_name = $1
+  and top_level_span_name = $2
+  and timestamp >= $3::BIGINT
+  and timestamp <= $4::BIGINT;",
+        request.service_name as _,
+        request.top_level_span_name as _,
+        from,
+        to
+    )
+    .fetch_one(&con)
+    .await?;
+    let spans = sqlx::query_as!(
+        RawDbProfileSpan,
+        "with recent_trace as (select id, timestamp
+                      from trace
+                      where service_name = $1
+                        and top_level_span_name = $2
+                        and timestamp >= $3::BIGINT
+                        and timestamp <= $4::BIGINT
+                      order by timestamp desc
+                      limit $5),
+     recent_trace_spans as (select id,
+                                   sum((select count(*) from span where span.trace_id = recent_trace.id))
+                                   over (order by timestamp desc, id desc) as spans_so_far
+                            from recent_trace),
+     profiled_trace as (select id from recent_trace_spans where spans_so_far <= $6::BIGINT)
+select span.trace_id::BIGINT  as \"trace_id!\",
+       span.id::BIGINT        as \"id!\",
+       span.parent_id::BIGINT as parent_id,
+       span.timestamp::BIGINT as \"timestamp!\",
+       span.duration::BIGINT  as \"duration!\",
+       span.name::TEXT        as \"name!\"
+from span
+where span.trace_id in (select id from profiled_trace)
+order by span.trace_id;",
+        request.service_name as _,
+        request.top_level_span_name as _,
+        from,
+        to,
+        MAX_PROFILED_TRACES,
+        MAX_PROFILED_SPANS
+    )
+    .fetch_all(&con)
+    .await?;
+    info!("Profiling {} spans", spans.len());
+    let mut tree = MergedTree::default();
+    let mut traces_profiled = 0;
+    let mut trace_spans: Vec<Span> = vec![];
+    // spans are ordered by trace, a trace ends where the next span is from another one
+    for (i, s) in spans.iter().enumerate() {
+        trace_spans.push(Span {
+            id: u64::try_from(s.id).expect("span id to fit u64"),
+            timestamp: u64::try_from(s.timestamp).expect("timestamp to fit u64"),
+            duration: u64::try_from(s.duration).expect("duration to fit u64"),
+            parent_id: s
+                .parent_id
+                .map(|id| u64::try_from(id).expect("span id to fit u64")),
+            name: s.name.clone(),
+            key_values: vec![],
+            events: vec![],
+        });
+        if spans
+            .get(i + 1)
+            .map_or(true, |next| next.trace_id != s.trace_id)
+        {
+            tree.add_trace(s.trace_id, &trace_spans);
+            trace_spans.clear();
+            traces_profiled += 1;
+        }
+    }
+    Ok(Json(Profile {
+        traces_in_window: u64::try_from(traces_in_window).expect("count to fit u64"),
+        traces_profiled,
+        nodes: tree.into_profile_nodes(traces_profiled),
+    }))
+}
+
+#[cfg(test)]
+use crate::api::test_span;
+
+#[cfg(test)]
+#[test]
+fn traces_are_merged_by_name_path() {
+    let mut tree = MergedTree::default();
+    tree.add_trace(
+        1,
+        &[
+            test_span(1, None, "list", 0, 100),
+            test_span(2, Some(1), "fetch_row", 0, 10),
+            test_span(3, Some(1), "fetch_row", 10, 10),
+            test_span(4, Some(1), "render", 50, 50),
+        ],
+    );
+    tree.add_trace(
+        2,
+        &[
+            test_span(1, None, "list", 0, 300),
+            test_span(2, Some(1), "render", 0, 100),
+            // same name under a different parent is a different node
+            test_span(3, Some(2), "fetch_row", 0, 40),
+        ],
+    );
+    let nodes = tree.into_profile_nodes(2);
+    let summary: Vec<(&str, Option<u32>, u64, u64, u64)> = nodes
+        .iter()
+        .map(|n| {
+            (
+                n.name.as_str(),
+                n.parent,
+                n.calls,
+                n.traces,
+                n.total_duration_per_trace,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("list", None, 2, 2, 200),
+            ("render", Some(0), 2, 2, 75),
+            ("fetch_row", Some(1), 1, 1, 20),
+            ("fetch_row", Some(0), 2, 1, 10),
+        ]
+    );
+    assert_eq!(nodes[0].avg_self_duration, (30 + 200) / 2);
+    assert_eq!(nodes[1].p90_self_duration, 60);
+}
@@ -0,0 +1,1155 @@
+use crate::api::search_filter::{push_value_condition, AttributeValue, QueryReadyFilter};
+use crate::api::{
+    into_escaped_like_search, u64_to_naive_date_time, ApiError, RawDbTraceGrid, GRID_PAGE_SIZE,
+};
+use api_structs::{
+    ApiTraceGrid, QuerySyntaxError, TextMatch, TraceGridCursor, TraceQueryRequest, ValueComparison,
+};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tracing::{info, instrument};
+
+const MAX_QUERY_LEN: usize = 2000;
+/// Same limit as the form based search
+const MAX_QUERY_CONDITIONS: usize = 30;
+/// Nested `not` and `(`, each one recurses in the parser so this bounds its stack usage
+const MAX_QUERY_NESTING: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    /// Case insensitive contains
+    Contains,
+}
+
+const TEXT_COMPARISONS: [Comparison; 3] = [
+    Comparison::Equal,
+    Comparison::NotEqual,
+    Comparison::Contains,
+];
+const NUMERIC_COMPARISONS: [Comparison; 6] = [
+    Comparison::Equal,
+    Comparison::NotEqual,
+    Comparison::GreaterThan,
+    Comparison::GreaterThanOrEqual,
+    Comparison::LessThan,
+    Comparison::LessThanOrEqual,
+];
+const ALL_COMPARISONS: [Comparison; 7] = [
+    Comparison::Equal,
+    Comparison::NotEqual,
+    Comparison::GreaterThan,
+    Comparison::GreaterThanOrEqual,
+    Comparison::LessThan,
+    Comparison::LessThanOrEqual,
+    Comparison::Contains,
+];
+
+impl Comparison {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "!=",
+            Comparison::GreaterThan => ">",
+            Comparison::GreaterThanOrEqual => ">=",
+            Comparison::LessThan => "<",
+            Comparison::LessThanOrEqual => "<=",
+            Comparison::Contains => "~",
+        }
+    }
+
+    fn is_ordering(&self) -> bool {
+        matches!(
+            self,
+            Comparison::GreaterThan
+                | Comparison::GreaterThanOrEqual
+                | Comparison::LessThan
+                | Comparison::LessThanOrEqual
+        )
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            Comparison::NotEqual => "<>",
+            Comparison::Contains => "ilike",
+            comparison => comparison.symbol(),
+        }
+    }
+
+    fn to_value_comparison(self) -> ValueComparison {
+        match self {
+            Comparison::Equal => ValueComparison::Equal,
+            Comparison::NotEqual => ValueComparison::NotEqual,
+            Comparison::GreaterThan => ValueComparison::GreaterThan,
+            Comparison::GreaterThanOrEqual => ValueComparison::GreaterThanOrEqual,
+            Comparison::LessThan => ValueComparison::LessThan,
+            Comparison::LessThanOrEqual => ValueComparison::LessThanOrEqual,
+            Comparison::Contains => ValueComparison::Contains,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    /// Attribute key, without the leading `.`
+    Attribute(String),
+    String(String),
+    Number(String),
+    /// Nanoseconds
+    Duration(i64),
+    Comparison(Comparison),
+    /// `>>`, the `>` for direct children is a [Comparison::GreaterThan]
+    Descendant,
+    OpenParen,
+    CloseParen,
+    Comma,
+    End,
+}
+
+impl TokenKind {
+    fn describe(&self) -> String {
+        match self {
+            TokenKind::Word(word) => format!("`{word}`"),
+            TokenKind::Attribute(key) => format!("`.{key}`"),
+            TokenKind::String(value) => format!("`\"{value}\"`"),
+            TokenKind::Number(number) => format!("`{number}`"),
+            TokenKind::Duration(nanos) => format!("duration `{nanos}ns`"),
+            TokenKind::Comparison(comparison) => format!("`{}`", comparison.symbol()),
+            TokenKind::Descendant => "`>>`".to_string(),
+            TokenKind::OpenParen => "`(`".to_string(),
+            TokenKind::CloseParen => "`)`".to_string(),
+            TokenKind::Comma => "`,`".to_string(),
+            TokenKind::End => "the end of the query".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    kind: TokenKind,
+    /// Char offsets into the query
+    start: usize,
+    end: usize,
+}
+
+fn syntax_error(message: impl Into<String>, start: usize, end: usize) -> QuerySyntaxError {
+    QuerySyntaxError {
+        message: message.into(),
+        start,
+        end,
+    }
+}
+
+fn is_attribute_key_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
+fn parse_duration(number: &str, unit: &str) -> Result<i64, String> {
+    let multiplier = match unit {
+        "ns" => 1.,
+        "us" => 1_000.,
+        "ms" => 1_000_000.,
+        "s" => 1_000_000_000.,
+        "m" => 60. * 1_000_000_000.,
+        "h" => 3600. * 1_000_000_000.,
+        _ => {
+            return Err(format!(
+                "Invalid duration unit `{unit}`, must be one of ns, us, ms, s, m or h"
+            ))
+        }
+    };
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration `{number}{unit}`"))?;
+    let nanos = value * multiplier;
+    if !nanos.is_finite() || nanos < 0. || nanos > i64::MAX as f64 {
+        return Err(format!("Duration `{number}{unit}` is out of range"));
+    }
+    Ok(nanos.round() as i64)
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, QuerySyntaxError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut idx = 0;
+    while idx < chars.len() {
+        let start = idx;
+        let kind = match chars[idx] {
+            c if c.is_whitespace() => {
+                idx += 1;
+                continue;
+            }
+            '(' => {
+                idx += 1;
+                TokenKind::OpenParen
+            }
+            ')' => {
+                idx += 1;
+                TokenKind::CloseParen
+            }
+            ',' => {
+                idx += 1;
+                TokenKind::Comma
+            }
+            '=' => {
+                idx += 1;
+                TokenKind::Comparison(Comparison::Equal)
+            }
+            '~' => {
+                idx += 1;
+                TokenKind::Comparison(Comparison::Contains)
+            }
+            '!' => {
+                if chars.get(idx + 1) != Some(&'=') {
+                    return Err(syntax_error("Expected `!=`", start, idx + 1));
+                }
+                idx += 2;
+                TokenKind::Comparison(Comparison::NotEqual)
+            }
+            '>' => match chars.get(idx + 1) {
+                Some('>') => {
+                    idx += 2;
+                    TokenKind::Descendant
+                }
+                Some('=') => {
+                    idx += 2;
+                    TokenKind::Comparison(Comparison::GreaterThanOrEqual)
+                }
+                _ => {
+                    idx += 1;
+                    TokenKind::Comparison(Comparison::GreaterThan)
+                }
+            },
+            '<' => match chars.get(idx + 1) {
+                Some('=') => {
+                    idx += 2;
+                    TokenKind::Comparison(Comparison::LessThanOrEqual)
+                }
+                _ => {
+                    idx += 1;
+                    TokenKind::Comparison(Comparison::LessThan)
+                }
+            },
+            '"' => {
+                idx += 1;
+                let mut value = String::new();
+                loop {
+                    match chars.get(idx) {
+                        None => {
+                            return Err(syntax_error(
+                                "Unterminated string, missing the closing `\"`",
+                                start,
+                                idx,
+                            ))
+                        }
+                        Some('"') => {
+                            idx += 1;
+                            break;
+                        }
+                        Some('\\') => {
+                            match chars.get(idx + 1) {
+                                Some('"') => value.push('"'),
+                                Some('\\') => value.push('\\'),
+                                Some('n') => value.push('\n'),
+                                Some(other) => {
+                                    return Err(syntax_error(
+                                        format!("Unknown escape sequence `\\{other}`"),
+                                        idx,
+                                        idx + 2,
+                                    ))
+                                }
+                                None => {
+                                    return Err(syntax_error(
+                                        "Unterminated string, missing the closing `\"`",
+                                        start,
+                                        idx + 1,
+                                    ))
+                                }
+                            }
+                            idx += 2;
+                        }
+                        Some(c) => {
+                            value.push(*c);
+                            idx += 1;
+                        }
+                    }
+                }
+                TokenKind::String(value)
+            }
+            '.' => {
+                idx += 1;
+                while idx < chars.len() && is_attribute_key_char(chars[idx]) {
+                    idx += 1;
+                }
+                if idx == start + 1 {
+                    return Err(syntax_error(
+                        "Expected an attribute key after `.`, like .http.status_code",
+                        start,
+                        idx,
+                    ));
+                }
+                TokenKind::Attribute(chars[start + 1..idx].iter().collect())
+            }
+            c if c.is_ascii_digit()
+                || (c == '-' && chars.get(idx + 1).is_some_and(|c| c.is_ascii_digit())) =>
+            {
+                idx += 1;
+                while idx < chars.len() && (chars[idx].is_ascii_digit() || chars[idx] == '.') {
+                    idx += 1;
+                }
+                let number: String = chars[start..idx].iter().collect();
+                let unit_start = idx;
+                while idx < chars.len() && chars[idx].is_alphabetic() {
+                    idx += 1;
+                }
+                if unit_start == idx {
+                    if number.parse::<f64>().is_err() {
+                        return Err(syntax_error(
+                            format!("Invalid number `{number}`"),
+                            start,
+                            idx,
+                        ));
+                    }
+                    TokenKind::Number(number)
+                } else {
+                    let unit: String = chars[unit_start..idx].iter().collect();
+                    TokenKind::Duration(
+                        parse_duration(&number, &unit)
+                            .map_err(|message| syntax_error(message, start, idx))?,
+                    )
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while idx < chars.len() && (chars[idx].is_alphanumeric() || chars[idx] == '_') {
+                    idx += 1;
+                }
+                TokenKind::Word(chars[start..idx].iter().collect())
+            }
+            other => {
+                return Err(syntax_error(
+                    format!("Unexpected character `{other}`"),
+                    start,
+                    idx + 1,
+                ))
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: idx,
+        });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        start: chars.len(),
+        end: chars.len(),
+    });
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Relation {
+    Child,
+    Descendant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SpanCondition {
+    Name(Comparison, String),
+    Duration(Comparison, i64),
+    Attribute { key: String, value: AttributeValue },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpanSelector {
+    conditions: Vec<SpanCondition>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Service(Comparison, String),
+    Root(Comparison, String),
+    Duration(Comparison, i64),
+    HasErrors(bool),
+    Warnings(Comparison, i64),
+    Event(Comparison, String),
+    /// Any span or event attribute
+    Attribute {
+        key: String,
+        value: AttributeValue,
+    },
+    /// Each span is related to the previous one by the relation with the same index
+    Spans {
+        spans: Vec<SpanSelector>,
+        relations: Vec<Relation>,
+    },
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    conditions: usize,
+    /// `not` and `(` being parsed
+    nesting: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn peek_is_word(&self, word: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Word(w) if w.eq_ignore_ascii_case(word))
+    }
+
+    fn unexpected(&self, expected: &str) -> QuerySyntaxError {
+        let token = self.peek();
+        syntax_error(
+            format!("Expected {expected}, found {}", token.kind.describe()),
+            token.start,
+            token.end.max(token.start + 1),
+        )
+    }
+
+    fn count_condition(&mut self, token: &Token) -> Result<(), QuerySyntaxError> {
+        self.conditions += 1;
+        if self.conditions > MAX_QUERY_CONDITIONS {
+            return Err(syntax_error(
+                format!("Query has too many conditions, the maximum is {MAX_QUERY_CONDITIONS}"),
+                token.start,
+                token.end,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parses `parse` one level deeper, `token` being the `not` or `(` that opened the level
+    fn nested<T>(
+        &mut self,
+        token: &Token,
+        parse: impl FnOnce(&mut Self) -> Result<T, QuerySyntaxError>,
+    ) -> Result<T, QuerySyntaxError> {
+        if self.nesting >= MAX_QUERY_NESTING {
+            return Err(syntax_error(
+                format!("Query is nested too deep, the maximum is {MAX_QUERY_NESTING} levels of `not` and `(`"),
+                token.start,
+                token.end,
+            ));
+        }
+        self.nesting += 1;
+        let parsed = parse(self);
+        self.nesting -= 1;
+        parsed
+    }
+
+    fn expect_comparison(
+        &mut self,
+        after: &str,
+        allowed: &[Comparison],
+    ) -> Result<Comparison, QuerySyntaxError> {
+        match self.peek().kind {
+            TokenKind::Comparison(comparison) if allowed.contains(&comparison) => {
+                self.next();
+                Ok(comparison)
+            }
+            _ => {
+                let allowed = allowed
+                    .iter()
+                    .map(|c| format!("`{}`", c.symbol()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Err(self.unexpected(&format!("one of {allowed} after `{after}`")))
+            }
+        }
+    }
+
+    fn expect_string(&mut self, after: &str) -> Result<String, QuerySyntaxError> {
+        match &self.peek().kind {
+            TokenKind::String(value) => {
+                let value = value.clone();
+                self.next();
+                Ok(value)
+            }
+            _ => Err(self.unexpected(&format!("a quoted string after `{after}`"))),
+        }
+    }
+
+    fn expect_duration(&mut self, after: &str) -> Result<i64, QuerySyntaxError> {
+        match &self.peek().kind {
+            TokenKind::Duration(nanos) => {
+                let nanos = *nanos;
+                self.next();
+                Ok(nanos)
+            }
+            TokenKind::Number(_) => {
+                let token = self.peek();
+                Err(syntax_error(
+                    "Duration is missing the unit, like 200ms",
+                    token.start,
+                    token.end,
+                ))
+            }
+            _ => Err(self.unexpected(&format!("a duration like 200ms after `{after}`"))),
+        }
+    }
+
+    fn expect_close_paren(&mut self, open: &Token) -> Result<(), QuerySyntaxError> {
+        if let TokenKind::CloseParen = self.peek().kind {
+            self.next();
+            return Ok(());
+        }
+        let token = self.peek();
+        Err(syntax_error(
+            format!(
+                "Expected `)` to close the `(` at position {}, found {}",
+                open.start + 1,
+                token.kind.describe()
+            ),
+            token.start,
+            token.end.max(token.start + 1),
+        ))
+    }
+
+    /// Value after an attribute key, missing if only the key has to exist
+    fn attribute_value(&mut self, key: &str) -> Result<AttributeValue, QuerySyntaxError> {
+        let TokenKind::Comparison(_) = self.peek().kind else {
+            return Ok(AttributeValue::Any);
+        };
+        let comparison = self.expect_comparison(&format!(".{key}"), &ALL_COMPARISONS)?;
+        let value_token = self.next();
+        let value = match &value_token.kind {
+            TokenKind::Number(number) => number.clone(),
+            TokenKind::String(value) if !comparison.is_ordering() => value.clone(),
+            TokenKind::Word(word)
+                if !comparison.is_ordering()
+                    && (word.eq_ignore_ascii_case("true")
+                        || word.eq_ignore_ascii_case("false")) =>
+            {
+                word.to_lowercase()
+            }
+            other => {
+                let expected = if comparison.is_ordering() {
+                    "a number"
+                } else {
+                    "a quoted string, number, true or false"
+                };
+                return Err(syntax_error(
+                    format!(
+                        "Expected {expected} after `{}`, found {}",
+                        comparison.symbol(),
+                        other.describe()
+                    ),
+                    value_token.start,
+                    value_token.end.max(value_token.start + 1),
+                ));
+            }
+        };
+        AttributeValue::from_search(
+            comparison.to_value_comparison(),
+            TextMatch::Contains,
+            &value,
+        )
+        .map_err(|e| syntax_error(e.message, value_token.start, value_token.end))
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QuerySyntaxError> {
+        let mut expr = self.parse_and()?;
+        while self.peek_is_word("or") {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QuerySyntaxError> {
+        let mut expr = self.parse_not()?;
+        while self.peek_is_word("and") {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_not()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, QuerySyntaxError> {
+        if self.peek_is_word("not") {
+            let not = self.next();
+            let expr = self.nested(&not, Self::parse_not)?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        if let TokenKind::OpenParen = self.peek().kind {
+            let open = self.next();
+            let expr = self.nested(&open, Self::parse_or)?;
+            self.expect_close_paren(&open)?;
+            return Ok(expr);
+        }
+        if self.peek_is_word("span") {
+            return self.parse_span_chain();
+        }
+        self.parse_condition()
+    }
+
+    fn parse_condition(&mut self) -> Result<Expr, QuerySyntaxError> {
+        let token = self.peek().clone();
+        let field = match &token.kind {
+            TokenKind::Word(word) => word.to_lowercase(),
+            TokenKind::Attribute(key) => {
+                self.next();
+                self.count_condition(&token)?;
+                let value = self.attribute_value(key)?;
+                return Ok(Expr::Attribute {
+                    key: key.clone(),
+                    value,
+                });
+            }
+            _ => {
+                return Err(self.unexpected(
+                    "a condition like service = \"api\", duration > 200ms, status = error, \
+                    .http.status_code >= 500 or span(name = \"query\")",
+                ))
+            }
+        };
+        self.next();
+        self.count_condition(&token)?;
+        match field.as_str() {
+            "service" => {
+                let comparison = self.expect_comparison("service", &TEXT_COMPARISONS)?;
+                Ok(Expr::Service(comparison, self.expect_string("service")?))
+            }
+            "root" => {
+                let comparison = self.expect_comparison("root", &TEXT_COMPARISONS)?;
+                Ok(Expr::Root(comparison, self.expect_string("root")?))
+            }
+            "event" => {
+                let comparison = self.expect_comparison("event", &TEXT_COMPARISONS)?;
+                Ok(Expr::Event(comparison, self.expect_string("event")?))
+            }
+            "duration" => {
+                let comparison = self.expect_comparison("duration", &NUMERIC_COMPARISONS)?;
+                Ok(Expr::Duration(
+                    comparison,
+                    self.expect_duration("duration")?,
+                ))
+            }
+            "warnings" => {
+                let comparison = self.expect_comparison("warnings", &NUMERIC_COMPARISONS)?;
+                let value_token = self.next();
+                match &value_token.kind {
+                    TokenKind::Number(number) if number.parse::<i64>().is_ok() => Ok(
+                        Expr::Warnings(comparison, number.parse().expect("number to be i64")),
+                    ),
+                    other => Err(syntax_error(
+                        format!(
+                            "Expected a whole number of warnings, found {}",
+                            other.describe()
+                        ),
+                        value_token.start,
+                        value_token.end.max(value_token.start + 1),
+                    )),
+                }
+            }
+            "status" => {
+                let comparison =
+                    self.expect_comparison("status", &[Comparison::Equal, Comparison::NotEqual])?;
+                let value_token = self.next();
+                let has_errors = match &value_token.kind {
+                    TokenKind::Word(word) | TokenKind::String(word)
+                        if word.eq_ignore_ascii_case("error") =>
+                    {
+                        true
+                    }
+                    TokenKind::Word(word) | TokenKind::String(word)
+                        if word.eq_ignore_ascii_case("ok") =>
+                    {
+                        false
+                    }
+                    other => {
+                        return Err(syntax_error(
+                            format!("Expected `error` or `ok`, found {}", other.describe()),
+                            value_token.start,
+                            value_token.end.max(value_token.start + 1),
+                        ))
+                    }
+                };
+                Ok(Expr::HasErrors(if comparison == Comparison::Equal {
+                    has_errors
+                } else {
+                    !has_errors
+                }))
+            }
+            _ => Err(syntax_error(
+                format!(
+                    "Unknown field `{field}`, expected service, root, duration, status, warnings, \
+                    event, span or an attribute like .http.status_code"
+                ),
+                token.start,
+                token.end,
+            )),
+        }
+    }
+
+    fn parse_span_chain(&mut self) -> Result<Expr, QuerySyntaxError> {
+        let mut spans = vec![self.parse_span_selector()?];
+        let mut relations = vec![];
+        loop {
+            let relation = match self.peek().kind {
+                TokenKind::Comparison(Comparison::GreaterThan) => Relation::Child,
+                TokenKind::Descendant => Relation::Descendant,
+                _ => break,
+            };
+            self.next();
+            if !self.peek_is_word("span") {
+                return Err(self.unexpected("`span(...)` after `>` or `>>`"));
+            }
+            relations.push(relation);
+            spans.push(self.parse_span_selector()?);
+        }
+        Ok(Expr::Spans { spans, relations })
+    }
+
+    fn parse_span_selector(&mut self) -> Result<SpanSelector, QuerySyntaxError> {
+        let span_token = self.next();
+        self.count_condition(&span_token)?;
+        let open = match self.peek().kind {
+            TokenKind::OpenParen => self.next(),
+            _ => return Err(self.unexpected("`(` after `span`")),
+        };
+        let mut conditions = vec![];
+        if let TokenKind::CloseParen = self.peek().kind {
+            self.next();
+            return Ok(SpanSelector { conditions });
+        }
+        loop {
+            conditions.push(self.parse_span_condition()?);
+            if let TokenKind::Comma = self.peek().kind {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_close_paren(&open)?;
+        Ok(SpanSelector { conditions })
+    }
+
+    fn parse_span_condition(&mut self) -> Result<SpanCondition, QuerySyntaxError> {
+        let token = self.peek().clone();
+        match &token.kind {
+            TokenKind::Word(word) if word.eq_ignore_ascii_case("name") => {
+                self.next();
+                let comparison = self.expect_comparison("name", &TEXT_COMPARISONS)?;
+                Ok(SpanCondition::Name(comparison, self.expect_string("name")?))
+            }
+            TokenKind::Word(word) if word.eq_ignore_ascii_case("duration") => {
+                self.next();
+                let comparison = self.expect_comparison("duration", &NUMERIC_COMPARISONS)?;
+                Ok(SpanCondition::Duration(
+                    comparison,
+                    self.expect_duration("duration")?,
+                ))
+            }
+            TokenKind::Attribute(key) => {
+                self.next();
+                self.count_condition(&token)?;
+                let value = self.attribute_value(key)?;
+                Ok(SpanCondition::Attribute {
+                    key: key.clone(),
+                    value,
+                })
+            }
+            _ => Err(self.unexpected(
+                "a span condition like name = \"query\", duration > 200ms or .db.system = \"postgres\"",
+            )),
+        }
+    }
+}
+
+/// Parses the trace query language, None for an empty query. Grammar:
+/// ```text
+/// query      = or
+/// or         = and ("or" and)*
+/// and        = not ("and" not)*
+/// not        = "not" not | "(" query ")" | span_chain | condition
+/// span_chain = span ((">" | ">>") span)*        `>` direct child, `>>` any descendant
+/// span       = "span" "(" [span_cond ("," span_cond)*] ")"
+/// span_cond  = "name" text_op string | "duration" cmp duration | attribute
+/// condition  = ("service" | "root" | "event") text_op string
+///            | "duration" cmp duration
+///            | "status" ("=" | "!=") ("error" | "ok")
+///            | "warnings" cmp integer
+///            | attribute                          span or event attribute
+/// attribute  = "." key [cmp value]                without a value only the key has to exist
+/// text_op    = "=" | "!=" | "~"                   `~` is a case insensitive contains
+/// cmp        = "=" | "!=" | ">" | ">=" | "<" | "<="
+/// duration   = number ("ns" | "us" | "ms" | "s" | "m" | "h")
+/// ```
+/// For example: `service = "api" and span(name = "GET /users") >> span(duration > 200ms)`
+fn parse_query(query: &str) -> Result<Option<Expr>, QuerySyntaxError> {
+    let query_len = query.chars().count();
+    if query_len > MAX_QUERY_LEN {
+        return Err(syntax_error(
+            format!("Query is too long, the maximum is {MAX_QUERY_LEN} characters"),
+            MAX_QUERY_LEN,
+            query_len,
+        ));
+    }
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        conditions: 0,
+        nesting: 0,
+    };
+    if let TokenKind::End = parser.peek().kind {
+        return Ok(None);
+    }
+    let expr = parser.parse_or()?;
+    if parser.peek().kind != TokenKind::End {
+        return Err(parser.unexpected("`and`, `or` or the end of the query"));
+    }
+    Ok(Some(expr))
+}
+
+fn push_text_condition(
+    query: &mut QueryBuilder<'_, Postgres>,
+    column: &str,
+    comparison: Comparison,
+    value: &str,
+) {
+    query.push(format!("{column} {} ", comparison.sql()));
+    if let Comparison::Contains = comparison {
+        query.push_bind(into_escaped_like_search(value));
+    } else {
+        query.push_bind(value.to_string());
+    }
+}
+
+impl SpanCondition {
+    fn push_condition(&self, query: &mut QueryBuilder<'_, Postgres>, alias: &str) {
+        match self {
+            SpanCondition::Name(comparison, name) => {
+                push_text_condition(query, &format!("{alias}.name"), *comparison, name)
+            }
+            SpanCondition::Duration(comparison, nanos) => {
+                query.push(format!("{alias}.duration {} ", comparison.sql()));
+                query.push_bind(*nanos);
+            }
+            SpanCondition::Attribute { key, value } => {
+                query.push(format!("exists (select 1 from span_key_value where span_key_value.trace_id = trace.id and span_key_value.span_id = {alias}.id and span_key_value.key = "));
+                query.push_bind(key.clone());
+                push_value_condition(query, "span_key_value", value);
+                query.push(")");
+            }
+        }
+    }
+}
+
+/// Each span of the chain is a nested exists so it can refer to the previous one
+fn push_span_chain(
+    query: &mut QueryBuilder<'_, Postgres>,
+    spans: &[SpanSelector],
+    relations: &[Relation],
+    depth: usize,
+) {
+    let alias = format!("span_{depth}");
+    query.push(format!(
+        "exists (select 1 from span {alias} where {alias}.trace_id = trace.id"
+    ));
+    if depth > 0 {
+        let parent = format!("span_{}", depth - 1);
+        match relations[depth - 1] {
+            Relation::Child => {
+                query.push(format!(" and {alias}.parent_id = {parent}.id"));
+            }
+            Relation::Descendant => {
+                // `union` rather than `union all` drops the ancestors already seen, so a trace
+                // with a cycle in its parents, which collection doesn't reject, still terminates
+                query.push(format!(
+                    " and exists (with recursive ancestor(id) as (select {alias}.parent_id \
+                    union select span.parent_id from span inner join ancestor on span.id = ancestor.id \
+                    where span.trace_id = trace.id) select 1 from ancestor where ancestor.id = {parent}.id)"
+                ));
+            }
+        }
+    }
+    for condition in &spans[depth].conditions {
+        query.push(" and ");
+        condition.push_condition(query, &alias);
+    }
+    if depth + 1 < spans.len() {
+        query.push(" and ");
+        push_span_chain(query, spans, relations, depth + 1);
+    }
+    query.push(")");
+}
+
+impl Expr {
+    /// Pushes a boolean SQL expression, the query must have the `trace` table in scope
+    fn push_condition(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Expr::And(left, right) => {
+                query.push("(");
+                left.push_condition(query);
+                query.push(" and ");
+                right.push_condition(query);
+                query.push(")");
+            }
+            Expr::Or(left, right) => {
+                query.push("(");
+                left.push_condition(query);
+                query.push(" or ");
+                right.push_condition(query);
+                query.push(")");
+            }
+            Expr::Not(expr) => {
+                query.push("not (");
+                expr.push_condition(query);
+                query.push(")");
+            }
+            Expr::Service(comparison, service_name) => {
+                push_text_condition(query, "trace.service_name", *comparison, service_name)
+            }
+            Expr::Root(comparison, span_name) => {
+                push_text_condition(query, "trace.top_level_span_name", *comparison, span_name)
+            }
+            Expr::Duration(comparison, nanos) => {
+                query.push(format!("trace.duration {} ", comparison.sql()));
+                query.push_bind(*nanos);
+            }
+            Expr::HasErrors(has_errors) => {
+                query.push("trace.has_errors = ");
+                query.push_bind(*has_errors);
+            }
+            Expr::Warnings(comparison, warnings) => {
+                query.push(format!("trace.warning_count {} ", comparison.sql()));
+                query.push_bind(*warnings);
+            }
+            Expr::Event(comparison, name) => {
+                query.push("exists (select 1 from event where event.trace_id = trace.id and ");
+                push_text_condition(query, "event.name", *comparison, name);
+                query.push(")");
+            }
+            Expr::Attribute { key, value } => QueryReadyFilter::Attribute {
+                key: key.clone(),
+                value: value.clone(),
+            }
+            .push_condition(query),
+            Expr::Spans { spans, relations } => push_span_chain(query, spans, relations, 0),
+        }
+    }
+}
+
+pub enum QueryError {
+    Syntax(QuerySyntaxError),
+    Api(ApiError),
+}
+
+impl From<ApiError> for QueryError {
+    fn from(value: ApiError) -> Self {
+        QueryError::Api(value)
+    }
+}
+
+impl IntoResponse for QueryError {
+    fn into_response(self) -> Response {
+        match self {
+            QueryError::Syntax(error) => (StatusCode::BAD_REQUEST, Json(error)).into_response(),
+            QueryError::Api(error) => error.into_response(),
+        }
+    }
+}
+
+/// Pushes the conditions on the trace table
+fn push_trace_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    from: i64,
+    to: i64,
+    expr: Option<&Expr>,
+) {
+    query.push("trace.timestamp >= ");
+    query.push_bind(from);
+    query.push(" and trace.timestamp <= ");
+    query.push_bind(to);
+    if let Some(expr) = expr {
+        query.push(" and ");
+        expr.push_condition(query);
+    }
+}
+
+/// Newest first, in pages of [GRID_PAGE_SIZE] traces
+#[instrument(skip_all)]
+pub async fn traces_query(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<TraceQueryRequest>,
+) -> Result<Json<ApiTraceGrid>, QueryError> {
+    let expr = parse_query(&request.query).map_err(QueryError::Syntax)?;
+    info!("Parsed query: {:?}", expr);
+    let from = u64_to_naive_date_time(request.from_date_unix)?.timestamp_nanos();
+    let to = u64_to_naive_date_time(request.to_date_unix)?.timestamp_nanos();
+    if from > to {
+        return Err(QueryError::Api(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: "Start date must be before the end date".to_string(),
+        }));
+    }
+    let cursor = request
+        .cursor
+        .map(
+            |cursor| match (i64::try_from(cursor.sort_value), i64::try_from(cursor.id)) {
+                (Ok(timestamp), Ok(id)) => Ok((timestamp, id)),
+                _ => Err(ApiError {
+                    code: StatusCode::BAD_REQUEST,
+                    message: "Invalid cursor".to_string(),
+                }),
+            },
+        )
+        .transpose()?;
+    let mut query = QueryBuilder::new(
+        "select trace.id,
+       trace.timestamp,
+       trace.duration,
+       trace.service_name::TEXT,
+       trace.has_errors,
+       trace.warning_count,
+       trace.top_level_span_name::TEXT,
+       null::TEXT as key,
+       null::TEXT as value,
+       null::TEXT as span_name,
+       null::BIGINT as span_id,
+       null::TEXT as event_name,
+       0::BIGINT as relevance,
+       trace.imported
+    from trace
+    where ",
+    );
+    push_trace_conditions(&mut query, from, to, expr.as_ref());
+    if let Some((timestamp, id)) = cursor {
+        query.push(" and (trace.timestamp, trace.id) < (");
+        query.push_bind(timestamp);
+        query.push(", ");
+        query.push_bind(id);
+        query.push(")");
+    }
+    query.push(format!(
+        " order by trace.timestamp desc, trace.id desc limit {}",
+        GRID_PAGE_SIZE + 1
+    ));
+    let mut rows = query
+        .build_query_as::<RawDbTraceGrid>()
+        .fetch_all(&con)
+        .await
+        .map_err(ApiError::from)?;
+    let total_count = if cursor.is_none() {
+        let mut count_query = QueryBuilder::new("select count(*) from trace where ");
+        push_trace_conditions(&mut count_query, from, to, expr.as_ref());
+        let (count,) = count_query
+            .build_query_as::<(i64,)>()
+            .fetch_one(&con)
+            .await
+            .map_err(ApiError::from)?;
+        Some(u64::try_from(count).expect("count to fit u64"))
+    } else {
+        None
+    };
+    let next_cursor = if rows.len() > GRID_PAGE_SIZE {
+        rows.truncate(GRID_PAGE_SIZE);
+        rows.last().map(|last| TraceGridCursor {
+            sort_value: u64::try_from(last.timestamp).expect("timestamp to fit u64"),
+            id: u64::try_from(last.id).expect("trace_id to fit u64"),
+        })
+    } else {
+        None
+    };
+    Ok(Json(ApiTraceGrid {
+        rows: rows.into_iter().map(|r| r.into_api_row("", "")).collect(),
+        total_count,
+        next_cursor,
+    }))
+}
+
+#[cfg(test)]
+#[test]
+fn query_language_precedence_and_structure() {
+    let expr = parse_query(
+        r#"service = "api" or not status = error and span(name ~ "db") >> span(duration >= 1.5s, .rows > 10)"#,
+    )
+    .unwrap()
+    .unwrap();
+    let Expr::Or(left, right) = expr else {
+        panic!("or must have the lowest precedence");
+    };
+    assert_eq!(*left, Expr::Service(Comparison::Equal, "api".to_string()));
+    let Expr::And(not, spans) = *right else {
+        panic!("and must bind tighter than or");
+    };
+    assert_eq!(*not, Expr::Not(Box::new(Expr::HasErrors(true))));
+    let Expr::Spans { spans, relations } = *spans else {
+        panic!("expected a span chain");
+    };
+    assert_eq!(relations, vec![Relation::Descendant]);
+    assert_eq!(
+        spans[1].conditions[0],
+        SpanCondition::Duration(Comparison::GreaterThanOrEqual, 1_500_000_000)
+    );
+    assert_eq!(parse_query("  ").unwrap(), None);
+}
+
+#[cfg(test)]
+#[test]
+fn query_language_errors_point_at_the_problem() {
+    let error = parse_query(r#"service = "api" and duration > 200"#).unwrap_err();
+    assert_eq!(error.message, "Duration is missing the unit, like 200ms");
+    assert_eq!((error.start, error.end), (31, 34));
+    let error = parse_query(r#"(service = "api""#).unwrap_err();
+    assert_eq!(
+        error.message,
+        "Expected `)` to close the `(` at position 1, found the end of the query"
+    );
+    let error = parse_query(r#"servce = "api""#).unwrap_err();
+    assert_eq!((error.start, error.end), (0, 6));
+    let error = parse_query(r#".http.status_code > "high""#).unwrap_err();
+    assert_eq!(
+        error.message,
+        "Expected a number after `>`, found `\"high\"`"
+    );
+    let nested = format!("{}warnings > 1{}", "(".repeat(990), ")".repeat(990));
+    let error = parse_query(&nested).unwrap_err();
+    assert_eq!((error.start, error.end), (32, 33));
+    assert!(parse_query(&format!("{}status = ok", "not ".repeat(32))).is_ok());
+    assert!(parse_query(&format!("{}status = ok", "not ".repeat(33))).is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn query_language_descendants_render_sql() {
+    let expr = parse_query(r#"span(name = "checkout") >> span(name = "db")"#)
+        .unwrap()
+        .unwrap();
+    let mut query = QueryBuilder::<Postgres>::new("");
+    expr.push_condition(&mut query);
+    assert_eq!(
+        query.sql(),
+        "exists (select 1 from span span_0 where span_0.trace_id = trace.id and span_0.name = $1 and \
+        exists (select 1 from span span_1 where span_1.trace_id = trace.id and \
+        exists (with recursive ancestor(id) as (select span_1.parent_id \
+        union select span.parent_id from span inner join ancestor on span.id = ancestor.id \
+        where span.trace_id = trace.id) select 1 from ancestor where ancestor.id = span_0.id) \
+        and span_1.name = $2))"
+    );
+}
@@ -0,0 +1,123 @@
+use crate::api::{ApiError, QueryReadyParameters};
+use api_structs::{DeleteSavedSearchRequest, GridSearchState, NewSavedSearch, SavedSearch};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Json as DbJson;
+use sqlx::PgPool;
+use tracing::instrument;
+
+/// Postgres error codes of a failed `check`, like the non empty text domains, and of a too long text
+const INVALID_TEXT_CODES: [&str; 2] = ["23514", "22001"];
+
+struct RawDbSavedSearch {
+    id: i64,
+    name: String,
+    owner: String,
+    description: Option<String>,
+    state: DbJson<GridSearchState>,
+    updated_at: i64,
+}
+
+impl RawDbSavedSearch {
+    fn into_saved_search(self) -> SavedSearch {
+        SavedSearch {
+            id: self.id,
+            name: self.name,
+            owner: self.owner,
+            description: self.description,
+            state: self.state.0,
+            updated_at: u64::try_from(self.updated_at).expect("updated_at to fit u64"),
+        }
+    }
+}
+
+/// Ordered by owner and then name
+#[instrument(skip_all)]
+pub async fn saved_searches(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+) -> Result<Json<Vec<SavedSearch>>, ApiError> {
+    let searches = sqlx::query_as!(
+        RawDbSavedSearch,
+        r#"select id,
+       name::TEXT as "name!",
+       owner::TEXT as "owner!",
+       description::TEXT,
+       state as "state: DbJson<GridSearchState>",
+       updated_at
+from saved_search
+order by owner, name"#
+    )
+    .fetch_all(&con)
+    .await?;
+    Ok(Json(
+        searches
+            .into_iter()
+            .map(RawDbSavedSearch::into_saved_search)
+            .collect(),
+    ))
+}
+
+/// Replaces the search of the same owner with the same name, if any
+#[instrument(skip_all)]
+pub async fn save_search(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<NewSavedSearch>,
+) -> Result<Json<SavedSearch>, ApiError> {
+    // refuse searches the grid would fail to run, like ones with invalid regexes
+    QueryReadyParameters::from_search(&con, request.state.search_for.clone()).await?;
+    let description = request
+        .description
+        .filter(|description| !description.trim().is_empty());
+    let search = sqlx::query_as!(
+        RawDbSavedSearch,
+        r#"insert into saved_search (name, owner, description, state, updated_at)
+values ($1::TEXT, $2::TEXT, $3::TEXT, $4::JSONB, $5::BIGINT)
+on conflict (owner, name) do update set description = excluded.description,
+                                        state       = excluded.state,
+                                        updated_at  = excluded.updated_at
+returning id,
+    name::TEXT as "name!",
+    owner::TEXT as "owner!",
+    description::TEXT,
+    state as "state: DbJson<GridSearchState>",
+    updated_at"#,
+        request.name.trim(),
+        request.owner.trim(),
+        description,
+        DbJson(request.state) as _,
+        chrono::Utc::now().timestamp_nanos()
+    )
+    .fetch_one(&con)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e)
+            if e.code()
+                .is_some_and(|code| INVALID_TEXT_CODES.contains(&code.as_ref())) =>
+        {
+            ApiError {
+                code: StatusCode::BAD_REQUEST,
+                message: "Saved search needs a name and an owner of at most 512 chars".to_string(),
+            }
+        }
+        e => ApiError::from(e),
+    })?;
+    Ok(Json(search.into_saved_search()))
+}
+
+#[instrument(skip_all)]
+pub async fn delete_saved_search(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<DeleteSavedSearchRequest>,
+) -> Result<Json<()>, ApiError> {
+    let deleted = sqlx::query!("delete from saved_search where id = $1::BIGINT", request.id)
+        .execute(&con)
+        .await?
+        .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError {
+            code: StatusCode::NOT_FOUND,
+            message: format!("No saved search with id {}", request.id),
+        });
+    }
+    Ok(Json(()))
+}
@@ -0,0 +1,908 @@
+use crate::api::text_search::{headline_options, to_tsquery, TEXT_SEARCH_CONFIG};
+use crate::api::{escape_like, into_escaped_like_search, ApiError};
+use api_structs::{Filter, SpanFilter, TextMatch, ValueComparison};
+use axum::http::StatusCode;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+
+/// Limits how complex (and slow) a single search can get
+const MAX_FILTER_CONDITIONS: usize = 30;
+/// ts_rank is a float, the grid cursor needs an integer to paginate by relevance
+const RELEVANCE_SCALE: i64 = 1_000_000;
+
+/// Postgres error code of an invalid regular expression
+const INVALID_REGULAR_EXPRESSION: &str = "2201B";
+
+/// Searched text with how it is compared, see [TextMatch]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextCondition {
+    text_match: TextMatch,
+    text: String,
+}
+
+impl TextCondition {
+    /// None for an empty text, which matches anything
+    pub fn new(text_match: TextMatch, text: String) -> Option<Self> {
+        if text.is_empty() {
+            None
+        } else {
+            Some(Self { text_match, text })
+        }
+    }
+
+    /// The text when it must be matched exactly
+    pub fn exact(&self) -> Option<&str> {
+        match self.text_match {
+            TextMatch::Exact => Some(&self.text),
+            _ => None,
+        }
+    }
+
+    pub fn regex(&self) -> Option<&str> {
+        match self.text_match {
+            TextMatch::Regex => Some(&self.text),
+            _ => None,
+        }
+    }
+
+    /// Same comparison as the SQL one, None for regexes since postgres has its own flavor
+    pub fn matches(&self, text: &str) -> Option<bool> {
+        match self.text_match {
+            TextMatch::Exact => Some(text == self.text),
+            TextMatch::Contains => Some(text.to_lowercase().contains(&self.text.to_lowercase())),
+            TextMatch::Prefix => Some(text.to_lowercase().starts_with(&self.text.to_lowercase())),
+            TextMatch::Regex => None,
+        }
+    }
+
+    /// Pushes `<column> <operator> <text>`
+    pub fn push_condition(&self, query: &mut QueryBuilder<'_, Postgres>, column: &str) {
+        match self.text_match {
+            TextMatch::Exact => {
+                query.push(format!("{column} = "));
+                query.push_bind(self.text.clone());
+            }
+            TextMatch::Contains => {
+                query.push(format!("{column} ilike "));
+                query.push_bind(into_escaped_like_search(&self.text));
+            }
+            TextMatch::Prefix => {
+                query.push(format!("{column} ilike "));
+                query.push_bind(format!("{}%", escape_like(&self.text)));
+            }
+            TextMatch::Regex => {
+                query.push(format!("{column} ~ "));
+                query.push_bind(self.text.clone());
+            }
+        }
+    }
+}
+
+/// Checks the regexes of a search up front, so a bad one is reported with the field it is in
+/// instead of failing the search with a DB error. `regexes` are (field, regex) pairs.
+pub async fn validate_regexes(con: &PgPool, regexes: &[(&str, &str)]) -> Result<(), ApiError> {
+    for (field, regex) in regexes {
+        let Err(e) = sqlx::query_scalar!(r#"select ''::TEXT ~ $1::TEXT as "matches!""#, regex)
+            .fetch_one(con)
+            .await
+        else {
+            continue;
+        };
+        return Err(match e.as_database_error() {
+            Some(db_error) if db_error.code().as_deref() == Some(INVALID_REGULAR_EXPRESSION) => {
+                ApiError {
+                    code: StatusCode::BAD_REQUEST,
+                    message: format!(
+                        "Invalid {field} regex \"{regex}\": {}",
+                        db_error
+                            .message()
+                            .trim_start_matches("invalid regular expression: ")
+                    ),
+                }
+            }
+            _ => ApiError::from(e),
+        });
+    }
+    Ok(())
+}
+
+/// Value filter that takes the attribute value_type into account.
+/// At least one of `text` or `numeric` is set, both for an equality with a number, which also
+/// matches string attributes holding the same text, like `"42"` or `"00123"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypedValueFilter {
+    /// Value compared against string or bool attributes, with its value_type
+    text: Option<(String, &'static str)>,
+    /// Number compared against i64 or f64 attributes
+    numeric: Option<String>,
+    operator: &'static str,
+}
+
+impl TypedValueFilter {
+    fn from_search(comparison: ValueComparison, value: &str) -> Result<Self, ApiError> {
+        let value = value.trim();
+        let numeric = value
+            .parse::<i64>()
+            .map(|v| v.to_string())
+            .ok()
+            .or_else(|| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .map(|v| v.to_string())
+            });
+        let operator = match comparison {
+            // postgres spelling
+            ValueComparison::NotEqual => "<>",
+            comparison => comparison.symbol(),
+        };
+        match comparison {
+            ValueComparison::Contains => Err(ApiError {
+                code: StatusCode::INTERNAL_SERVER_ERROR,
+                message: "Contains is not a typed comparison".to_string(),
+            }),
+            ValueComparison::Equal | ValueComparison::NotEqual => {
+                let lowercase = value.to_lowercase();
+                if lowercase == "true" || lowercase == "false" {
+                    Ok(Self {
+                        text: Some((lowercase, "bool")),
+                        numeric: None,
+                        operator,
+                    })
+                } else {
+                    Ok(Self {
+                        text: Some((value.to_string(), "string")),
+                        numeric,
+                        operator,
+                    })
+                }
+            }
+            ValueComparison::GreaterThan
+            | ValueComparison::GreaterThanOrEqual
+            | ValueComparison::LessThan
+            | ValueComparison::LessThanOrEqual => match numeric {
+                None => Err(ApiError {
+                    code: StatusCode::BAD_REQUEST,
+                    message: format!("Value must be a number to be compared using {operator}"),
+                }),
+                Some(numeric) => Ok(Self {
+                    text: None,
+                    numeric: Some(numeric),
+                    operator,
+                }),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    /// Only the key has to match
+    Any,
+    /// Matches attributes of any value_type
+    Text(TextCondition),
+    Typed(TypedValueFilter),
+}
+
+impl AttributeValue {
+    /// `text_match` is how [ValueComparison::Contains] compares the value
+    pub fn from_search(
+        comparison: ValueComparison,
+        text_match: TextMatch,
+        value: &str,
+    ) -> Result<Self, ApiError> {
+        if value.is_empty() {
+            Ok(AttributeValue::Any)
+        } else if let ValueComparison::Contains = comparison {
+            Ok(AttributeValue::Text(TextCondition {
+                text_match,
+                text: value.to_string(),
+            }))
+        } else {
+            Ok(AttributeValue::Typed(TypedValueFilter::from_search(
+                comparison, value,
+            )?))
+        }
+    }
+}
+
+/// [Filter] validated and converted into the values used in the SQL query
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryReadyFilter {
+    Attribute { key: String, value: AttributeValue },
+    SpanName(TextCondition),
+    Span(SpanCondition),
+    EventName(EventCondition),
+    And(Vec<QueryReadyFilter>),
+    Or(Vec<QueryReadyFilter>),
+    Not(Box<QueryReadyFilter>),
+}
+
+/// [api_structs::SpanFilter] validated, durations in nanoseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanCondition {
+    name: Option<TextCondition>,
+    min_duration: Option<i64>,
+    max_duration: Option<i64>,
+    attributes: Vec<(String, AttributeValue)>,
+}
+
+impl SpanCondition {
+    fn from_filter(filter: SpanFilter, text_matches: FilterTextMatches) -> Result<Self, ApiError> {
+        let duration = |duration: Option<u64>| {
+            duration
+                .map(|duration| {
+                    i64::try_from(duration).map_err(|_| ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Invalid span duration".to_string(),
+                    })
+                })
+                .transpose()
+        };
+        let min_duration = duration(filter.min_duration)?;
+        let max_duration = duration(filter.max_duration)?;
+        if let (Some(min_duration), Some(max_duration)) = (min_duration, max_duration) {
+            if min_duration > max_duration {
+                return Err(ApiError {
+                    code: StatusCode::BAD_REQUEST,
+                    message: "Span min duration must not be above the max duration".to_string(),
+                });
+            }
+        }
+        let attributes = filter
+            .attributes
+            .into_iter()
+            .map(|attribute| {
+                if attribute.key.is_empty() {
+                    return Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Span attribute filter is missing the key".to_string(),
+                    });
+                }
+                let value = AttributeValue::from_search(
+                    attribute.comparison,
+                    text_matches.attribute_value,
+                    &attribute.value,
+                )?;
+                Ok((attribute.key, value))
+            })
+            .collect::<Result<Vec<_>, ApiError>>()?;
+        let name = TextCondition::new(text_matches.span_name, filter.name);
+        if name.is_none()
+            && min_duration.is_none()
+            && max_duration.is_none()
+            && attributes.is_empty()
+        {
+            return Err(ApiError {
+                code: StatusCode::BAD_REQUEST,
+                message: "Span filter needs a name, a duration or an attribute".to_string(),
+            });
+        }
+        Ok(Self {
+            name,
+            min_duration,
+            max_duration,
+            attributes,
+        })
+    }
+
+    /// Pushes ` and <condition>` for each part, the query must have the `span` table in scope
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        if let Some(name) = &self.name {
+            query.push(" and ");
+            name.push_condition(query, "span.name");
+        }
+        if let Some(min_duration) = self.min_duration {
+            query.push(" and span.duration >= ");
+            query.push_bind(min_duration);
+        }
+        if let Some(max_duration) = self.max_duration {
+            query.push(" and span.duration <= ");
+            query.push_bind(max_duration);
+        }
+        for (key, value) in &self.attributes {
+            query.push(" and exists (select 1 from span_key_value where span_key_value.trace_id = span.trace_id and span_key_value.span_id = span.id and span_key_value.key = ");
+            query.push_bind(key.clone());
+            push_value_condition(query, "span_key_value", value);
+            query.push(")");
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventCondition {
+    /// to_tsquery expression over the message and string attribute values
+    Words(String),
+    Message(TextCondition),
+}
+
+/// How the text filters compare their text, from [api_structs::SearchFor]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilterTextMatches {
+    pub span_name: TextMatch,
+    /// None for the word search
+    pub event: Option<TextMatch>,
+    pub attribute_value: TextMatch,
+}
+
+fn count_conditions(filter: &Filter) -> usize {
+    match filter {
+        Filter::Attribute(_) | Filter::SpanName(_) | Filter::EventName(_) => 1,
+        Filter::Span(span) => 1 + span.attributes.len(),
+        Filter::And(filters) | Filter::Or(filters) => {
+            1 + filters.iter().map(count_conditions).sum::<usize>()
+        }
+        Filter::Not(filter) => 1 + count_conditions(filter),
+    }
+}
+
+impl QueryReadyFilter {
+    /// All filters must match, so they are combined into a single And
+    pub fn from_filters(
+        filters: Vec<Filter>,
+        text_matches: FilterTextMatches,
+    ) -> Result<Option<Self>, ApiError> {
+        if filters.is_empty() {
+            return Ok(None);
+        }
+        let conditions: usize = filters.iter().map(count_conditions).sum();
+        if conditions > MAX_FILTER_CONDITIONS {
+            return Err(ApiError {
+                code: StatusCode::BAD_REQUEST,
+                message: format!(
+                    "Search has {conditions} conditions, the maximum is {MAX_FILTER_CONDITIONS}"
+                ),
+            });
+        }
+        Ok(Some(Self::from_filter(Filter::And(filters), text_matches)?))
+    }
+
+    fn from_filter(filter: Filter, text_matches: FilterTextMatches) -> Result<Self, ApiError> {
+        match filter {
+            Filter::Attribute(attribute) => {
+                if attribute.key.is_empty() {
+                    return Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Attribute filter is missing the key".to_string(),
+                    });
+                }
+                let value = AttributeValue::from_search(
+                    attribute.comparison,
+                    text_matches.attribute_value,
+                    &attribute.value,
+                )?;
+                Ok(Self::Attribute {
+                    key: attribute.key,
+                    value,
+                })
+            }
+            Filter::SpanName(name) => {
+                if name.is_empty() {
+                    return Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Span filter is missing the span name".to_string(),
+                    });
+                }
+                Ok(Self::SpanName(TextCondition {
+                    text_match: text_matches.span_name,
+                    text: name,
+                }))
+            }
+            Filter::Span(span) => Ok(Self::Span(SpanCondition::from_filter(span, text_matches)?)),
+            Filter::EventName(name) => {
+                if name.is_empty() {
+                    return Err(ApiError {
+                        code: StatusCode::BAD_REQUEST,
+                        message: "Event filter is missing the text".to_string(),
+                    });
+                }
+                match text_matches.event {
+                    None => Ok(Self::EventName(EventCondition::Words(to_tsquery(&name)?))),
+                    Some(text_match) => {
+                        Ok(Self::EventName(EventCondition::Message(TextCondition {
+                            text_match,
+                            text: name,
+                        })))
+                    }
+                }
+            }
+            Filter::And(filters) => Ok(Self::And(
+                filters
+                    .into_iter()
+                    .map(|filter| Self::from_filter(filter, text_matches))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            Filter::Or(filters) => Ok(Self::Or(
+                filters
+                    .into_iter()
+                    .map(|filter| Self::from_filter(filter, text_matches))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            Filter::Not(filter) => Ok(Self::Not(Box::new(Self::from_filter(
+                *filter,
+                text_matches,
+            )?))),
+        }
+    }
+
+    /// (field, regex) pairs of all the regex conditions, see [validate_regexes]
+    pub fn regexes(&self) -> Vec<(&'static str, &str)> {
+        match self {
+            Self::Attribute {
+                value: AttributeValue::Text(condition),
+                ..
+            } => condition
+                .regex()
+                .map(|regex| ("attribute value", regex))
+                .into_iter()
+                .collect(),
+            Self::Attribute { .. } | Self::EventName(EventCondition::Words(_)) => vec![],
+            Self::SpanName(condition) => condition
+                .regex()
+                .map(|regex| ("span name", regex))
+                .into_iter()
+                .collect(),
+            Self::Span(span) => span
+                .name
+                .iter()
+                .filter_map(|name| name.regex().map(|regex| ("span name", regex)))
+                .chain(
+                    span.attributes
+                        .iter()
+                        .filter_map(|(_key, value)| match value {
+                            AttributeValue::Text(condition) => {
+                                condition.regex().map(|regex| ("attribute value", regex))
+                            }
+                            _ => None,
+                        }),
+                )
+                .collect(),
+            Self::EventName(EventCondition::Message(condition)) => condition
+                .regex()
+                .map(|regex| ("event", regex))
+                .into_iter()
+                .collect(),
+            Self::And(filters) | Self::Or(filters) => {
+                filters.iter().flat_map(|f| f.regexes()).collect()
+            }
+            Self::Not(filter) => filter.regexes(),
+        }
+    }
+
+    fn positive_conditions(&self) -> Vec<&QueryReadyFilter> {
+        match self {
+            Self::Attribute { .. } | Self::SpanName(_) | Self::Span(_) | Self::EventName(_) => {
+                vec![self]
+            }
+            Self::And(filters) | Self::Or(filters) => filters
+                .iter()
+                .flat_map(|f| f.positive_conditions())
+                .collect(),
+            Self::Not(_) => vec![],
+        }
+    }
+
+    /// Pushes a boolean SQL expression, the query must have the `trace` table in scope
+    pub fn push_condition(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Attribute { key, value } => {
+                query.push("(exists (select 1 from span_key_value where span_key_value.trace_id = trace.id and span_key_value.key = ");
+                query.push_bind(key.clone());
+                push_value_condition(query, "span_key_value", value);
+                query.push(") or exists (select 1 from event_key_value where event_key_value.trace_id = trace.id and event_key_value.key = ");
+                query.push_bind(key.clone());
+                push_value_condition(query, "event_key_value", value);
+                query.push("))");
+            }
+            Self::SpanName(condition) => {
+                query.push("exists (select 1 from span where span.trace_id = trace.id and ");
+                condition.push_condition(query, "span.name");
+                query.push(")");
+            }
+            Self::Span(span) => {
+                query.push("exists (select 1 from span where span.trace_id = trace.id");
+                span.push_conditions(query);
+                query.push(")");
+            }
+            Self::EventName(EventCondition::Message(condition)) => {
+                query.push("exists (select 1 from event where event.trace_id = trace.id and ");
+                condition.push_condition(query, "event.name");
+                query.push(")");
+            }
+            Self::EventName(EventCondition::Words(tsquery)) => {
+                query.push(format!(
+                    "(exists (select 1 from event where event.trace_id = trace.id and \
+                    to_tsvector('{TEXT_SEARCH_CONFIG}', event.name) @@ to_tsquery('{TEXT_SEARCH_CONFIG}', "
+                ));
+                query.push_bind(tsquery.clone());
+                query.push(format!(
+                    ")) or exists (select 1 from event_key_value where event_key_value.trace_id = trace.id and \
+                    event_key_value.value_type = 'string' and \
+                    to_tsvector('{TEXT_SEARCH_CONFIG}', event_key_value.value) @@ to_tsquery('{TEXT_SEARCH_CONFIG}', "
+                ));
+                query.push_bind(tsquery.clone());
+                query.push(")))");
+            }
+            Self::And(filters) => push_joined_conditions(query, filters, " and ", "true"),
+            Self::Or(filters) => push_joined_conditions(query, filters, " or ", "false"),
+            Self::Not(filter) => {
+                query.push("not (");
+                filter.push_condition(query);
+                query.push(")");
+            }
+        }
+    }
+
+    /// Pushes a lateral join named `matched_attribute` with the key and value of the first attribute
+    /// satisfying a positive attribute condition, so the grid can show why a trace matched
+    pub fn push_matched_attribute_join(
+        filter: Option<&QueryReadyFilter>,
+        query: &mut QueryBuilder<'_, Postgres>,
+    ) {
+        let conditions = filter.map(|f| f.positive_conditions()).unwrap_or_default();
+        let mut attribute_conditions = conditions.iter().filter_map(|c| match c {
+            Self::Attribute { key, value } => Some((key, value)),
+            _ => None,
+        });
+        query.push(" left join lateral (");
+        let Some(first) = attribute_conditions.next() else {
+            query.push("select null::TEXT as key, null::TEXT as value) matched_attribute on true");
+            return;
+        };
+        for (idx, (key, value)) in std::iter::once(first)
+            .chain(attribute_conditions)
+            .enumerate()
+        {
+            if idx > 0 {
+                query.push(" union all ");
+            }
+            for (table_idx, table) in ["span_key_value", "event_key_value"].iter().enumerate() {
+                if table_idx > 0 {
+                    query.push(" union all ");
+                }
+                query.push(format!("(select {table}.key::TEXT, {table}.value::TEXT from {table} where {table}.trace_id = trace.id and {table}.key = "));
+                query.push_bind(key.clone());
+                push_value_condition(query, table, value);
+                query.push(" limit 1)");
+            }
+        }
+        query.push(" limit 1) matched_attribute on true");
+    }
+
+    /// Pushes lateral joins named `matched_span` and `matched_event`. The first has the name and id
+    /// of the slowest span satisfying a positive span condition, the second a highlighted snippet of the event
+    /// best matching the positive event conditions and its relevance, the rank scaled to a BIGINT.
+    pub fn push_matched_span_and_event_joins(
+        filter: Option<&QueryReadyFilter>,
+        query: &mut QueryBuilder<'_, Postgres>,
+    ) {
+        let conditions = filter.map(|f| f.positive_conditions()).unwrap_or_default();
+        let span_conditions: Vec<&QueryReadyFilter> = conditions
+            .iter()
+            .filter(|c| matches!(c, Self::SpanName(_) | Self::Span(_)))
+            .copied()
+            .collect();
+        let event_tsqueries: Vec<String> = conditions
+            .iter()
+            .filter_map(|c| match c {
+                Self::EventName(EventCondition::Words(tsquery)) => Some(format!("({tsquery})")),
+                _ => None,
+            })
+            .collect();
+        let event_messages: Vec<&TextCondition> = conditions
+            .iter()
+            .filter_map(|c| match c {
+                Self::EventName(EventCondition::Message(condition)) => Some(condition),
+                _ => None,
+            })
+            .collect();
+        query.push(" left join lateral (");
+        if span_conditions.is_empty() {
+            query.push("select null::TEXT as name, null::BIGINT as id");
+        } else {
+            query.push(
+                "select span.name::TEXT, span.id from span where span.trace_id = trace.id and (",
+            );
+            for (idx, condition) in span_conditions.iter().enumerate() {
+                if idx > 0 {
+                    query.push(" or ");
+                }
+                match condition {
+                    Self::SpanName(name) => name.push_condition(query, "span.name"),
+                    Self::Span(span) => {
+                        query.push("(true");
+                        span.push_conditions(query);
+                        query.push(")");
+                    }
+                    _ => unreachable!("only span conditions were kept"),
+                }
+            }
+            query.push(") order by span.duration desc limit 1");
+        }
+        query.push(") matched_span on true left join lateral (");
+        if !event_messages.is_empty() {
+            query.push("select event.name::TEXT, 0::BIGINT as relevance from event where event.trace_id = trace.id and ");
+            push_any_text_condition(query, &event_messages, "event.name");
+            query.push(" order by event.timestamp limit 1");
+        } else if event_tsqueries.is_empty() {
+            query.push("select null::TEXT as name, null::BIGINT as relevance");
+        } else {
+            query.push(format!(
+                "select ts_headline('{TEXT_SEARCH_CONFIG}', event_text.text, search_query, "
+            ));
+            query.push_bind(headline_options());
+            query.push(format!(
+                ") as name,
+       (ts_rank(to_tsvector('{TEXT_SEARCH_CONFIG}', event_text.text), search_query) * {RELEVANCE_SCALE})::BIGINT as relevance
+from event,
+     to_tsquery('{TEXT_SEARCH_CONFIG}', "
+            ));
+            query.push_bind(event_tsqueries.join(" | "));
+            query.push(format!(
+                ") search_query,
+     lateral (select concat_ws(' ', event.name, (select string_agg(event_key_value.value, ' ')
+                                                 from event_key_value
+                                                 where event_key_value.trace_id = event.trace_id
+                                                   and event_key_value.span_id = event.span_id
+                                                   and event_key_value.event_id = event.id
+                                                   and event_key_value.value_type = 'string')) as text) event_text
+where event.trace_id = trace.id
+  and to_tsvector('{TEXT_SEARCH_CONFIG}', event_text.text) @@ search_query
+order by relevance desc, event.timestamp
+limit 1"
+            ));
+        }
+        query.push(") matched_event on true");
+    }
+}
+
+/// Pushes `(<condition> or <condition>...)` on `column`
+fn push_any_text_condition(
+    query: &mut QueryBuilder<'_, Postgres>,
+    conditions: &[&TextCondition],
+    column: &str,
+) {
+    query.push("(");
+    for (idx, condition) in conditions.iter().enumerate() {
+        if idx > 0 {
+            query.push(" or ");
+        }
+        condition.push_condition(query, column);
+    }
+    query.push(")");
+}
+
+fn push_joined_conditions(
+    query: &mut QueryBuilder<'_, Postgres>,
+    filters: &[QueryReadyFilter],
+    separator: &str,
+    empty: &str,
+) {
+    if filters.is_empty() {
+        query.push(empty);
+        return;
+    }
+    query.push("(");
+    for (idx, filter) in filters.iter().enumerate() {
+        if idx > 0 {
+            query.push(separator);
+        }
+        filter.push_condition(query);
+    }
+    query.push(")");
+}
+
+/// Pushes ` and <condition>` on the value columns of `table`
+pub fn push_value_condition(
+    query: &mut QueryBuilder<'_, Postgres>,
+    table: &str,
+    value: &AttributeValue,
+) {
+    match value {
+        AttributeValue::Any => {}
+        AttributeValue::Text(condition) => {
+            query.push(" and ");
+            condition.push_condition(query, &format!("{table}.value"));
+        }
+        AttributeValue::Typed(TypedValueFilter {
+            text: Some((text, value_type)),
+            numeric: Some(numeric),
+            operator,
+        }) => {
+            query.push(format!(
+                " and (({table}.value_type in ('i64', 'f64') and numeric_value({table}.value_type, {table}.value) {operator} "
+            ));
+            query.push_bind(numeric.clone());
+            query.push(format!("::NUMERIC) or ({table}.value_type = "));
+            query.push_bind(*value_type);
+            query.push(format!("::value_type and {table}.value {operator} "));
+            query.push_bind(text.clone());
+            query.push("))");
+        }
+        AttributeValue::Typed(TypedValueFilter {
+            text: Some((text, value_type)),
+            numeric: None,
+            operator,
+        }) => {
+            query.push(format!(" and {table}.value_type = "));
+            query.push_bind(*value_type);
+            query.push(format!("::value_type and {table}.value {operator} "));
+            query.push_bind(text.clone());
+        }
+        AttributeValue::Typed(TypedValueFilter {
+            text: None,
+            numeric: Some(numeric),
+            operator,
+        }) => {
+            query.push(format!(
+                " and {table}.value_type in ('i64', 'f64') and numeric_value({table}.value_type, {table}.value) {operator} "
+            ));
+            query.push_bind(numeric.clone());
+            query.push("::NUMERIC");
+        }
+        AttributeValue::Typed(TypedValueFilter {
+            text: None,
+            numeric: None,
+            ..
+        }) => {
+            unreachable!("TypedValueFilter always has text or numeric set")
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn typed_value_filter_uses_value_type() {
+    let bool_filter = TypedValueFilter::from_search(ValueComparison::Equal, " FALSE").unwrap();
+    assert_eq!(bool_filter.text, Some(("false".to_string(), "bool")));
+    let int_filter = TypedValueFilter::from_search(ValueComparison::GreaterThan, "500").unwrap();
+    assert_eq!(int_filter.numeric, Some("500".to_string()));
+    assert_eq!(int_filter.operator, ">");
+    let float_filter = TypedValueFilter::from_search(ValueComparison::LessThan, "1e3").unwrap();
+    assert_eq!(float_filter.numeric, Some("1000".to_string()));
+    let string_filter = TypedValueFilter::from_search(ValueComparison::NotEqual, "acme").unwrap();
+    assert_eq!(string_filter.text, Some(("acme".to_string(), "string")));
+    assert_eq!(string_filter.operator, "<>");
+    assert!(TypedValueFilter::from_search(ValueComparison::GreaterThan, "acme").is_err());
+    let zip_filter = TypedValueFilter::from_search(ValueComparison::Equal, "00123").unwrap();
+    assert_eq!(zip_filter.numeric, Some("123".to_string()));
+    assert_eq!(zip_filter.text, Some(("00123".to_string(), "string")));
+    let mut query = QueryBuilder::<Postgres>::new("");
+    push_value_condition(
+        &mut query,
+        "span_key_value",
+        &AttributeValue::Typed(zip_filter),
+    );
+    assert_eq!(
+        query.sql(),
+        " and ((span_key_value.value_type in ('i64', 'f64') and \
+        numeric_value(span_key_value.value_type, span_key_value.value) = $1::NUMERIC) or \
+        (span_key_value.value_type = $2::value_type and span_key_value.value = $3))"
+    );
+    assert_eq!(int_filter.text, None);
+}
+
+#[cfg(test)]
+#[test]
+fn filters_render_boolean_sql() {
+    let filter = QueryReadyFilter::from_filters(
+        vec![
+            Filter::Or(vec![
+                Filter::SpanName("db_query".to_string()),
+                Filter::EventName("timeout".to_string()),
+            ]),
+            Filter::Not(Box::new(Filter::Attribute(api_structs::AttributeFilter {
+                key: "endpoint".to_string(),
+                comparison: ValueComparison::Equal,
+                value: "/health".to_string(),
+            }))),
+        ],
+        FilterTextMatches {
+            span_name: TextMatch::Exact,
+            event: None,
+            attribute_value: TextMatch::Contains,
+        },
+    )
+    .unwrap()
+    .unwrap();
+    let mut query = QueryBuilder::<Postgres>::new("");
+    filter.push_condition(&mut query);
+    assert_eq!(
+        query.sql(),
+        "((exists (select 1 from span where span.trace_id = trace.id and span.name = $1) or \
+        (exists (select 1 from event where event.trace_id = trace.id and \
+        to_tsvector('simple', event.name) @@ to_tsquery('simple', $2)) or \
+        exists (select 1 from event_key_value where event_key_value.trace_id = trace.id and \
+        event_key_value.value_type = 'string' and \
+        to_tsvector('simple', event_key_value.value) @@ to_tsquery('simple', $3)))) and \
+        not ((exists (select 1 from span_key_value where span_key_value.trace_id = trace.id and span_key_value.key = $4 \
+        and span_key_value.value_type = $5::value_type and span_key_value.value = $6) or \
+        exists (select 1 from event_key_value where event_key_value.trace_id = trace.id and event_key_value.key = $7 \
+        and event_key_value.value_type = $8::value_type and event_key_value.value = $9))))"
+    );
+}
+
+#[cfg(test)]
+#[test]
+fn text_filters_use_their_match_mode() {
+    let filter = QueryReadyFilter::from_filters(
+        vec![
+            Filter::SpanName("^db_(query|exec)$".to_string()),
+            Filter::EventName("Query took".to_string()),
+            Filter::Attribute(api_structs::AttributeFilter {
+                key: "endpoint".to_string(),
+                comparison: ValueComparison::Contains,
+                value: "/api".to_string(),
+            }),
+        ],
+        FilterTextMatches {
+            span_name: TextMatch::Regex,
+            event: Some(TextMatch::Contains),
+            attribute_value: TextMatch::Prefix,
+        },
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(filter.regexes(), vec![("span name", "^db_(query|exec)$")]);
+    let mut query = QueryBuilder::<Postgres>::new("");
+    filter.push_condition(&mut query);
+    assert_eq!(
+        query.sql(),
+        "(exists (select 1 from span where span.trace_id = trace.id and span.name ~ $1) and \
+        exists (select 1 from event where event.trace_id = trace.id and event.name ilike $2) and \
+        (exists (select 1 from span_key_value where span_key_value.trace_id = trace.id and span_key_value.key = $3 \
+        and span_key_value.value ilike $4) or \
+        exists (select 1 from event_key_value where event_key_value.trace_id = trace.id and event_key_value.key = $5 \
+        and event_key_value.value ilike $6)))"
+    );
+    let prefix = TextCondition::new(TextMatch::Prefix, "Check".to_string()).unwrap();
+    assert_eq!(prefix.matches("checkout"), Some(true));
+    assert_eq!(prefix.matches("precheck"), Some(false));
+}
+
+#[cfg(test)]
+#[test]
+fn span_filter_matches_a_single_span() {
+    let filter = QueryReadyFilter::from_filters(
+        vec![Filter::Span(SpanFilter {
+            name: "db_query".to_string(),
+            min_duration: Some(500_000_000),
+            max_duration: None,
+            attributes: vec![api_structs::AttributeFilter {
+                key: "table".to_string(),
+                comparison: ValueComparison::Equal,
+                value: "orders".to_string(),
+            }],
+        })],
+        FilterTextMatches {
+            span_name: TextMatch::Exact,
+            event: None,
+            attribute_value: TextMatch::Contains,
+        },
+    )
+    .unwrap()
+    .unwrap();
+    let mut query = QueryBuilder::<Postgres>::new("");
+    filter.push_condition(&mut query);
+    assert_eq!(
+        query.sql(),
+        "(exists (select 1 from span where span.trace_id = trace.id and span.name = $1 and span.duration >= $2 \
+        and exists (select 1 from span_key_value where span_key_value.trace_id = span.trace_id and span_key_value.span_id = span.id \
+        and span_key_value.key = $3 and span_key_value.value_type = $4::value_type and span_key_value.value = $5)))"
+    );
+    let empty_span = SpanFilter {
+        name: String::new(),
+        min_duration: Some(2),
+        max_duration: Some(1),
+        attributes: vec![],
+    };
+    assert!(QueryReadyFilter::from_filters(
+        vec![Filter::Span(empty_span)],
+        FilterTextMatches {
+            span_name: TextMatch::Exact,
+            event: None,
+            attribute_value: TextMatch::Contains,
+        },
+    )
+    .is_err());
+}
@@ -0,0 +1,152 @@
+use crate::api::{u64_to_naive_date_time, ApiError};
+use api_structs::{StorageUsage, StorageUsageReport, StorageUsageRequest, StorageUsageTrendPoint};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use tracing::{info, instrument};
+
+/// How many points the storage trend is split into, regardless of the window size
+const TREND_BUCKETS: i64 = 48;
+const MIN_TREND_BUCKET_SIZE_NANOS: i64 = 60 * 1_000_000_000;
+
+struct RawDbStorageUsage {
+    service_name: String,
+    top_level_span_name: String,
+    bucket: i64,
+    traces: i64,
+    spans: i64,
+    events: i64,
+    attributes: i64,
+    estimated_bytes: i64,
+}
+
+/// Counts are per service, top level span and trend bucket, the estimated bytes are the
+/// size of the rows themselves (including tuple headers) and do not account for indexes.
+#[instrument(skip_all)]
+async fn get_storage_usage_per_bucket(
+    con: &PgPool,
+    from: i64,
+    to: i64,
+    bucket_size_nanos: i64,
+) -> Result<Vec<RawDbStorageUsage>, ApiError> {
+    Ok(sqlx::query_as!(
+        RawDbStorageUsage,
+        "with window_trace as (select trace.id,
+                             trace.service_name,
+                             trace.top_level_span_name,
+                             (trace.timestamp - $1::BIGINT) / $3::BIGINT as bucket,
+                             pg_column_size(trace.*)                    as bytes
+                      from trace
+                      where trace.timestamp >= $1::BIGINT
+                        and trace.timestamp <= $2::BIGINT),
+     span_usage as (select span.trace_id, count(*) as rows, sum(pg_column_size(span.*)) as bytes
+                    from span
+                             inner join window_trace on span.trace_id = window_trace.id
+                    group by span.trace_id),
+     span_key_value_usage as (select span_key_value.trace_id,
+                                     count(*)                               as rows,
+                                     sum(pg_column_size(span_key_value.*)) as bytes
+                              from span_key_value
+                                       inner join window_trace on span_key_value.trace_id = window_trace.id
+                              group by span_key_value.trace_id),
+     event_usage as (select event.trace_id, count(*) as rows, sum(pg_column_size(event.*)) as bytes
+                     from event
+                              inner join window_trace on event.trace_id = window_trace.id
+                     group by event.trace_id),
+     event_key_value_usage as (select event_key_value.trace_id,
+                                      count(*)                                as rows,
+                                      sum(pg_column_size(event_key_value.*)) as bytes
+                               from event_key_value
+                                        inner join window_trace on event_key_value.trace_id = window_trace.id
+                               group by event_key_value.trace_id)
+select window_trace.service_name,
+       window_trace.top_level_span_name,
+       window_trace.bucket                                                  as \"bucket!\",
+       count(*)                                                             as \"traces!\",
+       COALESCE(sum(span_usage.rows), 0)::BIGINT                            as \"spans!\",
+       COALESCE(sum(event_usage.rows), 0)::BIGINT                           as \"events!\",
+       (COALESCE(sum(span_key_value_usage.rows), 0) +
+        COALESCE(sum(event_key_value_usage.rows), 0))::BIGINT               as \"attributes!\",
+       (sum(window_trace.bytes) +
+        COALESCE(sum(span_usage.bytes), 0) +
+        COALESCE(sum(span_key_value_usage.bytes), 0) +
+        COALESCE(sum(event_usage.bytes), 0) +
+        COALESCE(sum(event_key_value_usage.bytes), 0))::BIGINT              as \"estimated_bytes!\"
+from window_trace
+         left join span_usage on span_usage.trace_id = window_trace.id
+         left join span_key_value_usage on span_key_value_usage.trace_id = window_trace.id
+         left join event_usage on event_usage.trace_id = window_trace.id
+         left join event_key_value_usage on event_key_value_usage.trace_id = window_trace.id
+group by window_trace.service_name, window_trace.top_level_span_name, window_trace.bucket;",
+        from,
+        to,
+        bucket_size_nanos
+    )
+    .fetch_all(con)
+    .await?)
+}
+
+#[instrument(skip_all)]
+pub async fn storage_usage(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(storage_usage_request): Json<StorageUsageRequest>,
+) -> Result<Json<StorageUsageReport>, ApiError> {
+    let from = u64_to_naive_date_time(storage_usage_request.from_date_unix)?.timestamp_nanos();
+    let to = u64_to_naive_date_time(storage_usage_request.to_date_unix)?.timestamp_nanos();
+    if from > to {
+        return Err(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: "Start date must be before the end date".to_string(),
+        });
+    }
+    let bucket_size_nanos = ((to - from) / TREND_BUCKETS).max(MIN_TREND_BUCKET_SIZE_NANOS);
+    info!("Getting storage usage with bucket size of {bucket_size_nanos}ns");
+    let per_bucket = get_storage_usage_per_bucket(&con, from, to, bucket_size_nanos).await?;
+
+    let mut usage: HashMap<(String, String), StorageUsage> = HashMap::new();
+    let mut trend: BTreeMap<(String, i64), StorageUsageTrendPoint> = BTreeMap::new();
+    for row in per_bucket {
+        let service_usage = usage
+            .entry((row.service_name.clone(), row.top_level_span_name.clone()))
+            .or_insert(StorageUsage {
+                service_name: row.service_name.clone(),
+                top_level_span_name: row.top_level_span_name,
+                traces: 0,
+                spans: 0,
+                events: 0,
+                attributes: 0,
+                estimated_bytes: 0,
+            });
+        service_usage.traces += row.traces;
+        service_usage.spans += row.spans;
+        service_usage.events += row.events;
+        service_usage.attributes += row.attributes;
+        service_usage.estimated_bytes += row.estimated_bytes;
+        let bucket_start_unix = from + row.bucket * bucket_size_nanos;
+        let trend_point = trend
+            .entry((row.service_name.clone(), row.bucket))
+            .or_insert(StorageUsageTrendPoint {
+                service_name: row.service_name,
+                bucket_start_unix: u64::try_from(bucket_start_unix)
+                    .expect("bucket start to fit u64"),
+                traces: 0,
+                spans: 0,
+                events: 0,
+                attributes: 0,
+                estimated_bytes: 0,
+            });
+        trend_point.traces += row.traces;
+        trend_point.spans += row.spans;
+        trend_point.events += row.events;
+        trend_point.attributes += row.attributes;
+        trend_point.estimated_bytes += row.estimated_bytes;
+    }
+    let mut usage: Vec<StorageUsage> = usage.into_values().collect();
+    usage.sort_by_key(|u| std::cmp::Reverse(u.estimated_bytes));
+    Ok(Json(StorageUsageReport {
+        bucket_size_nanos: u64::try_from(bucket_size_nanos).expect("bucket size to fit u64"),
+        usage,
+        trend: trend.into_values().collect(),
+    }))
+}
@@ -0,0 +1,256 @@
+use crate::api::live_tail::{may_match, new_traces_query_params};
+use crate::api::ApiError;
+use crate::notification_worthy_events::{NotificationWorthyEventsPusher, SubscriptionMatches};
+use crate::otel_trace_processing::InsertedTrace;
+use api_structs::{
+    DeleteSearchSubscriptionRequest, GridSearchState, NewSearchSubscription, SearchFor,
+    SearchSubscription,
+};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::types::Json as DbJson;
+use sqlx::{PgPool, QueryBuilder};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::task::JoinHandle;
+use tracing::{error, info, instrument, warn};
+
+/// Postgres error code of a foreign key pointing to a missing row
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+/// Notifying more often than this is just noise
+const MIN_INTERVAL_SECONDS: u64 = 10;
+
+struct RawDbSearchSubscription {
+    id: i64,
+    saved_search_id: i64,
+    saved_search_name: String,
+    owner: String,
+    min_interval_seconds: i64,
+    created_at: i64,
+}
+
+impl RawDbSearchSubscription {
+    fn into_search_subscription(self) -> SearchSubscription {
+        SearchSubscription {
+            id: self.id,
+            saved_search_id: self.saved_search_id,
+            saved_search_name: self.saved_search_name,
+            owner: self.owner,
+            min_interval_seconds: u64::try_from(self.min_interval_seconds)
+                .expect("min_interval_seconds to fit u64"),
+            created_at: u64::try_from(self.created_at).expect("created_at to fit u64"),
+        }
+    }
+}
+
+/// Ordered by the name of the saved search
+#[instrument(skip_all)]
+pub async fn search_subscriptions(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+) -> Result<Json<Vec<SearchSubscription>>, ApiError> {
+    let subscriptions = sqlx::query_as!(
+        RawDbSearchSubscription,
+        r#"select search_subscription.id,
+       search_subscription.saved_search_id,
+       saved_search.name::TEXT as "saved_search_name!",
+       saved_search.owner::TEXT as "owner!",
+       search_subscription.min_interval_seconds,
+       search_subscription.created_at
+from search_subscription
+         inner join saved_search on saved_search.id = search_subscription.saved_search_id
+order by saved_search.name, saved_search.owner"#
+    )
+    .fetch_all(&con)
+    .await?;
+    Ok(Json(
+        subscriptions
+            .into_iter()
+            .map(RawDbSearchSubscription::into_search_subscription)
+            .collect(),
+    ))
+}
+
+#[instrument(skip_all)]
+pub async fn subscribe(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<NewSearchSubscription>,
+) -> Result<Json<SearchSubscription>, ApiError> {
+    if request.min_interval_seconds < MIN_INTERVAL_SECONDS {
+        return Err(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("Subscription interval must be at least {MIN_INTERVAL_SECONDS}s"),
+        });
+    }
+    let min_interval_seconds =
+        i64::try_from(request.min_interval_seconds).map_err(|_| ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: "Subscription interval is too long".to_string(),
+        })?;
+    let subscription = sqlx::query_as!(
+        RawDbSearchSubscription,
+        r#"with subscription as (
+    insert into search_subscription (saved_search_id, min_interval_seconds, created_at)
+        values ($1::BIGINT, $2::BIGINT, $3::BIGINT)
+        on conflict (saved_search_id) do update set min_interval_seconds = excluded.min_interval_seconds
+        returning id, saved_search_id, min_interval_seconds, created_at)
+select subscription.id,
+       subscription.saved_search_id,
+       saved_search.name::TEXT as "saved_search_name!",
+       saved_search.owner::TEXT as "owner!",
+       subscription.min_interval_seconds,
+       subscription.created_at
+from subscription
+         inner join saved_search on saved_search.id = subscription.saved_search_id"#,
+        request.saved_search_id,
+        min_interval_seconds,
+        chrono::Utc::now().timestamp_nanos()
+    )
+    .fetch_one(&con)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) => {
+            ApiError {
+                code: StatusCode::NOT_FOUND,
+                message: format!("No saved search with id {}", request.saved_search_id),
+            }
+        }
+        e => ApiError::from(e),
+    })?;
+    Ok(Json(subscription.into_search_subscription()))
+}
+
+#[instrument(skip_all)]
+pub async fn unsubscribe(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<DeleteSearchSubscriptionRequest>,
+) -> Result<Json<()>, ApiError> {
+    let deleted = sqlx::query!(
+        "delete from search_subscription where id = $1::BIGINT",
+        request.id
+    )
+    .execute(&con)
+    .await?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(ApiError {
+            code: StatusCode::NOT_FOUND,
+            message: format!("No subscription with id {}", request.id),
+        });
+    }
+    Ok(Json(()))
+}
+
+/// Subscription with the search it watches
+struct ActiveSubscription {
+    id: i64,
+    search_name: String,
+    min_interval_seconds: i64,
+    state: DbJson<GridSearchState>,
+}
+
+/// Evaluates the subscriptions against each batch of stored traces, pushing the matches to the
+/// notifier which rate limits them
+pub fn start_subscriptions_task(
+    con: PgPool,
+    mut stored_traces: Receiver<Vec<InsertedTrace>>,
+    notification_pusher: NotificationWorthyEventsPusher,
+) -> JoinHandle<()> {
+    info!("Starting search subscriptions task");
+    tokio::spawn(async move {
+        loop {
+            match stored_traces.recv().await {
+                Ok(traces) => {
+                    notify_matching_subscriptions(&con, &traces, &notification_pusher).await
+                }
+                Err(RecvError::Lagged(skipped_batches)) => {
+                    warn!("Search subscriptions fell behind, skipped {skipped_batches} batches");
+                }
+                Err(RecvError::Closed) => {
+                    error!("Trace storage is gone, stopping search subscriptions");
+                    return;
+                }
+            }
+        }
+    })
+}
+
+#[instrument(skip_all)]
+async fn notify_matching_subscriptions(
+    con: &PgPool,
+    traces: &[InsertedTrace],
+    notification_pusher: &NotificationWorthyEventsPusher,
+) {
+    let subscriptions = sqlx::query_as!(
+        ActiveSubscription,
+        r#"select search_subscription.id,
+       saved_search.name::TEXT as "search_name!",
+       search_subscription.min_interval_seconds,
+       saved_search.state as "state: DbJson<GridSearchState>"
+from search_subscription
+         inner join saved_search on saved_search.id = search_subscription.saved_search_id"#
+    )
+    .fetch_all(con)
+    .await;
+    let subscriptions = match subscriptions {
+        Ok(subscriptions) => subscriptions,
+        Err(e) => {
+            error!("Error loading search subscriptions: {:?}", e);
+            return;
+        }
+    };
+    for subscription in subscriptions {
+        match matching_traces(con, subscription.state.0.search_for, traces).await {
+            Ok(matching) if matching.is_empty() => {}
+            Ok(matching) => {
+                let min_interval = Duration::from_secs(
+                    u64::try_from(subscription.min_interval_seconds)
+                        .expect("min_interval_seconds to fit u64"),
+                );
+                notification_pusher
+                    .push_subscription_matches(
+                        subscription.id,
+                        SubscriptionMatches::new(subscription.search_name, min_interval, matching),
+                    )
+                    .await;
+            }
+            Err(e) => error!(
+                "Error matching traces of subscription {}: {:?}",
+                subscription.id, e
+            ),
+        }
+    }
+}
+
+/// The traces matching the search, its time range is ignored
+async fn matching_traces(
+    con: &PgPool,
+    search_for: SearchFor,
+    traces: &[InsertedTrace],
+) -> Result<Vec<InsertedTrace>, ApiError> {
+    let mut query_params = new_traces_query_params(con, search_for).await?;
+    let candidate_ids: Vec<i64> = traces
+        .iter()
+        .filter(|trace| may_match(&query_params, trace))
+        .map(|trace| trace.id)
+        .collect();
+    if candidate_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    query_params.trace_ids = Some(candidate_ids);
+    let mut query = QueryBuilder::new("select trace.id from trace where ");
+    query_params.push_trace_conditions(&mut query);
+    let matching_ids: HashSet<i64> = query
+        .build_query_as::<(i64,)>()
+        .fetch_all(con)
+        .await?
+        .into_iter()
+        .map(|(id,)| id)
+        .collect();
+    Ok(traces
+        .iter()
+        .filter(|trace| matching_ids.contains(&trace.id))
+        .cloned()
+        .collect())
+}
@@ -0,0 +1,100 @@
+use crate::api::ApiError;
+use api_structs::{HIGHLIGHT_END, HIGHLIGHT_START};
+use axum::http::StatusCode;
+
+/// Text search configuration, `simple` doesn't stem or drop stop words, which suits log messages
+pub const TEXT_SEARCH_CONFIG: &str = "simple";
+
+/// One term of an event search
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    /// Words in double quotes, they must appear next to each other and in this order
+    Phrase(String),
+    Word(String),
+}
+
+/// Splits the search into words and "quoted phrases", a trailing `*` makes the term a prefix
+fn parse_terms(search: &str) -> Vec<(Term, bool)> {
+    let mut terms = vec![];
+    let mut chars = search.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let term = if c == '"' {
+            let phrase: String = chars.by_ref().take_while(|c| *c != '"').collect();
+            Term::Phrase(phrase)
+        } else {
+            let mut word = c.to_string();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != '"') {
+                word.push(c);
+            }
+            Term::Word(word)
+        };
+        let mut prefix = false;
+        while chars.next_if_eq(&'*').is_some() {
+            prefix = true;
+        }
+        let (Term::Phrase(text) | Term::Word(text)) = &term;
+        let trimmed = text.trim_end_matches('*');
+        prefix |= trimmed.len() != text.len();
+        // terms of only punctuation have no lexemes and would match nothing
+        if !trimmed.chars().any(char::is_alphanumeric) {
+            continue;
+        }
+        let term = match term {
+            Term::Phrase(_) => Term::Phrase(trimmed.to_string()),
+            Term::Word(_) => Term::Word(trimmed.to_string()),
+        };
+        terms.push((term, prefix));
+    }
+    terms
+}
+
+/// Converts an event search into a `to_tsquery` expression where all terms must match.
+/// Each term is quoted so postgres splits it into lexemes with the same parser used on the
+/// messages, words like `db.query` or `user_id=42` match the same way they were indexed.
+pub fn to_tsquery(search: &str) -> Result<String, ApiError> {
+    let terms = parse_terms(search);
+    if terms.is_empty() {
+        return Err(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("Event search \"{search}\" has no words to search for"),
+        });
+    }
+    Ok(terms
+        .into_iter()
+        .map(|(term, prefix)| {
+            let (Term::Phrase(text) | Term::Word(text)) = term;
+            let escaped = text.replace('\\', "\\\\").replace('\'', "''");
+            if prefix {
+                format!("'{escaped}':*")
+            } else {
+                format!("'{escaped}'")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" & "))
+}
+
+/// `ts_headline` options marking the matched words with [HIGHLIGHT_START] and [HIGHLIGHT_END]
+pub fn headline_options() -> String {
+    format!(
+        "StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_END}, MaxWords=20, MinWords=8, \
+        MaxFragments=2, FragmentDelimiter=\" ... \""
+    )
+}
+
+#[cfg(test)]
+#[test]
+fn event_search_syntax_to_tsquery() {
+    assert_eq!(
+        to_tsquery(r#"timeout "connection reset"  db.quer* it's -- "#).unwrap(),
+        r#"'timeout' & 'connection reset' & 'db.quer':* & 'it''s'"#
+    );
+    assert_eq!(
+        to_tsquery(r#""user_id=4"* back\slash"#).unwrap(),
+        r#"'user_id=4':* & 'back\\slash'"#
+    );
+    assert!(to_tsquery(r#" "" * - "#).is_err());
+}
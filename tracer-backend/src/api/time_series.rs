@@ -0,0 +1,196 @@
+use crate::api::{u64_to_naive_date_time, ApiError};
+use crate::otel_trace_processing::rollup::{histogram_percentile, NANOS_IN_1_MINUTE};
+use api_structs::{TimeSeries, TimeSeriesPoint, TimeSeriesRequest};
+use axum::http::StatusCode;
+use axum::Json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing::{info, instrument};
+
+/// The bucket size is the smallest of these giving at most MAX_POINTS buckets
+const BUCKET_SIZES_MINUTES: [i64; 12] = [1, 2, 5, 10, 15, 30, 60, 120, 180, 360, 720, 1440];
+const MAX_POINTS: i64 = 120;
+
+struct RawDbTimeSeriesPoint {
+    bucket_start: i64,
+    count: i64,
+    error_count: i64,
+    warning_count: i64,
+    max_duration: i64,
+    duration_histogram: Vec<i64>,
+}
+
+fn bucket_size_nanos(from: i64, to: i64) -> i64 {
+    let window_minutes = (to - from) / NANOS_IN_1_MINUTE + 1;
+    let minutes = BUCKET_SIZES_MINUTES
+        .into_iter()
+        .find(|size| window_minutes / size < MAX_POINTS)
+        .unwrap_or_else(|| {
+            let day = BUCKET_SIZES_MINUTES[BUCKET_SIZES_MINUTES.len() - 1];
+            (window_minutes / MAX_POINTS / day + 1) * day
+        });
+    minutes * NANOS_IN_1_MINUTE
+}
+
+#[instrument(skip_all)]
+async fn get_points(
+    con: &PgPool,
+    request: &TimeSeriesRequest,
+    from: i64,
+    to: i64,
+    bucket_size_nanos: i64,
+) -> Result<Vec<RawDbTimeSeriesPoint>, ApiError> {
+    let points = if let Some(span_name) = &request.span_name {
+        sqlx::query_as!(
+            RawDbTimeSeriesPoint,
+            "select (minute - minute % $6::BIGINT)::BIGINT  as \"bucket_start!\",
+       SUM(count)::BIGINT                        as \"count!\",
+       SUM(error_count)::BIGINT                  as \"error_count!\",
+       SUM(warning_count)::BIGINT                as \"warning_count!\",
+       MAX(max_duration)::BIGINT                 as \"max_duration!\",
+       sum_histograms(duration_histogram)        as \"duration_histogram!\"
+from span_rollup_minute
+where service_name = $1
+  and top_level_span_name = $2
+  and span_name = $3
+  and minute > $4::BIGINT - $7::BIGINT
+  and minute <= $5::BIGINT
+group by 1
+order by 1;",
+            request.service_name as _,
+            request.top_level_span_name as _,
+            span_name as _,
+            from,
+            to,
+            bucket_size_nanos,
+            NANOS_IN_1_MINUTE
+        )
+        .fetch_all(con)
+        .await?
+    } else {
+        sqlx::query_as!(
+            RawDbTimeSeriesPoint,
+            "select (minute - minute % $5::BIGINT)::BIGINT  as \"bucket_start!\",
+       SUM(count)::BIGINT                        as \"count!\",
+       SUM(error_count)::BIGINT                  as \"error_count!\",
+       SUM(warning_count)::BIGINT                as \"warning_count!\",
+       MAX(max_duration)::BIGINT                 as \"max_duration!\",
+       sum_histograms(duration_histogram)        as \"duration_histogram!\"
+from trace_rollup_minute
+where service_name = $1
+  and top_level_span_name = $2
+  and minute > $3::BIGINT - $6::BIGINT
+  and minute <= $4::BIGINT
+group by 1
+order by 1;",
+            request.service_name as _,
+            request.top_level_span_name as _,
+            from,
+            to,
+            bucket_size_nanos,
+            NANOS_IN_1_MINUTE
+        )
+        .fetch_all(con)
+        .await?
+    };
+    Ok(points)
+}
+
+#[instrument(skip_all)]
+async fn get_span_names(
+    con: &PgPool,
+    request: &TimeSeriesRequest,
+    from: i64,
+    to: i64,
+) -> Result<Vec<String>, ApiError> {
+    Ok(sqlx::query_scalar!(
+        "select distinct span_name::TEXT as \"span_name!\"
+from span_rollup_minute
+where service_name = $1
+  and top_level_span_name = $2
+  and minute > $3::BIGINT - $5::BIGINT
+  and minute <= $4::BIGINT
+order by 1;",
+        request.service_name as _,
+        request.top_level_span_name as _,
+        from,
+        to,
+        NANOS_IN_1_MINUTE
+    )
+    .fetch_all(con)
+    .await?)
+}
+
+/// Answered from the per minute rollups, so buckets are at least a minute wide and the
+/// percentiles are approximated from the duration histogram
+#[instrument(skip_all)]
+pub async fn time_series(
+    axum::extract::State(con): axum::extract::State<PgPool>,
+    Json(request): Json<TimeSeriesRequest>,
+) -> Result<Json<TimeSeries>, ApiError> {
+    let from = u64_to_naive_date_time(request.from_date_unix)?.timestamp_nanos();
+    let to = u64_to_naive_date_time(request.to_date_unix)?.timestamp_nanos();
+    if from > to {
+        return Err(ApiError {
+            code: StatusCode::BAD_REQUEST,
+            message: "Start date must be before the end date".to_string(),
+        });
+    }
+    let bucket_size_nanos = bucket_size_nanos(from, to);
+    info!("Getting time series with bucket size of {bucket_size_nanos}ns");
+    let (points, span_names) = tokio::try_join!(
+        get_points(&con, &request, from, to, bucket_size_nanos),
+        get_span_names(&con, &request, from, to)
+    )?;
+    let mut points: HashMap<i64, RawDbTimeSeriesPoint> =
+        points.into_iter().map(|p| (p.bucket_start, p)).collect();
+    let first_bucket = from - from.rem_euclid(bucket_size_nanos);
+    let points = (first_bucket..=to)
+        .step_by(usize::try_from(bucket_size_nanos).expect("bucket size to fit usize"))
+        .map(|bucket_start| {
+            let bucket_start_unix = u64::try_from(bucket_start).expect("bucket start to fit u64");
+            let Some(p) = points.remove(&bucket_start) else {
+                return TimeSeriesPoint {
+                    bucket_start_unix,
+                    count: 0,
+                    error_count: 0,
+                    warning_count: 0,
+                    p50_duration: 0,
+                    p90_duration: 0,
+                    p99_duration: 0,
+                };
+            };
+            let max_duration = u64::try_from(p.max_duration).expect("duration to fit u64");
+            let percentile = |percentile: f64| {
+                histogram_percentile(&p.duration_histogram, percentile).min(max_duration)
+            };
+            TimeSeriesPoint {
+                bucket_start_unix,
+                count: p.count,
+                error_count: p.error_count,
+                warning_count: p.warning_count,
+                p50_duration: percentile(0.5),
+                p90_duration: percentile(0.9),
+                p99_duration: percentile(0.99),
+            }
+        })
+        .collect();
+    Ok(Json(TimeSeries {
+        bucket_size_nanos: u64::try_from(bucket_size_nanos).expect("bucket size to fit u64"),
+        points,
+        span_names,
+    }))
+}
+
+#[cfg(test)]
+#[test]
+fn bucket_size_keeps_the_point_count_bounded() {
+    let hour = 60 * NANOS_IN_1_MINUTE;
+    assert_eq!(bucket_size_nanos(0, hour), NANOS_IN_1_MINUTE);
+    assert_eq!(bucket_size_nanos(0, 24 * hour), 15 * NANOS_IN_1_MINUTE);
+    assert_eq!(bucket_size_nanos(0, 7 * 24 * hour), 2 * hour);
+    for days in [1, 3, 14, 30, 90, 365] {
+        let to = days * 24 * hour;
+        assert!(to / bucket_size_nanos(0, to) < MAX_POINTS, "{days} days");
+    }
+}
@@ -0,0 +1,416 @@
+use crate::api::{get_trace_spans, ApiError};
+use api_structs::{
+    AttributeDifference, CompareTracesRequest, ComparedSpan, CriticalPathSpan, Span,
+    SpanComparison, SpanSelfTime, TraceComparison, TraceId,
+};
+use axum::Json;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tracing::instrument;
+
+fn span_end(span: &Span) -> u64 {
+    span.timestamp.saturating_add(span.duration)
+}
+
+pub fn children_by_parent_id(spans: &[Span]) -> HashMap<u64, Vec<&Span>> {
+    let mut children_by_parent_id: HashMap<u64, Vec<&Span>> = HashMap::new();
+    for span in spans {
+        if let Some(parent_id) = span.parent_id {
+            children_by_parent_id
+                .entry(parent_id)
+                .or_default()
+                .push(span);
+        }
+    }
+    children_by_parent_id
+}
+
+/// Walks back from the end of the root span: a span contributes the time in which none of its
+/// children was running, and when children overlap the one finishing last is the one that
+/// was waited on. Children running past their parent are clipped to it.
+pub fn critical_path(spans: &[Span]) -> Vec<CriticalPathSpan> {
+    let Some(root) = spans.iter().find(|s| s.parent_id.is_none()) else {
+        return vec![];
+    };
+    let mut children_by_parent_id = children_by_parent_id(spans);
+    for children in children_by_parent_id.values_mut() {
+        children.sort_by_key(|child| std::cmp::Reverse(span_end(child)));
+    }
+    let mut path: Vec<(&Span, u64)> = vec![];
+    // explicit stack, traces can be deep enough to overflow the thread's stack otherwise
+    let mut to_visit: Vec<(&Span, u64)> = vec![(root, span_end(root))];
+    while let Some((span, end)) = to_visit.pop() {
+        let mut cursor = end.min(span_end(span));
+        let mut contribution = 0;
+        for child in children_by_parent_id.get(&span.id).into_iter().flatten() {
+            if child.timestamp >= cursor || span_end(child) <= span.timestamp {
+                continue;
+            }
+            let child_end = span_end(child).min(cursor);
+            contribution += cursor - child_end;
+            to_visit.push((child, child_end));
+            cursor = child.timestamp.max(span.timestamp);
+        }
+        contribution += cursor.saturating_sub(span.timestamp);
+        path.push((span, contribution));
+    }
+    path.sort_by_key(|(span, _contribution)| (span.timestamp, span.id));
+    path.into_iter()
+        .map(|(span, contribution)| CriticalPathSpan {
+            span_id: span.id,
+            contribution,
+        })
+        .collect()
+}
+
+/// Duration of the span minus the time covered by at least one of its children, overlapping
+/// children are only counted once and the parts of them outside of the span are ignored
+pub fn self_duration(span: &Span, children: &[&Span]) -> u64 {
+    let mut intervals: Vec<(u64, u64)> = children
+        .iter()
+        .map(|child| {
+            (
+                child.timestamp.max(span.timestamp),
+                span_end(child).min(span_end(span)),
+            )
+        })
+        .filter(|(start, end)| start < end)
+        .collect();
+    intervals.sort_unstable();
+    let mut covered = 0;
+    let mut covered_until = span.timestamp;
+    for (start, end) in intervals {
+        let start = start.max(covered_until);
+        if start < end {
+            covered += end - start;
+            covered_until = end;
+        }
+    }
+    span.duration.saturating_sub(covered)
+}
+
+/// Spans grouped by name, the ones with the most self time first
+pub fn self_time_by_span_name(spans: &[Span]) -> Vec<SpanSelfTime> {
+    let trace_duration = spans
+        .iter()
+        .find(|s| s.parent_id.is_none())
+        .map(|root| root.duration)
+        .unwrap_or(0);
+    let children_by_parent_id = children_by_parent_id(spans);
+    let mut by_name: HashMap<&str, SpanSelfTime> = HashMap::new();
+    for span in spans {
+        let children = children_by_parent_id
+            .get(&span.id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let entry = by_name.entry(&span.name).or_insert_with(|| SpanSelfTime {
+            name: span.name.clone(),
+            count: 0,
+            total_duration: 0,
+            self_duration: 0,
+            trace_fraction: 0.,
+            self_time_fraction: 0.,
+        });
+        entry.count += 1;
+        entry.total_duration += span.duration;
+        entry.self_duration += self_duration(span, children);
+    }
+    let total_self_duration: u64 = by_name.values().map(|s| s.self_duration).sum();
+    let mut self_times: Vec<SpanSelfTime> = by_name
+        .into_values()
+        .map(|mut self_time| {
+            self_time.trace_fraction = if trace_duration == 0 {
+                0.
+            } else {
+                self_time.self_duration as f64 / trace_duration as f64
+            };
+            self_time.self_time_fraction = if total_self_duration == 0 {
+                0.
+            } else {
+                self_time.self_duration as f64 / total_self_duration as f64
+            };
+            self_time
+        })
+        .collect();
+    self_times.sort_by(|a, b| {
+        b.self_duration
+            .cmp(&a.self_duration)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    self_times
+}
+
+#[instrument(skip_all, fields(trace_id=trace_id.trace_id))]
+pub async fn get_self_time(
+    axum::extract::Query(trace_id): axum::extract::Query<TraceId>,
+    axum::extract::State(con): axum::extract::State<PgPool>,
+) -> Result<Json<Vec<SpanSelfTime>>, ApiError> {
+    let spans = get_trace_spans(&con, trace_id.trace_id).await?;
+    Ok(Json(self_time_by_span_name(&spans)))
+}
+
+fn compared_span(span: &Span) -> ComparedSpan {
+    ComparedSpan {
+        span_id: span.id,
+        duration: span.duration,
+        event_count: span.events.len() as u64,
+    }
+}
+
+fn attribute_differences(left: &Span, right: &Span) -> Vec<AttributeDifference> {
+    let user_attributes = |span: &Span| -> BTreeMap<String, String> {
+        span.key_values
+            .iter()
+            .filter(|kv| kv.user_generated)
+            .map(|kv| (kv.key.clone(), kv.value.clone()))
+            .collect()
+    };
+    let mut left = user_attributes(left);
+    let mut right = user_attributes(right);
+    let mut keys: Vec<String> = left.keys().chain(right.keys()).cloned().collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| {
+            let left = left.remove(&key);
+            let right = right.remove(&key);
+            (left != right).then_some(AttributeDifference { key, left, right })
+        })
+        .collect()
+}
+
+/// Same span in the left and right traces, one of them can be missing
+type SpanPair<'a> = (Option<&'a Span>, Option<&'a Span>);
+
+/// Pairs the children with the same name in the order they started, the ones without a match
+/// on the left come last
+fn pair_children<'a>(left: &[&'a Span], right: &[&'a Span]) -> Vec<SpanPair<'a>> {
+    let mut right_by_name: HashMap<&str, VecDeque<&Span>> = HashMap::new();
+    for span in right {
+        right_by_name.entry(&span.name).or_default().push_back(span);
+    }
+    let mut pairs: Vec<SpanPair> = left
+        .iter()
+        .map(|span| {
+            let matching = right_by_name
+                .get_mut(span.name.as_str())
+                .and_then(VecDeque::pop_front);
+            (Some(*span), matching)
+        })
+        .collect();
+    let mut right_only: Vec<&Span> = right_by_name.into_values().flatten().collect();
+    right_only.sort_by_key(|span| (span.timestamp, span.id));
+    pairs.extend(right_only.into_iter().map(|span| (None, Some(span))));
+    pairs
+}
+
+fn children_of<'a>(
+    children_by_parent_id: &HashMap<u64, Vec<&'a Span>>,
+    span: Option<&Span>,
+) -> Vec<&'a Span> {
+    span.and_then(|span| children_by_parent_id.get(&span.id))
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn compare_traces(left: &[Span], right: &[Span]) -> TraceComparison {
+    let left_root = left.iter().find(|s| s.parent_id.is_none());
+    let right_root = right.iter().find(|s| s.parent_id.is_none());
+    let mut left_children = children_by_parent_id(left);
+    let mut right_children = children_by_parent_id(right);
+    for children in left_children
+        .values_mut()
+        .chain(right_children.values_mut())
+    {
+        children.sort_by_key(|span| (span.timestamp, span.id));
+    }
+    let mut spans = vec![];
+    let mut to_visit = if left_root.is_some() || right_root.is_some() {
+        vec![(left_root, right_root, 0)]
+    } else {
+        vec![]
+    };
+    while let Some((left_span, right_span, depth)) = to_visit.pop() {
+        let name = left_span
+            .or(right_span)
+            .expect("one side to exist")
+            .name
+            .clone();
+        let (duration_delta, attribute_differences) = match (left_span, right_span) {
+            (Some(l), Some(r)) => (
+                Some(r.duration as i64 - l.duration as i64),
+                attribute_differences(l, r),
+            ),
+            _ => (None, vec![]),
+        };
+        spans.push(SpanComparison {
+            name,
+            depth,
+            left: left_span.map(compared_span),
+            right: right_span.map(compared_span),
+            duration_delta,
+            attribute_differences,
+        });
+        let pairs = pair_children(
+            &children_of(&left_children, left_span),
+            &children_of(&right_children, right_span),
+        );
+        to_visit.extend(
+            pairs
+                .into_iter()
+                .rev()
+                .map(|(left, right)| (left, right, depth + 1)),
+        );
+    }
+    TraceComparison {
+        left_duration: left_root.map(|s| s.duration).unwrap_or(0),
+        right_duration: right_root.map(|s| s.duration).unwrap_or(0),
+        spans,
+    }
+}
+
+#[instrument(skip_all, fields(left_trace_id=request.left_trace_id, right_trace_id=request.right_trace_id))]
+pub async fn get_comparison(
+    axum::extract::Query(request): axum::extract::Query<CompareTracesRequest>,
+    axum::extract::State(con): axum::extract::State<PgPool>,
+) -> Result<Json<TraceComparison>, ApiError> {
+    let (left, right) = tokio::try_join!(
+        get_trace_spans(&con, request.left_trace_id),
+        get_trace_spans(&con, request.right_trace_id)
+    )?;
+    Ok(Json(compare_traces(&left, &right)))
+}
+
+#[instrument(skip_all, fields(trace_id=trace_id.trace_id))]
+pub async fn get_critical_path(
+    axum::extract::Query(trace_id): axum::extract::Query<TraceId>,
+    axum::extract::State(con): axum::extract::State<PgPool>,
+) -> Result<Json<Vec<CriticalPathSpan>>, ApiError> {
+    let spans = get_trace_spans(&con, trace_id.trace_id).await?;
+    Ok(Json(critical_path(&spans)))
+}
+
+#[cfg(test)]
+use crate::api::test_span;
+
+#[cfg(test)]
+#[test]
+fn critical_path_follows_the_last_finishing_child() {
+    // root 0..100, two concurrent children 10..50 and 20..80, the later one has a child
+    // 30..70, then a sequential child 85..95
+    let spans = vec![
+        test_span(1, None, "span_1", 0, 100),
+        test_span(2, Some(1), "span_2", 10, 40),
+        test_span(3, Some(1), "span_3", 20, 60),
+        test_span(4, Some(3), "span_4", 30, 40),
+        test_span(5, Some(1), "span_5", 85, 10),
+    ];
+    let path: Vec<(u64, u64)> = critical_path(&spans)
+        .into_iter()
+        .map(|s| (s.span_id, s.contribution))
+        .collect();
+    // root: 0..10 + 80..85 + 95..100, 2: 10..20 (the root was waiting on it until 3 started),
+    // 3: 20..30 + 70..80, 4: 30..70, 5: 85..95
+    assert_eq!(path, vec![(1, 20), (2, 10), (3, 20), (4, 40), (5, 10)]);
+    assert_eq!(path.iter().map(|(_id, c)| c).sum::<u64>(), 100);
+}
+
+#[cfg(test)]
+#[test]
+fn comparison_matches_spans_by_name_path() {
+    let mut left = vec![
+        test_span(1, None, "span_1", 0, 100),
+        test_span(2, Some(1), "fetch_row", 0, 10),
+        test_span(3, Some(1), "fetch_row", 10, 10),
+        test_span(4, Some(1), "span_4", 20, 50),
+    ];
+    left[3].key_values = vec![api_structs::KeyValue {
+        key: "rows".to_string(),
+        user_generated: true,
+        value: "10".to_string(),
+    }];
+    let mut right = vec![
+        test_span(7, None, "span_1", 0, 300),
+        test_span(8, Some(7), "fetch_row", 0, 30),
+        test_span(9, Some(7), "span_4", 30, 200),
+        test_span(10, Some(7), "span_10", 250, 5),
+    ];
+    right[2].key_values = left[3].key_values.clone();
+    right[2].key_values[0].value = "1000".to_string();
+    let comparison = compare_traces(&left, &right);
+    let summary: Vec<_> = comparison
+        .spans
+        .iter()
+        .map(|s| {
+            (
+                s.name.as_str(),
+                s.depth,
+                s.left.as_ref().map(|l| l.span_id),
+                s.right.as_ref().map(|r| r.span_id),
+                s.duration_delta,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("span_1", 0, Some(1), Some(7), Some(200)),
+            ("fetch_row", 1, Some(2), Some(8), Some(20)),
+            ("fetch_row", 1, Some(3), None, None),
+            ("span_4", 1, Some(4), Some(9), Some(150)),
+            ("span_10", 1, None, Some(10), None),
+        ]
+    );
+    assert_eq!(
+        comparison.spans[3].attribute_differences,
+        vec![AttributeDifference {
+            key: "rows".to_string(),
+            left: Some("10".to_string()),
+            right: Some("1000".to_string()),
+        }]
+    );
+    assert!(comparison.spans[1].attribute_differences.is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn self_time_excludes_overlapping_children_once() {
+    // root 0..100 with fetch_row 10..50 and 30..60 overlapping, and one 90..120 running
+    // past the root
+    let spans = vec![
+        test_span(1, None, "span_1", 0, 100),
+        test_span(2, Some(1), "fetch_row", 10, 40),
+        test_span(3, Some(1), "fetch_row", 30, 30),
+        test_span(4, Some(1), "fetch_row", 90, 30),
+    ];
+    let self_times = self_time_by_span_name(&spans);
+    assert_eq!(self_times.len(), 2);
+    assert_eq!(self_times[0].name, "fetch_row");
+    assert_eq!(self_times[0].count, 3);
+    assert_eq!(self_times[0].total_duration, 100);
+    assert_eq!(self_times[0].self_duration, 100);
+    // 0..10 + 60..90
+    assert_eq!(self_times[1].self_duration, 40);
+    assert_eq!(self_times[0].trace_fraction, 1.);
+    assert_eq!(self_times[1].trace_fraction, 0.4);
+    assert_eq!(self_times[0].self_time_fraction, 100. / 140.);
+    assert_eq!(self_times[1].self_time_fraction, 40. / 140.);
+}
+
+#[cfg(test)]
+#[test]
+fn self_time_of_concurrent_siblings_exceeds_the_trace() {
+    // three workers running for the whole root 0..100 have 3x the trace duration of self time
+    let spans = vec![
+        test_span(1, None, "span_1", 0, 100),
+        test_span(2, Some(1), "worker", 0, 100),
+        test_span(3, Some(1), "worker", 0, 100),
+        test_span(4, Some(1), "worker", 0, 100),
+    ];
+    let self_times = self_time_by_span_name(&spans);
+    assert_eq!(self_times[0].name, "worker");
+    assert_eq!(self_times[0].self_duration, 300);
+    assert_eq!(self_times[0].trace_fraction, 3.);
+    assert_eq!(self_times[0].self_time_fraction, 1.);
+    assert_eq!(self_times[1].self_time_fraction, 0.);
+}
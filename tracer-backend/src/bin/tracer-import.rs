@@ -0,0 +1,99 @@
+use api_structs::ImportResult;
+use clap::Parser;
+use std::path::{Path, PathBuf};
+
+/// Uploads OTLP trace files (JSON or protobuf encoded ExportTraceServiceRequest) to a running
+/// tracer backend. Traces go through the same validation as the collector ones.
+#[derive(Debug, clap::Parser)]
+struct Args {
+    /// Base url of the tracer API
+    #[clap(long, env = "TRACER_API_URL", default_value = "http://localhost:4200")]
+    url: String,
+    /// .json files are sent as OTLP JSON, .pb, .bin and .protobuf ones as OTLP protobuf
+    #[clap(required = true)]
+    files: Vec<PathBuf>,
+}
+
+fn content_type(file: &Path) -> Result<&'static str, String> {
+    match file.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => Ok("application/json"),
+        Some("pb" | "bin" | "protobuf") => Ok("application/x-protobuf"),
+        _ => Err(format!(
+            "Unknown extension for {}, expected .json, .pb, .bin or .protobuf",
+            file.display()
+        )),
+    }
+}
+
+async fn import_file(
+    client: &reqwest::Client,
+    url: &str,
+    file: &Path,
+) -> Result<ImportResult, String> {
+    let content_type = content_type(file)?;
+    let body =
+        std::fs::read(file).map_err(|e| format!("Failed to read {}: {e}", file.display()))?;
+    let response = client
+        .post(format!("{}/api/import", url.trim_end_matches('/')))
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to upload {}: {e}", file.display()))?;
+    let status = response.status();
+    let body = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read the response for {}: {e}", file.display()))?;
+    if !status.is_success() {
+        return Err(format!(
+            "Import of {} failed with {status}: {}",
+            file.display(),
+            String::from_utf8_lossy(&body)
+        ));
+    }
+    serde_json::from_slice(&body)
+        .map_err(|e| format!("Invalid response for {}: {e}", file.display()))
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let client = reqwest::Client::new();
+    let mut failed = false;
+    for file in &args.files {
+        match import_file(&client, &args.url, file).await {
+            Ok(result) => {
+                println!(
+                    "{}: imported {} traces {:?}",
+                    file.display(),
+                    result.imported_trace_ids.len(),
+                    result.imported_trace_ids
+                );
+                if result.failed_to_store > 0 {
+                    failed = true;
+                    println!("  {} traces failed to be stored", result.failed_to_store);
+                }
+                for rejected in &result.rejected {
+                    failed = true;
+                    println!(
+                        "  rejected {} {}: {}",
+                        rejected
+                            .service_name
+                            .as_deref()
+                            .unwrap_or("<no service name>"),
+                        rejected.otel_trace_id.as_deref().unwrap_or(""),
+                        rejected.reason
+                    );
+                }
+            }
+            Err(e) => {
+                failed = true;
+                eprintln!("{e}");
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::notification_worthy_events::{ChannelConfig, NotificationChannelsConfig};
 use crate::otel_trace_processing::trace_fragment;
 use clap::Parser;
 use proto_generated::opentelemetry::proto::collector::trace::v1::{
@@ -28,6 +29,8 @@ pub const TIME_WAIT_PANIC_TASKS_ON_STARTUP_SECONDS: u64 = 5;
 pub const MAX_TIME_WAIT_NEW_TRACE_DATA_SECONDS: u64 = 5;
 pub const MAX_COMBINED_SPAN_AND_EVENTS_PER_TRACE: usize = 2_000_000;
 pub const EVENT_CHARS_LIMIT: usize = 32_000;
+/// Live tail subscribers further behind than this many stored batches skip ahead
+pub const LIVE_TAIL_BUFFERED_BATCHES: usize = 100;
 
 // ~10 span+logs per trace, 2 traces per second = 20 span+logs per second
 pub const SPAN_PLUS_EVENTS_PER_SERVICE_PER_SECOND_NOTIFICATION_THRESHOLD: usize = 20;
@@ -42,10 +45,19 @@ pub struct Config {
     pub api_listen_port: u16,
     #[clap(long, env)]
     pub environment: String,
+    /// Shortcut for a Slack channel in addition to the `notification_channels`
     #[clap(long, env)]
     pub slack_notification_url: Option<String>,
+    /// JSON list of channels, like `[{"type": "discord", "url": "https://..."}]`, the types
+    /// being slack, webhook, teams, discord, mattermost and email
+    #[clap(long, env)]
+    pub notification_channels: Option<NotificationChannelsConfig>,
+    /// Time between notifications of errors and invalid traces, in every channel
     #[clap(long, env, default_value_t = 3600)]
     pub slack_notification_interval_seconds: u32,
+    /// Days imported traces are kept after their import, collected ones being kept for a day
+    #[clap(long, env, default_value_t = 7)]
+    pub imported_trace_retention_days: u16,
 }
 #[derive(clap::Parser)]
 pub struct DbConfig {
@@ -75,31 +87,51 @@ async fn connect_to_db(config: &Config) -> Result<PgPool, Box<dyn std::error::Er
 #[instrument(skip_all)]
 async fn start_tasks(config: Config) -> Result<(), Box<dyn std::error::Error>> {
     info!("Using config: {:#?}", config);
+    let mut channel_configs = config
+        .notification_channels
+        .clone()
+        .map(|channels| channels.0)
+        .unwrap_or_default();
+    if let Some(url) = config.slack_notification_url.clone() {
+        channel_configs.push(ChannelConfig::Slack { url });
+    }
+    let channels = channel_configs
+        .into_iter()
+        .map(ChannelConfig::into_channel)
+        .collect::<Result<Vec<_>, String>>()?;
     let con = connect_to_db(&config).await?;
-    let api_handle = api::start(con.clone(), config.api_listen_port);
+    let (live_tail, _) = tokio::sync::broadcast::channel(LIVE_TAIL_BUFFERED_BATCHES);
+    let api_handle = api::start(con.clone(), config.api_listen_port, live_tail.clone());
     let delete_handle = otel_trace_processing::start_background_delete_traces_task(
         con.clone(),
         Duration::from_secs(TIME_WAIT_BETWEEN_DELETE_TRACES_RUN_SECONDS),
+        config.imported_trace_retention_days,
     );
-    let (notification_pusher, notification_task_handle) =
-        if let Some(slack_notification_url) = config.slack_notification_url {
-            info!("Going to try to notify errors via slack");
-            let (pusher, notifier_task_handle) =
-                notification_worthy_events::Notifier::initialize_and_start_notification_task(
-                    slack_notification_url,
-                    Duration::from_secs(u64::from(config.slack_notification_interval_seconds)),
-                    SPAN_PLUS_EVENTS_PER_SERVICE_PER_SECOND_NOTIFICATION_THRESHOLD,
-                );
-            (Some(pusher), Some(notifier_task_handle))
-        } else {
-            info!("Missing Slack URL, not going to try to notify errors via Slack");
-            (None, None)
-        };
+    let (notification_pusher, notification_task_handle) = if !channels.is_empty() {
+        info!(
+            "Going to try to notify errors via {} channels",
+            channels.len()
+        );
+        let (pusher, notifier_task_handle) =
+            notification_worthy_events::Notifier::initialize_and_start_notification_task(
+                channels,
+                Duration::from_secs(u64::from(config.slack_notification_interval_seconds)),
+                SPAN_PLUS_EVENTS_PER_SERVICE_PER_SECOND_NOTIFICATION_THRESHOLD,
+            );
+        (Some(pusher), Some(notifier_task_handle))
+    } else {
+        info!("No notification channels, not going to try to notify errors");
+        (None, None)
+    };
+    let subscriptions_handle = notification_pusher
+        .clone()
+        .map(|pusher| api::start_subscriptions_task(con.clone(), live_tail.subscribe(), pusher));
     let (incoming_traces_pusher, store_handle) =
         otel_trace_processing::TraceStorage::initialize_and_start_storage_task(
             con,
             Duration::from_secs(TIME_WAIT_BETWEEN_STORE_TRACES_RUN_SECONDS),
             notification_pusher.clone(),
+            live_tail,
         );
     let trace_collector = TraceCollector {
         trace_fragment_pusher: incoming_traces_pusher,
@@ -119,9 +151,8 @@ async fn start_tasks(config: Config) -> Result<(), Box<dyn std::error::Error>> {
     .instrument(info_span!("Waiting to see if background tasks panic"))
     .await;
     let mut handles = vec![api_handle, delete_handle, store_handle, tonic_handle];
-    if let Some(handle) = notification_task_handle {
-        handles.push(handle);
-    }
+    handles.extend(notification_task_handle);
+    handles.extend(subscriptions_handle);
     let any_early_finished = handles.into_iter().any(|h| h.is_finished());
     if any_early_finished {
         let err: Box<dyn std::error::Error> =
@@ -1,16 +1,21 @@
 use crate::otel_trace_processing::{InsertedTrace, ServiceName};
-use serde::Serialize;
+pub use channel::{ChannelConfig, NotificationChannel, NotificationChannelsConfig};
+use channel::{MessagePart, Notification};
 use std::collections::HashMap;
 use std::ops::DerefMut;
-use std::str::FromStr;
 use std::sync::{Arc, OnceLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tracing::{error, info, instrument};
 
+mod channel;
+mod email;
+
 const MAX_ENTRIES_PER_SERVICE: usize = 1_00;
 const MAX_SAMPLES_IN_MESSAGE: usize = 10;
+/// Search subscriptions are notified more often than the rest, each limited by its own interval
+const SUBSCRIPTION_NOTIFICATION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Debug, Clone)]
 pub struct TraceInvalidationCause(String);
@@ -19,10 +24,69 @@ impl TraceInvalidationCause {
     pub fn from_cause(cause: &str) -> Self {
         Self(cause.to_string())
     }
+    pub fn cause(&self) -> &str {
+        &self.0
+    }
 }
 
 pub type Shared<T> = Arc<RwLock<T>>;
 pub type TopLevelSpanName = String;
+pub type SubscriptionId = i64;
+
+/// Traces matching a search subscription which were not notified yet
+#[derive(Debug, Clone)]
+pub struct SubscriptionMatches {
+    pub search_name: String,
+    pub min_interval: Duration,
+    pub count: usize,
+    /// At most [MAX_SAMPLES_IN_MESSAGE], oldest first
+    pub samples: Vec<InsertedTrace>,
+}
+
+impl SubscriptionMatches {
+    pub fn new(search_name: String, min_interval: Duration, traces: Vec<InsertedTrace>) -> Self {
+        Self {
+            search_name,
+            min_interval,
+            count: traces.len(),
+            samples: traces.into_iter().take(MAX_SAMPLES_IN_MESSAGE).collect(),
+        }
+    }
+
+    /// Keeps the newer name and interval, in case the subscription changed
+    fn merge(&mut self, newer: SubscriptionMatches) {
+        self.search_name = newer.search_name;
+        self.min_interval = newer.min_interval;
+        self.count += newer.count;
+        let missing_samples = MAX_SAMPLES_IN_MESSAGE.saturating_sub(self.samples.len());
+        self.samples
+            .extend(newer.samples.into_iter().take(missing_samples));
+    }
+}
+
+/// Removes the matches of the subscriptions whose interval since their last notification passed
+fn take_matches_due(
+    pending: &mut HashMap<SubscriptionId, SubscriptionMatches>,
+    last_notified: &mut HashMap<SubscriptionId, Instant>,
+    now: Instant,
+) -> Vec<SubscriptionMatches> {
+    let due_ids: Vec<SubscriptionId> = pending
+        .iter()
+        .filter(|(id, matches)| {
+            last_notified.get(id).map_or(true, |last| {
+                now.duration_since(*last) >= matches.min_interval
+            })
+        })
+        .map(|(id, _matches)| *id)
+        .collect();
+    due_ids
+        .into_iter()
+        .filter_map(|id| {
+            last_notified.insert(id, now);
+            pending.remove(&id)
+        })
+        .collect()
+}
 
 #[derive(Debug, Clone)]
 pub struct Stats {
@@ -37,9 +101,24 @@ pub struct NotificationWorthyEventsPusher {
     traces_with_errors: Shared<HashMap<ServiceName, Vec<InsertedTrace>>>,
     malformed_traces: Shared<HashMap<ServiceName, Vec<TraceInvalidationCause>>>,
     trace_stats: Shared<HashMap<ServiceName, HashMap<TopLevelSpanName, Stats>>>,
+    subscription_matches: Shared<HashMap<SubscriptionId, SubscriptionMatches>>,
 }
 
 impl NotificationWorthyEventsPusher {
+    pub async fn push_subscription_matches(
+        &self,
+        subscription_id: SubscriptionId,
+        matches: SubscriptionMatches,
+    ) {
+        let mut w_lock = self.subscription_matches.write().await;
+        match w_lock.get_mut(&subscription_id) {
+            Some(pending) => pending.merge(matches),
+            None => {
+                w_lock.insert(subscription_id, matches);
+            }
+        }
+    }
+
     pub async fn push_trace_with_error(&self, service_name: String, trace: InsertedTrace) {
         let mut w_lock = self.traces_with_errors.write().await;
         let existing_entries = w_lock.entry(service_name).or_default();
@@ -86,10 +165,12 @@ impl NotificationWorthyEventsPusher {
 }
 
 pub struct Notifier {
-    slack_messenger: SlackNotifier,
+    channels: Vec<Box<dyn NotificationChannel>>,
     traces_with_errors: Shared<HashMap<ServiceName, Vec<InsertedTrace>>>,
     invalid_traces: Shared<HashMap<ServiceName, Vec<TraceInvalidationCause>>>,
     trace_stats: Shared<HashMap<ServiceName, HashMap<TopLevelSpanName, Stats>>>,
+    subscription_matches: Shared<HashMap<SubscriptionId, SubscriptionMatches>>,
+    subscriptions_last_notified: HashMap<SubscriptionId, Instant>,
     time_between_runs: Duration,
     span_plus_events_per_service_per_second_notification_threshold: usize,
 }
@@ -97,17 +178,19 @@ pub struct Notifier {
 impl Notifier {
     #[instrument(skip_all)]
     pub fn initialize_and_start_notification_task(
-        webhook_url: String,
+        channels: Vec<Box<dyn NotificationChannel>>,
         time_between_runs: Duration,
         span_plus_events_per_service_per_second_notification_threshold: usize,
     ) -> (NotificationWorthyEventsPusher, JoinHandle<()>) {
         static CELL: OnceLock<bool> = OnceLock::new();
         let notifier = match CELL.set(true) {
             Ok(()) => Self {
-                slack_messenger: SlackNotifier::new(webhook_url),
+                channels,
                 traces_with_errors: Arc::new(RwLock::new(HashMap::new())),
                 invalid_traces: Arc::new(RwLock::new(HashMap::new())),
                 trace_stats: Arc::new(RwLock::new(HashMap::new())),
+                subscription_matches: Arc::new(RwLock::new(HashMap::new())),
+                subscriptions_last_notified: HashMap::new(),
                 time_between_runs,
                 span_plus_events_per_service_per_second_notification_threshold,
             },
@@ -117,9 +200,16 @@ impl Notifier {
         info!("Starting notifier task");
         let task_handle = tokio::task::spawn(async move {
             let mut err_notifier = notifier;
+            let mut last_run = Instant::now();
+            err_notifier.consume_errors_sending_notifications().await;
             loop {
-                err_notifier.consume_errors_sending_notifications().await;
-                tokio::time::sleep(time_between_runs).await;
+                tokio::time::sleep(SUBSCRIPTION_NOTIFICATION_CHECK_INTERVAL.min(time_between_runs))
+                    .await;
+                err_notifier.send_subscription_notifications().await;
+                if last_run.elapsed() >= time_between_runs {
+                    last_run = Instant::now();
+                    err_notifier.consume_errors_sending_notifications().await;
+                }
             }
         });
         (pusher, task_handle)
@@ -129,12 +219,34 @@ impl Notifier {
             traces_with_errors: Arc::clone(&self.traces_with_errors),
             malformed_traces: Arc::clone(&self.invalid_traces),
             trace_stats: Arc::clone(&self.trace_stats),
+            subscription_matches: Arc::clone(&self.subscription_matches),
+        }
+    }
+    #[instrument(skip_all)]
+    async fn send_subscription_notifications(&mut self) {
+        let due = take_matches_due(
+            self.subscription_matches.write().await.deref_mut(),
+            &mut self.subscriptions_last_notified,
+            Instant::now(),
+        );
+        if due.is_empty() {
+            return;
+        }
+        let mut notification = Notification::default();
+        for matches in due {
+            let mut line = vec![MessagePart::Text(format!(
+                "Search {} matched new traces ({}). Samples:",
+                matches.search_name, matches.count
+            ))];
+            line.extend(trace_links(&matches.samples));
+            notification.lines.push(line);
         }
+        self.send(&notification).await;
     }
     #[instrument(skip_all)]
     async fn consume_errors_sending_notifications(&mut self) {
         let trace_stats = std::mem::take(self.trace_stats.write().await.deref_mut());
-        let mut message_lines_to_send = vec![];
+        let mut notification = Notification::default();
         for (service, service_stats) in trace_stats {
             let mut service_total_span_plus_events = 0;
             for stats in service_stats.values() {
@@ -168,8 +280,7 @@ impl Notifier {
                     })
                     .collect();
                 service_message_lines_to_send.extend_from_slice(&top_spans_message);
-                let full_msg = service_message_lines_to_send.join("\n    ");
-                message_lines_to_send.push(full_msg);
+                notification.push_text(service_message_lines_to_send.join("\n    "));
             }
         }
         let invalid_traces = std::mem::take(self.invalid_traces.write().await.deref_mut());
@@ -181,93 +292,88 @@ impl Notifier {
                 .map(|e| e.0)
                 .collect::<Vec<String>>();
             let samples = samples.join("\n    ");
-            message_lines_to_send.push(format!(
+            notification.push_text(format!(
                 "{service_name} sent invalid traces ({count}). Sample causes:\n    {samples}"
             ));
         }
         let traces_with_error = std::mem::take(self.traces_with_errors.write().await.deref_mut());
         for (service_name, trace_with_error) in traces_with_error {
             let count = trace_with_error.len();
-            let err: String = trace_with_error
-                .iter()
-                .take(MAX_SAMPLES_IN_MESSAGE)
-                .map(|e| {
-                    let id = &e.id;
-                    let top_level_span_name = &e.top_level_span_name;
-                    let frontend_url = api_structs::FRONTEND_PUBLIC_URL_PATH_NO_TRAILING_SLASH;
-                    format!("<{frontend_url}/trace?trace_id={id}|{top_level_span_name}>")
-                })
-                .collect::<Vec<String>>()
-                .join(" ");
-            message_lines_to_send.push(format!(
-                "{service_name} had errors ({count}). Samples: {err}"
+            let mut line = vec![MessagePart::Text(format!(
+                "{service_name} had errors ({count}). Samples:"
+            ))];
+            line.extend(trace_links(
+                &trace_with_error[..trace_with_error.len().min(MAX_SAMPLES_IN_MESSAGE)],
             ));
+            notification.lines.push(line);
         }
-        if !message_lines_to_send.is_empty() {
-            self.slack_messenger
-                .send_slack_msg(&message_lines_to_send.join("\n"))
-                .await;
+        if !notification.is_empty() {
+            self.send(&notification).await;
         } else {
             info!("Notifier has no events to send");
         }
     }
-}
-
-#[derive(Clone, Debug, Serialize)]
-struct SlackMessage {
-    text: String,
-}
-
-struct SlackNotifier {
-    client: reqwest::Client,
-    webhook_url: reqwest::Url,
-}
-
-impl SlackNotifier {
-    pub fn new(webhook_url: String) -> Self {
-        Self {
-            client: reqwest::ClientBuilder::new()
-                .timeout(Duration::from_secs(30))
-                .build()
-                .expect("Couldn't create Reqwest client"),
-            webhook_url: reqwest::Url::from_str(&webhook_url)
-                .unwrap_or_else(|e| panic!("invalid slack url: {webhook_url}\n{e:?}")),
+    /// Sends to all channels at once, so a slow one doesn't delay the others
+    async fn send(&self, notification: &Notification) {
+        let results = futures::future::join_all(
+            self.channels
+                .iter()
+                .map(|channel| channel.send(notification)),
+        )
+        .await;
+        for (channel, result) in self.channels.iter().zip(results) {
+            if let Err(e) = result {
+                error!("Error notifying through {} channel: {e}", channel.kind());
+            }
         }
     }
 }
-impl SlackNotifier {
-    #[instrument(skip_all)]
-    async fn send_slack_msg(&self, msg: &str) {
-        info!("Sending a Slack notification: {msg}");
-        let response = self
-            .client
-            .request(reqwest::Method::POST, self.webhook_url.clone())
-            .body(
-                serde_json::to_string(&SlackMessage {
-                    text: msg.to_string(),
-                })
-                .expect("Slack msg was not serializable"),
-            )
-            .send()
-            .await;
 
-        match response {
-            Ok(request_response) => {
-                // got response
-                let status = request_response.status();
-                if status.is_success() {
-                    info!("Sent a Slack notification");
-                } else {
-                    error!(
-                        "Error sending Slack notification. HTTP Status: {:?}",
-                        status
-                    );
-                }
-            }
-            // timed out
-            Err(err) => {
-                error!("Error sending Slack notification: {}", err.to_string());
-            }
-        }
-    }
+/// Links to the traces, each preceded by a space
+fn trace_links(traces: &[InsertedTrace]) -> Vec<MessagePart> {
+    let frontend_url = api_structs::FRONTEND_PUBLIC_URL_PATH_NO_TRAILING_SLASH;
+    traces
+        .iter()
+        .flat_map(|trace| {
+            [
+                MessagePart::Text(" ".to_string()),
+                MessagePart::Link {
+                    text: trace.top_level_span_name.clone(),
+                    url: format!("{frontend_url}/trace?trace_id={}", trace.id),
+                },
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn subscription_matches_wait_for_their_interval() {
+    let start = Instant::now();
+    let mut pending = HashMap::new();
+    let mut last_notified = HashMap::new();
+    let minute = Duration::from_secs(60);
+    pending.insert(1, SubscriptionMatches::new("a".to_string(), minute, vec![]));
+    pending.insert(
+        2,
+        SubscriptionMatches::new("b".to_string(), minute * 10, vec![]),
+    );
+    // never notified before, both are due
+    assert_eq!(
+        take_matches_due(&mut pending, &mut last_notified, start).len(),
+        2
+    );
+    let mut later = SubscriptionMatches::new("a".to_string(), minute, vec![]);
+    later.count = 3;
+    pending.insert(1, later.clone());
+    pending.get_mut(&1).unwrap().merge(later);
+    pending.insert(
+        2,
+        SubscriptionMatches::new("b".to_string(), minute * 10, vec![]),
+    );
+    assert!(take_matches_due(&mut pending, &mut last_notified, start + minute / 2).is_empty());
+    let due = take_matches_due(&mut pending, &mut last_notified, start + minute);
+    assert_eq!(due.len(), 1);
+    assert_eq!((due[0].search_name.as_str(), due[0].count), ("a", 6));
+    assert!(pending.contains_key(&2));
 }
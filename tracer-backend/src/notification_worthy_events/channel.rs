@@ -0,0 +1,548 @@
+use crate::notification_worthy_events::email::{EmailChannel, EmailConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{info, instrument};
+
+/// Discord refuses longer messages
+const DISCORD_MAX_CONTENT_CHARS: usize = 2000;
+const DEFAULT_WEBHOOK_BODY_TEMPLATE: &str = r#"{"text": {{text}}}"#;
+
+/// Piece of a notification line, each channel writes links in its own syntax
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessagePart {
+    Text(String),
+    Link { text: String, url: String },
+}
+
+/// Lines of a notification, formatted by each [NotificationChannel]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Notification {
+    pub lines: Vec<Vec<MessagePart>>,
+}
+
+impl Notification {
+    pub fn push_text(&mut self, text: String) {
+        self.lines.push(vec![MessagePart::Text(text)]);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Lines joined by `line_separator`, with `link(text, url)` written for links
+    pub fn render(&self, line_separator: &str, link: impl Fn(&str, &str) -> String) -> String {
+        self.lines
+            .iter()
+            .map(|parts| {
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        MessagePart::Text(text) => text.clone(),
+                        MessagePart::Link { text, url } => link(text, url),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join(line_separator)
+    }
+
+    pub fn plain_text(&self) -> String {
+        self.render("\n", |text, url| format!("{text} ({url})"))
+    }
+
+    pub fn markdown(&self, line_separator: &str) -> String {
+        self.render(line_separator, |text, url| format!("[{text}]({url})"))
+    }
+}
+
+/// Where notifications are delivered
+#[async_trait::async_trait]
+pub trait NotificationChannel: Send + Sync {
+    /// Type of channel, used in logs
+    fn kind(&self) -> &'static str;
+    async fn send(&self, notification: &Notification) -> Result<(), String>;
+}
+
+/// One notification channel, configured as JSON with its `type`, like
+/// `{"type": "discord", "url": "https://discord.com/api/webhooks/..."}`
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelConfig {
+    Slack {
+        url: String,
+    },
+    /// Posts `body_template` with `{{text}}` and `{{markdown}}` replaced by the message as JSON
+    /// strings and `{{links}}` by a JSON array of `{"text": .., "url": ..}` objects
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_body_template")]
+        body_template: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    Teams {
+        url: String,
+    },
+    Discord {
+        url: String,
+    },
+    Mattermost {
+        url: String,
+    },
+    Email(EmailConfig),
+}
+
+fn default_webhook_body_template() -> String {
+    DEFAULT_WEBHOOK_BODY_TEMPLATE.to_string()
+}
+
+impl ChannelConfig {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ChannelConfig::Slack { .. } => "slack",
+            ChannelConfig::Webhook { .. } => "webhook",
+            ChannelConfig::Teams { .. } => "teams",
+            ChannelConfig::Discord { .. } => "discord",
+            ChannelConfig::Mattermost { .. } => "mattermost",
+            ChannelConfig::Email(_) => "email",
+        }
+    }
+
+    pub fn into_channel(self) -> Result<Box<dyn NotificationChannel>, String> {
+        let kind = self.kind();
+        let channel: Box<dyn NotificationChannel> = match self {
+            ChannelConfig::Slack { url } => Box::new(SlackChannel {
+                webhook: JsonWebhook::new(kind, &url, HashMap::new())?,
+            }),
+            ChannelConfig::Webhook {
+                url,
+                body_template,
+                headers,
+            } => {
+                let channel = TemplatedWebhookChannel {
+                    webhook: JsonWebhook::new(kind, &url, headers)?,
+                    body_template,
+                };
+                let mut sample = Notification::default();
+                sample.push_text("\"sample\"\n".to_string());
+                let sample_body = channel.body(&sample);
+                serde_json::from_str::<serde_json::Value>(&sample_body).map_err(|e| {
+                    format!("webhook body template doesn't render JSON: {e}\n{sample_body}")
+                })?;
+                Box::new(channel)
+            }
+            ChannelConfig::Teams { url } => Box::new(TeamsChannel {
+                webhook: JsonWebhook::new(kind, &url, HashMap::new())?,
+            }),
+            ChannelConfig::Discord { url } => Box::new(DiscordChannel {
+                webhook: JsonWebhook::new(kind, &url, HashMap::new())?,
+            }),
+            ChannelConfig::Mattermost { url } => Box::new(MattermostChannel {
+                webhook: JsonWebhook::new(kind, &url, HashMap::new())?,
+            }),
+            ChannelConfig::Email(config) => Box::new(EmailChannel::new(config)?),
+        };
+        Ok(channel)
+    }
+}
+
+/// JSON list of [ChannelConfig]
+#[derive(Clone)]
+pub struct NotificationChannelsConfig(pub Vec<ChannelConfig>);
+
+impl FromStr for NotificationChannelsConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+            .map(NotificationChannelsConfig)
+            .map_err(|e| format!("invalid notification channels: {e}"))
+    }
+}
+
+/// Only the channel types, their URLs and passwords are secrets
+impl Debug for NotificationChannelsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(ChannelConfig::kind))
+            .finish()
+    }
+}
+
+/// Posts JSON bodies to a webhook URL
+struct JsonWebhook {
+    kind: &'static str,
+    client: reqwest::Client,
+    url: reqwest::Url,
+    headers: reqwest::header::HeaderMap,
+}
+
+impl JsonWebhook {
+    fn new(
+        kind: &'static str,
+        url: &str,
+        headers: HashMap<String, String>,
+    ) -> Result<Self, String> {
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| {
+                let header_name = reqwest::header::HeaderName::from_str(&name)
+                    .map_err(|e| format!("invalid {kind} header name {name}: {e}"))?;
+                let header_value = reqwest::header::HeaderValue::from_str(&value)
+                    .map_err(|e| format!("invalid {kind} header value for {name}: {e}"))?;
+                Ok((header_name, header_value))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            kind,
+            client: reqwest::ClientBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("Couldn't create Reqwest client"),
+            url: reqwest::Url::from_str(url)
+                .map_err(|e| format!("invalid {kind} url: {url}\n{e:?}"))?,
+            headers,
+        })
+    }
+
+    #[instrument(skip_all, fields(kind = self.kind))]
+    async fn post(&self, body: String) -> Result<(), String> {
+        info!("Sending a {} notification: {body}", self.kind);
+        let response = self
+            .client
+            .post(self.url.clone())
+            .headers(self.headers.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("Error sending {} notification: {e}", self.kind))?;
+        let status = response.status();
+        if status.is_success() {
+            info!("Sent a {} notification", self.kind);
+            Ok(())
+        } else {
+            Err(format!(
+                "Error sending {} notification. HTTP Status: {status:?}",
+                self.kind
+            ))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TextMessage {
+    text: String,
+}
+
+struct SlackChannel {
+    webhook: JsonWebhook,
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for SlackChannel {
+    fn kind(&self) -> &'static str {
+        self.webhook.kind
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let text = notification.render("\n", |text, url| format!("<{url}|{text}>"));
+        let body = serde_json::to_string(&TextMessage { text }).expect("Slack msg to serialize");
+        self.webhook.post(body).await
+    }
+}
+
+/// Microsoft Teams incoming webhook, which needs a blank line to break lines
+struct TeamsChannel {
+    webhook: JsonWebhook,
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for TeamsChannel {
+    fn kind(&self) -> &'static str {
+        self.webhook.kind
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let text = notification.markdown("\n\n");
+        let body = serde_json::to_string(&TextMessage { text }).expect("Teams msg to serialize");
+        self.webhook.post(body).await
+    }
+}
+
+/// Mattermost incoming webhook, Slack compatible but with markdown links
+struct MattermostChannel {
+    webhook: JsonWebhook,
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for MattermostChannel {
+    fn kind(&self) -> &'static str {
+        self.webhook.kind
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let text = notification.markdown("\n");
+        let body =
+            serde_json::to_string(&TextMessage { text }).expect("Mattermost msg to serialize");
+        self.webhook.post(body).await
+    }
+}
+
+#[derive(Serialize)]
+struct DiscordMessage {
+    content: String,
+}
+
+struct DiscordChannel {
+    webhook: JsonWebhook,
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for DiscordChannel {
+    fn kind(&self) -> &'static str {
+        self.webhook.kind
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        let mut content = notification.markdown("\n");
+        if content.chars().count() > DISCORD_MAX_CONTENT_CHARS {
+            content = content
+                .chars()
+                .take(DISCORD_MAX_CONTENT_CHARS - 1)
+                .chain(['…'])
+                .collect();
+        }
+        let body =
+            serde_json::to_string(&DiscordMessage { content }).expect("Discord msg to serialize");
+        self.webhook.post(body).await
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookLink<'a> {
+    text: &'a str,
+    url: &'a str,
+}
+
+struct TemplatedWebhookChannel {
+    webhook: JsonWebhook,
+    body_template: String,
+}
+
+impl TemplatedWebhookChannel {
+    fn body(&self, notification: &Notification) -> String {
+        fn to_json(value: &impl Serialize) -> String {
+            serde_json::to_string(value).expect("webhook placeholder to serialize")
+        }
+        let links: Vec<WebhookLink> = notification
+            .lines
+            .iter()
+            .flatten()
+            .filter_map(|part| match part {
+                MessagePart::Link { text, url } => Some(WebhookLink { text, url }),
+                MessagePart::Text(_) => None,
+            })
+            .collect();
+        let placeholders = [
+            ("{{text}}", to_json(&notification.plain_text())),
+            ("{{markdown}}", to_json(&notification.markdown("\n"))),
+            ("{{links}}", to_json(&links)),
+        ];
+        // a single pass, as the values hold span names that may look like placeholders too
+        let mut body = String::with_capacity(self.body_template.len());
+        let mut rest = self.body_template.as_str();
+        while let Some(start) = rest.find("{{") {
+            body.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match placeholders
+                .iter()
+                .find(|(placeholder, _)| rest.starts_with(placeholder))
+            {
+                Some((placeholder, value)) => {
+                    body.push_str(value);
+                    rest = &rest[placeholder.len()..];
+                }
+                None => {
+                    body.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        body.push_str(rest);
+        body
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for TemplatedWebhookChannel {
+    fn kind(&self) -> &'static str {
+        self.webhook.kind
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        self.webhook.post(self.body(notification)).await
+    }
+}
+
+#[cfg(test)]
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+#[cfg(test)]
+use tokio::net::TcpListener;
+
+/// Stands in for a webhook, answering a single request with `status` and returning the
+/// request head and body
+#[cfg(test)]
+async fn stand_in_webhook(status: u16) -> (String, tokio::task::JoinHandle<(String, String)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = vec![];
+        let mut buf = [0; 4096];
+        loop {
+            let read = stream.read(&mut buf).await.unwrap();
+            assert_ne!(read, 0, "client closed the connection mid request");
+            request.extend_from_slice(&buf[..read]);
+            let request = String::from_utf8_lossy(&request);
+            if let Some((head, body)) = request.split_once("\r\n\r\n") {
+                let content_length = head
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(str::to_string)
+                    })
+                    .map(|length| length.parse::<usize>().unwrap())
+                    .unwrap_or(0);
+                if body.len() >= content_length {
+                    let response = format!("HTTP/1.1 {status} OK\r\ncontent-length: 0\r\n\r\n");
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                    return (head.to_string(), body.to_string());
+                }
+            }
+        }
+    });
+    (url, handle)
+}
+
+#[cfg(test)]
+fn notification() -> Notification {
+    Notification {
+        lines: vec![
+            vec![
+                MessagePart::Text("Checkout had errors (1). Samples: ".to_string()),
+                MessagePart::Link {
+                    text: "POST /pay".to_string(),
+                    url: "http://tracer/trace?trace_id=1".to_string(),
+                },
+            ],
+            vec![MessagePart::Text(
+                "Cart sent invalid traces (2)".to_string(),
+            )],
+        ],
+    }
+}
+
+#[cfg(test)]
+async fn send_to_stand_in(config: serde_json::Value) -> (String, serde_json::Value) {
+    let (url, request) = stand_in_webhook(200).await;
+    let mut config = config;
+    config["url"] = url.into();
+    let channel = serde_json::from_value::<ChannelConfig>(config)
+        .unwrap()
+        .into_channel()
+        .unwrap();
+    channel.send(&notification()).await.unwrap();
+    let (head, body) = request.await.unwrap();
+    (head, serde_json::from_str(&body).unwrap())
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn channels_post_their_payloads() {
+    let link_line = "Checkout had errors (1). Samples: [POST /pay](http://tracer/trace?trace_id=1)";
+    let (head, slack) = send_to_stand_in(serde_json::json!({"type": "slack"})).await;
+    assert!(head.contains("content-type: application/json"));
+    assert_eq!(
+        slack,
+        serde_json::json!({"text": "Checkout had errors (1). Samples: <http://tracer/trace?trace_id=1|POST /pay>\nCart sent invalid traces (2)"})
+    );
+    let (_, teams) = send_to_stand_in(serde_json::json!({"type": "teams"})).await;
+    assert_eq!(
+        teams,
+        serde_json::json!({"text": format!("{link_line}\n\nCart sent invalid traces (2)")})
+    );
+    let (_, discord) = send_to_stand_in(serde_json::json!({"type": "discord"})).await;
+    assert_eq!(
+        discord,
+        serde_json::json!({"content": format!("{link_line}\nCart sent invalid traces (2)")})
+    );
+    let (_, mattermost) = send_to_stand_in(serde_json::json!({"type": "mattermost"})).await;
+    assert_eq!(
+        mattermost,
+        serde_json::json!({"text": format!("{link_line}\nCart sent invalid traces (2)")})
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn webhook_renders_its_template() {
+    let (head, body) = send_to_stand_in(serde_json::json!({
+        "type": "webhook",
+        "body_template": r#"{"source": "tracer", "summary": {{text}}, "links": {{links}}}"#,
+        "headers": {"X-Api-Key": "secret"}
+    }))
+    .await;
+    assert!(head.contains("x-api-key: secret"));
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "source": "tracer",
+            "summary": "Checkout had errors (1). Samples: POST /pay (http://tracer/trace?trace_id=1)\nCart sent invalid traces (2)",
+            "links": [{"text": "POST /pay", "url": "http://tracer/trace?trace_id=1"}]
+        })
+    );
+    let unquoted_text = serde_json::json!({"type": "webhook", "url": "http://localhost/hook", "body_template": r#"{"text": "{{text}}"}"#});
+    assert!(serde_json::from_value::<ChannelConfig>(unquoted_text)
+        .unwrap()
+        .into_channel()
+        .is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn webhook_placeholders_in_span_names_stay_text() {
+    let channel = TemplatedWebhookChannel {
+        webhook: JsonWebhook::new("webhook", "http://localhost/hook", HashMap::new()).unwrap(),
+        body_template: r#"{"text": {{text}}, "markdown": {{markdown}}, "links": {{links}}}"#
+            .to_string(),
+    };
+    let mut notification = Notification::default();
+    notification.push_text("{{links}} had errors (1)".to_string());
+    notification.lines.push(vec![MessagePart::Link {
+        text: "{{markdown}}".to_string(),
+        url: "http://tracer/trace?trace_id=1".to_string(),
+    }]);
+    let body: serde_json::Value = serde_json::from_str(&channel.body(&notification)).unwrap();
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "text": "{{links}} had errors (1)\n{{markdown}} (http://tracer/trace?trace_id=1)",
+            "markdown": "{{links}} had errors (1)\n[{{markdown}}](http://tracer/trace?trace_id=1)",
+            "links": [{"text": "{{markdown}}", "url": "http://tracer/trace?trace_id=1"}]
+        })
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn failed_deliveries_are_errors() {
+    let (url, request) = stand_in_webhook(500).await;
+    let channel = ChannelConfig::Slack { url }.into_channel().unwrap();
+    assert!(channel.send(&notification()).await.is_err());
+    request.await.unwrap();
+}
@@ -0,0 +1,244 @@
+use crate::notification_worthy_events::channel::{Notification, NotificationChannel};
+use base64::Engine;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tracing::{info, instrument};
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Plain SMTP relay, without TLS, so meant for a relay in the same network
+#[derive(Clone, Deserialize)]
+pub struct EmailConfig {
+    smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    smtp_port: u16,
+    /// Sent with `AUTH PLAIN` when given along with the password
+    username: Option<String>,
+    password: Option<String>,
+    from: String,
+    to: Vec<String>,
+    #[serde(default = "default_subject")]
+    subject: String,
+}
+
+fn default_smtp_port() -> u16 {
+    25
+}
+
+fn default_subject() -> String {
+    "Tracer notification".to_string()
+}
+
+pub struct EmailChannel {
+    config: EmailConfig,
+}
+
+impl EmailChannel {
+    pub fn new(config: EmailConfig) -> Result<Self, String> {
+        if config.to.is_empty() {
+            return Err("email channel needs at least one recipient".to_string());
+        }
+        if config
+            .to
+            .iter()
+            .chain([&config.from, &config.subject])
+            .any(|value| value.contains(['\r', '\n']))
+        {
+            return Err("email addresses and subject must be a single line".to_string());
+        }
+        Ok(Self { config })
+    }
+
+    /// Headers and body, with lines starting with a dot escaped as the end of the data is a
+    /// line with a single dot
+    fn message(&self, body: &str) -> String {
+        let config = &self.config;
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            config.from,
+            config.to.join(", "),
+            config.subject,
+            chrono::Utc::now().to_rfc2822()
+        );
+        for line in body.lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+
+    async fn send_email(&self, body: &str) -> Result<(), String> {
+        let config = &self.config;
+        let stream = TcpStream::connect((config.smtp_host.as_str(), config.smtp_port))
+            .await
+            .map_err(|e| format!("Error connecting to {}: {e}", config.smtp_host))?;
+        let (reader, writer) = stream.into_split();
+        let mut smtp = SmtpConnection {
+            reader: BufReader::new(reader),
+            writer,
+        };
+        smtp.expect_reply(&[220]).await?;
+        smtp.command("EHLO tracer", &[250]).await?;
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            let credentials = base64::engine::general_purpose::STANDARD
+                .encode(format!("\0{username}\0{password}"));
+            smtp.command(&format!("AUTH PLAIN {credentials}"), &[235])
+                .await?;
+        }
+        smtp.command(&format!("MAIL FROM:<{}>", config.from), &[250])
+            .await?;
+        for to in &config.to {
+            smtp.command(&format!("RCPT TO:<{to}>"), &[250, 251])
+                .await?;
+        }
+        smtp.command("DATA", &[354]).await?;
+        smtp.command(&format!("{}.", self.message(body)), &[250])
+            .await?;
+        // the email was accepted already, the server closing early is fine
+        let _ = smtp.command("QUIT", &[221]).await;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl NotificationChannel for EmailChannel {
+    fn kind(&self) -> &'static str {
+        "email"
+    }
+
+    #[instrument(skip_all)]
+    async fn send(&self, notification: &Notification) -> Result<(), String> {
+        info!("Sending an email notification");
+        tokio::time::timeout(SMTP_TIMEOUT, self.send_email(&notification.plain_text()))
+            .await
+            .map_err(|_| "Timed out sending email notification".to_string())??;
+        info!("Sent an email notification");
+        Ok(())
+    }
+}
+
+struct SmtpConnection {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl SmtpConnection {
+    async fn command(&mut self, command: &str, expected_codes: &[u16]) -> Result<(), String> {
+        self.writer
+            .write_all(format!("{command}\r\n").as_bytes())
+            .await
+            .map_err(|e| format!("Error writing to SMTP server: {e}"))?;
+        self.expect_reply(expected_codes).await
+    }
+
+    /// Reads the reply, which may span lines like `250-first` up to `250 last`
+    async fn expect_reply(&mut self, expected_codes: &[u16]) -> Result<(), String> {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .reader
+                .read_line(&mut line)
+                .await
+                .map_err(|e| format!("Error reading from SMTP server: {e}"))?;
+            if read == 0 {
+                return Err(format!("SMTP server closed the connection: {reply}"));
+            }
+            reply.push_str(&line);
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+        }
+        let code = reply.get(..3).and_then(|code| code.parse::<u16>().ok());
+        match code {
+            Some(code) if expected_codes.contains(&code) => Ok(()),
+            _ => Err(format!("Unexpected SMTP reply: {}", reply.trim_end())),
+        }
+    }
+}
+
+#[cfg(test)]
+use tokio::net::TcpListener;
+
+/// Stands in for an SMTP server accepting one email, returning the commands and data received
+#[cfg(test)]
+async fn stand_in_smtp_server() -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let handle = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(b"220 stand-in ESMTP\r\n").await.unwrap();
+        let mut received = vec![];
+        loop {
+            let mut line = String::new();
+            let read = reader.read_line(&mut line).await.unwrap();
+            assert_ne!(read, 0, "client closed the connection before QUIT");
+            let reply: &[u8] = match line.trim_end() {
+                "EHLO tracer" => b"250-stand-in\r\n250 AUTH PLAIN\r\n",
+                "DATA" => {
+                    writer.write_all(b"354 go ahead\r\n").await.unwrap();
+                    let mut data = String::new();
+                    while !data.ends_with("\r\n.\r\n") {
+                        let read = reader.read_line(&mut data).await.unwrap();
+                        assert_ne!(read, 0, "client closed the connection mid data");
+                    }
+                    received.push(data);
+                    b"250 queued\r\n"
+                }
+                "QUIT" => {
+                    writer.write_all(b"221 bye\r\n").await.unwrap();
+                    return received;
+                }
+                command if command.starts_with("AUTH PLAIN ") => b"235 authenticated\r\n",
+                _ => b"250 ok\r\n",
+            };
+            received.push(line.trim_end().to_string());
+            writer.write_all(reply).await.unwrap();
+        }
+    });
+    (port, handle)
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn email_is_sent_over_smtp() {
+    let (port, received) = stand_in_smtp_server().await;
+    let config: EmailConfig = serde_json::from_value(serde_json::json!({
+        "smtp_host": "127.0.0.1",
+        "smtp_port": port,
+        "username": "tracer",
+        "password": "pass",
+        "from": "tracer@example.com",
+        "to": ["oncall@example.com", "dev@example.com"],
+    }))
+    .unwrap();
+    let mut notification = Notification::default();
+    notification.push_text("Checkout had errors (1)\n.hidden".to_string());
+    EmailChannel::new(config)
+        .unwrap()
+        .send(&notification)
+        .await
+        .unwrap();
+    let received = received.await.unwrap();
+    assert_eq!(
+        received[..5],
+        [
+            "EHLO tracer",
+            "AUTH PLAIN AHRyYWNlcgBwYXNz",
+            "MAIL FROM:<tracer@example.com>",
+            "RCPT TO:<oncall@example.com>",
+            "RCPT TO:<dev@example.com>",
+        ]
+    );
+    let data = &received[5];
+    assert!(data.starts_with("From: tracer@example.com\r\nTo: oncall@example.com, dev@example.com\r\nSubject: Tracer notification\r\n"));
+    assert!(data.ends_with("\r\n\r\nCheckout had errors (1)\r\n..hidden\r\n.\r\n"));
+}
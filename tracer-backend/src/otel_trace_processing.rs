@@ -18,14 +18,24 @@ use std::time::{Duration, Instant};
 use tokio::task::JoinHandle;
 use tracing::{error, info, info_span, instrument, warn, Instrument};
 
+pub mod error_group;
+
+pub mod otlp_json;
+
+pub mod rollup;
+
 pub mod trace_fragment;
 
 pub mod span_processing;
 
+/// Every batch of traces stored from the collector, for the live tail
+pub type LiveTailSender = tokio::sync::broadcast::Sender<Vec<InsertedTrace>>;
+
 pub struct TraceStorage {
     con: PgPool,
     popper: trace_fragment::Popper,
     notification_pusher: Option<NotificationWorthyEventsPusher>,
+    live_tail: LiveTailSender,
 }
 
 impl TraceStorage {
@@ -34,6 +44,7 @@ impl TraceStorage {
         con: PgPool,
         time_between_runs: Duration,
         notification_pusher: Option<NotificationWorthyEventsPusher>,
+        live_tail: LiveTailSender,
     ) -> (trace_fragment::Pusher, JoinHandle<()>) {
         static CELL: OnceLock<bool> = OnceLock::new();
         let (incoming_traces_pusher, storer) = match CELL.set(true) {
@@ -45,6 +56,7 @@ impl TraceStorage {
                         con,
                         popper,
                         notification_pusher,
+                        live_tail,
                     },
                 )
             }
@@ -59,6 +71,7 @@ impl TraceStorage {
                         &storer.con,
                         traces,
                         storer.notification_pusher.clone(),
+                        &storer.live_tail,
                     )
                     .await;
                 }
@@ -72,10 +85,15 @@ impl TraceStorage {
         con: &PgPool,
         traces: HashMap<ServiceName, HashMap<OtelTraceId, Vec<ProtoSpan>>>,
         notification_pusher: Option<NotificationWorthyEventsPusher>,
+        live_tail: &LiveTailSender,
     ) {
         let trace_processing_outcome =
             Self::validate_traces_and_shape_for_db(traces, notification_pusher.clone()).await;
         let inserted_traces = batch_store_traces(con, trace_processing_outcome).await;
+        if !inserted_traces.is_empty() {
+            // only fails when nobody is tailing
+            let _ = live_tail.send(inserted_traces.clone());
+        }
         if let Some(notification_pusher) = notification_pusher {
             for trace in inserted_traces {
                 notification_pusher
@@ -202,14 +220,15 @@ async fn insert_trace_metadata(
     service: &DbReadyTraceData,
 ) -> Result<i64, Error> {
     let id = sqlx::query_scalar!(
-        "insert into trace (timestamp, service_name, top_level_span_name, duration, warning_count, has_errors)
-    values ($1::ubigint, $2, $3, $4, $5, $6) returning id;",
+        "insert into trace (timestamp, service_name, top_level_span_name, duration, warning_count, has_errors, imported, imported_at)
+    values ($1::ubigint, $2, $3, $4, $5, $6, $7, case when $7 then (EXTRACT(epoch FROM now()) * 1000000000)::bigint end) returning id;",
         service.timestamp as _,
         service.service_name as _,
         service.top_level_span_name as _,
         service.duration as _,
         i64::from(service.warning_count) as _,
-        service.has_errors
+        service.has_errors,
+        service.imported
     )
     .fetch_one(con)
     .await?;
@@ -487,10 +506,18 @@ fn valid_data(spans: &Vec<ProtoSpan>) -> bool {
     true
 }
 
+/// Collected traces are kept for a day, imported ones for `imported_trace_retention_days` after
+/// their import, as they are usually older than a day already
 #[instrument(skip_all)]
-pub async fn delete_old_traces(con: &PgPool) -> Result<(), Error> {
+pub async fn delete_old_traces(
+    con: &PgPool,
+    imported_trace_retention_days: u16,
+) -> Result<(), Error> {
     let res: PgQueryResult =
-        sqlx::query!("delete from trace where timestamp < (EXTRACT(epoch FROM now() - INTERVAL '1 DAY') * 1000000000);")
+        sqlx::query!("delete from trace where (not imported and timestamp < (EXTRACT(epoch FROM now() - INTERVAL '1 DAY') * 1000000000))
+    or (imported and imported_at < (EXTRACT(epoch FROM now() - make_interval(days => $1)) * 1000000000));",
+            i32::from(imported_trace_retention_days)
+        )
             .execute(con)
             .instrument(info_span!("deleting_old_traces"))
             .await?;
@@ -499,21 +526,28 @@ pub async fn delete_old_traces(con: &PgPool) -> Result<(), Error> {
 }
 
 #[instrument(skip_all)]
-pub async fn delete_old_traces_logging_errors(con: &PgPool) {
-    if let Err(e) = delete_old_traces(con).await {
+pub async fn delete_old_traces_logging_errors(con: &PgPool, imported_trace_retention_days: u16) {
+    if let Err(e) = delete_old_traces(con, imported_trace_retention_days).await {
         error!("Error deleting old traces: {:#?}", e);
     }
+    if let Err(e) = rollup::delete_old_rollups(con).await {
+        error!("Error deleting old rollups: {:#?}", e);
+    }
+    if let Err(e) = error_group::delete_old_error_groups(con).await {
+        error!("Error deleting old error groups: {:#?}", e);
+    }
 }
 
 #[instrument(skip_all)]
 pub fn start_background_delete_traces_task(
     con: PgPool,
     time_between_runs: Duration,
+    imported_trace_retention_days: u16,
 ) -> JoinHandle<()> {
     tokio::spawn(async move {
         let con = con.clone();
         loop {
-            delete_old_traces_logging_errors(&con).await;
+            delete_old_traces_logging_errors(&con, imported_trace_retention_days).await;
             tokio::time::sleep(time_between_runs).await;
         }
     })
@@ -534,22 +568,31 @@ async fn batch_store_traces(con: &PgPool, traces: Vec<DbReadyTraceData>) -> Vec<
             let has_errors = trace.has_errors;
             let warning_count = trace.warning_count;
             let span_plus_events_count = trace.span_plus_events_count;
-            let id = store_trace(con.clone(), trace).await?;
-            Ok(InsertedTrace {
+            let id = store_trace(con.clone(), &trace).await?;
+            let inserted = InsertedTrace {
                 id,
                 service_name,
                 top_level_span_name,
                 has_errors,
                 warning_count,
                 span_plus_events_count,
-            })
+            };
+            Ok((inserted, trace))
         });
     }
+    let mut rollups = rollup::Rollups::default();
+    let mut error_groups = error_group::ErrorGroups::default();
     let mut buffer = futures::stream::iter(futs).buffer_unordered(30);
     while let Some(res) = buffer.next().await {
-        let res: Result<InsertedTrace, Error> = res;
+        let res: Result<(InsertedTrace, DbReadyTraceData), Error> = res;
         match res {
-            Ok(inserted) => {
+            Ok((inserted, trace)) => {
+                // imported traces are out of the rollup retention and would skew the live stats
+                // and error groups
+                if !trace.imported {
+                    rollups.add_trace(inserted.id, &trace);
+                    error_groups.add_trace(inserted.id, &trace);
+                }
                 inserted_traces.push(inserted);
             }
             Err(err) => {
@@ -557,6 +600,8 @@ async fn batch_store_traces(con: &PgPool, traces: Vec<DbReadyTraceData>) -> Vec<
             }
         }
     }
+    rollup::store_rollups_logging_errors(con, rollups).await;
+    error_group::store_error_groups_logging_errors(con, error_groups).await;
     inserted_traces
 }
 
@@ -597,6 +642,56 @@ pub async fn stage_trace_fragment(
     trace_fragment_pusher.try_push(otel_service_traces).await;
 }
 
+/// Validates and stores the traces of an uploaded file right away, without going through the
+/// fragment buffer or notifying. Traces missing a service name or failing validation are
+/// reported back instead.
+#[instrument(skip_all)]
+pub async fn import_traces(
+    con: &PgPool,
+    request: ExportTraceServiceRequest,
+) -> api_structs::ImportResult {
+    let mut rejected = vec![];
+    let mut db_ready_trace_data = vec![];
+    for resource_spans in request.resource_spans {
+        let single_trace = match extract_service_name_and_spans(resource_spans) {
+            Ok(single_trace) => single_trace,
+            Err(Error::Malformed(reason) | Error::Db(reason)) => {
+                rejected.push(api_structs::RejectedTrace {
+                    service_name: None,
+                    otel_trace_id: None,
+                    reason,
+                });
+                continue;
+            }
+        };
+        for (otel_trace_id, spans) in single_trace.trace_to_spans {
+            match process_trace_data_for_insertion(single_trace.service_name.clone(), spans) {
+                Ok(mut valid_data) => {
+                    valid_data.imported = true;
+                    db_ready_trace_data.push(valid_data);
+                }
+                Err(e) => rejected.push(api_structs::RejectedTrace {
+                    service_name: Some(single_trace.service_name.clone()),
+                    otel_trace_id: Some(otel_trace_id),
+                    reason: e.cause().to_string(),
+                }),
+            }
+        }
+    }
+    let to_store = db_ready_trace_data.len();
+    let mut imported_trace_ids: Vec<i64> = batch_store_traces(con, db_ready_trace_data)
+        .await
+        .into_iter()
+        .map(|inserted| inserted.id)
+        .collect();
+    imported_trace_ids.sort_unstable();
+    api_structs::ImportResult {
+        failed_to_store: to_store - imported_trace_ids.len(),
+        imported_trace_ids,
+        rejected,
+    }
+}
+
 pub struct DbReadyTraceData {
     timestamp: i64,
     service_name: String,
@@ -606,6 +701,7 @@ pub struct DbReadyTraceData {
     warning_count: u32,
     spans: Vec<DbSpan>,
     span_plus_events_count: usize,
+    imported: bool,
 }
 #[derive(Debug)]
 pub struct DbSpan {
@@ -614,6 +710,9 @@ pub struct DbSpan {
     parent_id: Option<i64>,
     name: String,
     duration: i64,
+    has_errors: bool,
+    /// Status message of error spans, when there is one
+    status_message: Option<String>,
     key_values: Vec<DbKeyValue>,
     events: Vec<DbEvent>,
 }
@@ -806,6 +905,13 @@ fn process_trace_data_for_insertion(
             parent_id,
             name: s.name.to_string(),
             duration: span_duration,
+            has_errors: span_processing::has_errors(s),
+            status_message: s
+                .status
+                .as_ref()
+                .filter(|_status| span_processing::has_errors(s))
+                .map(|status| status.message.trim().to_string())
+                .filter(|message| !message.is_empty()),
             key_values,
             events,
         });
@@ -819,16 +925,17 @@ fn process_trace_data_for_insertion(
         warning_count,
         spans: db_spans,
         span_plus_events_count,
+        imported: false,
     })
 }
 
 #[instrument(skip_all)]
-pub async fn store_trace(con: PgPool, data_for_insertion: DbReadyTraceData) -> Result<i64, Error> {
+pub async fn store_trace(con: PgPool, data_for_insertion: &DbReadyTraceData) -> Result<i64, Error> {
     let mut trans = con
         .begin()
         .instrument(info_span!("Starting DB transaction"))
         .await?;
-    let trace_id = insert_all_trace_data(&mut trans, &data_for_insertion).await?;
+    let trace_id = insert_all_trace_data(&mut trans, data_for_insertion).await?;
     trans
         .commit()
         .instrument(info_span!("Committing to DB"))
@@ -0,0 +1,363 @@
+use crate::otel_trace_processing::rollup::{NANOS_IN_1_MINUTE, ROLLUP_RETENTION_DAYS};
+use crate::otel_trace_processing::{DbReadyTraceData, Error, Level};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
+use tracing::{error, info, info_span, instrument, Instrument};
+
+/// Most recent traces kept per group, older ones are usually gone with the raw trace retention
+pub const SAMPLE_TRACE_IDS_PER_GROUP: usize = 5;
+/// Postgres limits a statement to 65535 bind parameters
+const ERROR_GROUP_ROWS_PER_INSERT: usize = 1000;
+/// Message of error status spans without an error event or a status message
+const ERROR_STATUS_MESSAGE: &str = "Span status is error";
+
+/// Ids with dashes (uuids, dates) are kept whole when long enough to be an id
+fn is_id(word: &str) -> bool {
+    word.chars().filter(|c| c.is_ascii_hexdigit()).count() >= 8
+        && word.chars().any(|c| c.is_ascii_digit())
+        && word.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+fn normalize_word(word: &str, normalized: &mut String) {
+    if is_id(word) {
+        normalized.push_str("<id>");
+        return;
+    }
+    for (idx, part) in word.split('-').enumerate() {
+        if idx > 0 {
+            normalized.push('-');
+        }
+        let leading_digits = part.chars().take_while(|c| c.is_ascii_digit()).count();
+        if part.len() >= 16 && part.chars().any(|c| c.is_ascii_digit()) {
+            // tokens and base64 ids
+            normalized.push_str("<id>");
+        } else if leading_digits > 0 {
+            // numbers and numbers with a unit, like 176ms
+            normalized.push_str("<num>");
+            normalized.push_str(&part[leading_digits..]);
+        } else {
+            normalized.push_str(part);
+        }
+    }
+}
+
+/// Replaces what usually differs between occurrences of the same error: quoted values, numbers
+/// and ids. "Query took 176ms for 'acme'" becomes "Query took <num>ms for '*'".
+pub fn normalize_message(message: &str) -> String {
+    let chars: Vec<char> = message.chars().collect();
+    let mut normalized = String::with_capacity(message.len());
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        // an apostrophe right after a letter is part of a word, like "can't"
+        let after_word = idx > 0 && chars[idx - 1].is_alphanumeric();
+        if matches!(c, '"' | '\'' | '`') && !after_word {
+            if let Some(quoted_len) = chars[idx + 1..].iter().position(|q| *q == c) {
+                normalized.push(c);
+                normalized.push('*');
+                normalized.push(c);
+                idx += quoted_len + 2;
+                continue;
+            }
+        }
+        if c.is_alphanumeric() {
+            let word_len = chars[idx..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '-')
+                .count();
+            let word: String = chars[idx..idx + word_len].iter().collect();
+            let trimmed = word.trim_end_matches('-');
+            normalize_word(trimmed, &mut normalized);
+            normalized.push_str(&word[trimmed.len()..]);
+            idx += word_len;
+            continue;
+        }
+        normalized.push(c);
+        idx += 1;
+    }
+    normalized
+}
+
+/// FNV-1a, stable across builds unlike the std hasher since it is stored
+fn fnv1a_64(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        // separator so ("ab", "c") and ("a", "bc") differ
+        for byte in part.bytes().chain(std::iter::once(0xff)) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+pub fn fingerprint(service_name: &str, span_name: &str, normalized_message: &str) -> String {
+    format!(
+        "{:016x}",
+        fnv1a_64(&[service_name, span_name, normalized_message])
+    )
+}
+
+#[derive(Debug, Clone)]
+struct ErrorGroupStats {
+    service_name: String,
+    span_name: String,
+    message: String,
+    first_seen: i64,
+    last_seen: i64,
+    occurrences: i64,
+    /// Most recent first
+    sample_trace_ids: Vec<i64>,
+}
+
+/// Error events and error status spans of a batch of stored traces grouped by fingerprint, merged
+/// into the error_group table in one go like the rollups
+#[derive(Debug, Default)]
+pub struct ErrorGroups {
+    groups: BTreeMap<String, ErrorGroupStats>,
+}
+
+impl ErrorGroups {
+    fn add_occurrence(
+        &mut self,
+        trace_id: i64,
+        service_name: &str,
+        span_name: &str,
+        message: &str,
+        timestamp: i64,
+    ) {
+        let message = normalize_message(message);
+        let group = self
+            .groups
+            .entry(fingerprint(service_name, span_name, &message))
+            .or_insert_with(|| ErrorGroupStats {
+                service_name: service_name.to_string(),
+                span_name: span_name.to_string(),
+                message,
+                first_seen: timestamp,
+                last_seen: timestamp,
+                occurrences: 0,
+                sample_trace_ids: vec![],
+            });
+        group.occurrences += 1;
+        group.first_seen = group.first_seen.min(timestamp);
+        group.last_seen = group.last_seen.max(timestamp);
+        if !group.sample_trace_ids.contains(&trace_id) {
+            // traces of a batch are stored concurrently, so the id order is close enough
+            group.sample_trace_ids.insert(0, trace_id);
+            group.sample_trace_ids.truncate(SAMPLE_TRACE_IDS_PER_GROUP);
+        }
+    }
+
+    /// Every error event is an occurrence, error status spans only count when they have no
+    /// error event themselves so one failure isn't counted twice
+    pub fn add_trace(&mut self, trace_id: i64, trace: &DbReadyTraceData) {
+        for span in &trace.spans {
+            let mut had_error_event = false;
+            for event in span
+                .events
+                .iter()
+                .filter(|e| matches!(e.severity, Level::Error))
+            {
+                had_error_event = true;
+                self.add_occurrence(
+                    trace_id,
+                    &trace.service_name,
+                    &span.name,
+                    &event.name,
+                    event.timestamp,
+                );
+            }
+            if span.has_errors && !had_error_event {
+                let message = span
+                    .status_message
+                    .as_deref()
+                    .unwrap_or(ERROR_STATUS_MESSAGE);
+                self.add_occurrence(
+                    trace_id,
+                    &trace.service_name,
+                    &span.name,
+                    message,
+                    span.timestamp,
+                );
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn store_error_groups(con: &PgPool, error_groups: ErrorGroups) -> Result<(), Error> {
+    info!(
+        error_groups = error_groups.groups.len(),
+        "Storing error groups"
+    );
+    let mut trans = con.begin().await?;
+    let groups: Vec<(String, ErrorGroupStats)> = error_groups.groups.into_iter().collect();
+    for chunk in groups.chunks(ERROR_GROUP_ROWS_PER_INSERT) {
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(
+            "insert into error_group as error_group (fingerprint, service_name, span_name, message, \
+            first_seen, last_seen, occurrences, sample_trace_ids) ",
+        );
+        query.push_values(chunk.iter().cloned(), |mut row, (fingerprint, stats)| {
+            row.push_bind(fingerprint)
+                .push_bind(stats.service_name)
+                .push_bind(stats.span_name)
+                .push_bind(stats.message)
+                .push_bind(stats.first_seen)
+                .push_bind(stats.last_seen)
+                .push_bind(stats.occurrences)
+                .push_bind(stats.sample_trace_ids);
+        });
+        query.push(format!(
+            " on conflict (fingerprint) do update set
+    first_seen = least(error_group.first_seen, excluded.first_seen),
+    last_seen = greatest(error_group.last_seen, excluded.last_seen),
+    occurrences = error_group.occurrences + excluded.occurrences,
+    sample_trace_ids = (excluded.sample_trace_ids || error_group.sample_trace_ids)[1:{SAMPLE_TRACE_IDS_PER_GROUP}],
+    resolved_at = case when excluded.last_seen > error_group.resolved_at
+        then null else error_group.resolved_at end"
+        ));
+        query
+            .build()
+            .execute(&mut trans)
+            .instrument(info_span!("Upserting error groups"))
+            .await?;
+    }
+    trans.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub async fn store_error_groups_logging_errors(con: &PgPool, error_groups: ErrorGroups) {
+    if error_groups.is_empty() {
+        return;
+    }
+    if let Err(e) = store_error_groups(con, error_groups).await {
+        error!("Error storing error groups: {:#?}", e);
+    }
+}
+
+/// Groups outlive the raw traces, they go once not seen for as long as the rollups are kept
+#[instrument(skip_all)]
+pub async fn delete_old_error_groups(con: &PgPool) -> Result<(), Error> {
+    let older_than =
+        chrono::Utc::now().timestamp_nanos() - ROLLUP_RETENTION_DAYS * 24 * 60 * NANOS_IN_1_MINUTE;
+    let res = sqlx::query!(
+        "delete from error_group where last_seen < $1::BIGINT;",
+        older_than
+    )
+    .execute(con)
+    .instrument(info_span!("deleting_old_error_groups"))
+    .await?;
+    info!("Deleted {} error groups", res.rows_affected());
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::otel_trace_processing::{DbEvent, DbSpan};
+
+#[cfg(test)]
+#[test]
+fn messages_are_normalized() {
+    assert_eq!(
+        normalize_message("Query took 176ms for 'acme' (can't retry)"),
+        "Query took <num>ms for '*' (can't retry)"
+    );
+    assert_eq!(
+        normalize_message("User 42 not found: id=550e8400-e29b-41d4-a716-446655440000"),
+        "User <num> not found: id=<id>"
+    );
+    assert_eq!(
+        normalize_message("order-1234 failed, key \"k1\" token eyJhbGciOiJIUzI1NiJ9 v2"),
+        "order-<num> failed, key \"*\" token <id> v2"
+    );
+    assert_eq!(
+        normalize_message("trace 4bf92f3577b34da6 at 1.5s -"),
+        "trace <id> at <num>.<num>s -"
+    );
+    assert_eq!(
+        fingerprint("billing", "pay", &normalize_message("timeout after 30s")),
+        fingerprint("billing", "pay", &normalize_message("timeout after 5s"))
+    );
+    assert_ne!(
+        fingerprint("billing", "pay", "timeout"),
+        fingerprint("billing", "refund", "timeout")
+    );
+}
+
+#[cfg(test)]
+fn span(
+    name: &str,
+    has_errors: bool,
+    status_message: Option<&str>,
+    events: Vec<DbEvent>,
+) -> DbSpan {
+    DbSpan {
+        id: 1,
+        timestamp: 10,
+        parent_id: None,
+        name: name.to_string(),
+        duration: 5,
+        has_errors,
+        status_message: status_message.map(str::to_string),
+        key_values: vec![],
+        events,
+    }
+}
+
+#[cfg(test)]
+fn event(name: &str, timestamp: i64, severity: Level) -> DbEvent {
+    DbEvent {
+        id: 1,
+        timestamp,
+        name: name.to_string(),
+        key_values: vec![],
+        severity,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn error_events_and_error_spans_are_grouped() {
+    let trace = DbReadyTraceData {
+        timestamp: 10,
+        service_name: "billing".to_string(),
+        duration: 5,
+        top_level_span_name: "pay".to_string(),
+        has_errors: true,
+        warning_count: 0,
+        spans: vec![
+            span(
+                "charge",
+                true,
+                Some("ignored, has an error event"),
+                vec![
+                    event("Card 1234 declined", 12, Level::Error),
+                    event("Card 99 declined", 11, Level::Error),
+                    event("Retrying", 13, Level::Warn),
+                ],
+            ),
+            span("refund", true, None, vec![]),
+            span("notify", false, None, vec![]),
+        ],
+        span_plus_events_count: 6,
+        imported: false,
+    };
+    let mut groups = ErrorGroups::default();
+    groups.add_trace(7, &trace);
+    groups.add_trace(8, &trace);
+    let mut groups: Vec<ErrorGroupStats> = groups.groups.into_values().collect();
+    groups.sort_by(|a, b| a.span_name.cmp(&b.span_name));
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].span_name, "charge");
+    assert_eq!(groups[0].message, "Card <num> declined");
+    assert_eq!(groups[0].occurrences, 4);
+    assert_eq!((groups[0].first_seen, groups[0].last_seen), (11, 12));
+    assert_eq!(groups[0].sample_trace_ids, vec![8, 7]);
+    assert_eq!(groups[1].message, ERROR_STATUS_MESSAGE);
+    assert_eq!(groups[1].occurrences, 2);
+}
@@ -0,0 +1,273 @@
+use crate::proto_generated::opentelemetry::proto::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto_generated::opentelemetry::proto::common::v1::{
+    any_value, AnyValue, ArrayValue, KeyValue, KeyValueList,
+};
+use crate::proto_generated::opentelemetry::proto::resource::v1::Resource;
+use crate::proto_generated::opentelemetry::proto::trace::v1::{
+    span, ResourceSpans, ScopeSpans, Span, Status,
+};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonExportTraceServiceRequest {
+    #[serde(default)]
+    resource_spans: Vec<JsonResourceSpans>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonResourceSpans {
+    resource: Option<JsonResource>,
+    #[serde(default)]
+    scope_spans: Vec<JsonScopeSpans>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonResource {
+    #[serde(default)]
+    attributes: Vec<JsonKeyValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonScopeSpans {
+    #[serde(default)]
+    spans: Vec<JsonSpan>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(default)]
+    parent_span_id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(deserialize_with = "string_or_number")]
+    start_time_unix_nano: u64,
+    #[serde(deserialize_with = "string_or_number")]
+    end_time_unix_nano: u64,
+    #[serde(default)]
+    attributes: Vec<JsonKeyValue>,
+    #[serde(default)]
+    dropped_attributes_count: u32,
+    #[serde(default)]
+    events: Vec<JsonEvent>,
+    #[serde(default)]
+    dropped_events_count: u32,
+    #[serde(default)]
+    dropped_links_count: u32,
+    status: Option<JsonStatus>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonEvent {
+    #[serde(deserialize_with = "string_or_number")]
+    time_unix_nano: u64,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    attributes: Vec<JsonKeyValue>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonStatus {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    code: i32,
+}
+
+#[derive(Deserialize)]
+struct JsonKeyValue {
+    key: String,
+    value: Option<JsonAnyValue>,
+}
+
+/// Variants are named after the OTLP JSON keys
+#[allow(clippy::enum_variant_names)]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum JsonAnyValue {
+    StringValue(String),
+    BoolValue(bool),
+    IntValue(#[serde(deserialize_with = "string_or_number")] i64),
+    DoubleValue(f64),
+    ArrayValue(JsonArrayValue),
+    KvlistValue(JsonKeyValueList),
+    /// Base64 in the spec, not decoded since bytes attributes are not supported anyway
+    BytesValue(String),
+}
+
+#[derive(Deserialize)]
+struct JsonArrayValue {
+    #[serde(default)]
+    values: Vec<JsonAnyValue>,
+}
+
+#[derive(Deserialize)]
+struct JsonKeyValueList {
+    #[serde(default)]
+    values: Vec<JsonKeyValue>,
+}
+
+fn string_or_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr + TryFrom<u64> + TryFrom<i64>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Unsigned(u64),
+        Signed(i64),
+    }
+    let invalid = || serde::de::Error::custom("integer out of range");
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s
+            .parse()
+            .map_err(|_e| serde::de::Error::custom(format!("invalid integer: {s}"))),
+        StringOrNumber::Unsigned(n) => T::try_from(n).map_err(|_e| invalid()),
+        StringOrNumber::Signed(n) => T::try_from(n).map_err(|_e| invalid()),
+    }
+}
+
+fn hex_id(id: &str) -> Result<Vec<u8>, String> {
+    base16::decode(id).map_err(|e| format!("Invalid hex id {id}: {e}"))
+}
+
+fn any_value(value: JsonAnyValue) -> AnyValue {
+    let value = match value {
+        JsonAnyValue::StringValue(s) => any_value::Value::StringValue(s),
+        JsonAnyValue::BoolValue(b) => any_value::Value::BoolValue(b),
+        JsonAnyValue::IntValue(i) => any_value::Value::IntValue(i),
+        JsonAnyValue::DoubleValue(d) => any_value::Value::DoubleValue(d),
+        JsonAnyValue::ArrayValue(array) => any_value::Value::ArrayValue(ArrayValue {
+            values: array.values.into_iter().map(any_value).collect(),
+        }),
+        JsonAnyValue::KvlistValue(list) => any_value::Value::KvlistValue(KeyValueList {
+            values: key_values(list.values),
+        }),
+        JsonAnyValue::BytesValue(b) => any_value::Value::BytesValue(b.into_bytes()),
+    };
+    AnyValue { value: Some(value) }
+}
+
+fn key_values(key_values: Vec<JsonKeyValue>) -> Vec<KeyValue> {
+    key_values
+        .into_iter()
+        .map(|kv| KeyValue {
+            key: kv.key,
+            value: kv.value.map(any_value),
+        })
+        .collect()
+}
+
+fn proto_span(json: JsonSpan) -> Result<Span, String> {
+    Ok(Span {
+        trace_id: hex_id(&json.trace_id)?,
+        span_id: hex_id(&json.span_id)?,
+        trace_state: String::new(),
+        parent_span_id: hex_id(&json.parent_span_id)?,
+        name: json.name,
+        kind: 0,
+        start_time_unix_nano: json.start_time_unix_nano,
+        end_time_unix_nano: json.end_time_unix_nano,
+        attributes: key_values(json.attributes),
+        dropped_attributes_count: json.dropped_attributes_count,
+        events: json
+            .events
+            .into_iter()
+            .map(|event| span::Event {
+                time_unix_nano: event.time_unix_nano,
+                name: event.name,
+                attributes: key_values(event.attributes),
+                dropped_attributes_count: 0,
+            })
+            .collect(),
+        dropped_events_count: json.dropped_events_count,
+        links: vec![],
+        dropped_links_count: json.dropped_links_count,
+        status: json.status.map(|status| Status {
+            message: status.message,
+            code: status.code,
+        }),
+    })
+}
+
+/// OTLP/HTTP JSON encoding of an [ExportTraceServiceRequest]: camelCase fields, trace and span
+/// ids as hex, 64 bit integers as strings or numbers and unknown fields ignored
+pub fn parse_export_trace_service_request(
+    json: &[u8],
+) -> Result<ExportTraceServiceRequest, String> {
+    let request: JsonExportTraceServiceRequest =
+        serde_json::from_slice(json).map_err(|e| format!("Invalid OTLP JSON: {e}"))?;
+    let resource_spans = request
+        .resource_spans
+        .into_iter()
+        .map(|resource_spans| {
+            let spans = resource_spans
+                .scope_spans
+                .into_iter()
+                .flat_map(|scope_spans| scope_spans.spans)
+                .map(proto_span)
+                .collect::<Result<Vec<Span>, String>>()?;
+            Ok(ResourceSpans {
+                resource: resource_spans.resource.map(|resource| Resource {
+                    attributes: key_values(resource.attributes),
+                    dropped_attributes_count: 0,
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: None,
+                    spans,
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            })
+        })
+        .collect::<Result<Vec<ResourceSpans>, String>>()?;
+    Ok(ExportTraceServiceRequest { resource_spans })
+}
+
+#[cfg(test)]
+#[test]
+fn parses_otlp_json_with_string_and_number_integers() {
+    let json = r#"{"resourceSpans": [{
+        "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "billing"}}]},
+        "scopeSpans": [{"scope": {"name": "x"}, "spans": [
+            {"traceId": "5b8efff798038103d269b633813fc60c", "spanId": "eee19b7ec3c1b174",
+             "parentSpanId": "", "name": "POST /pay", "kind": 2,
+             "startTimeUnixNano": "1544712660000000000", "endTimeUnixNano": 1544712661000000000,
+             "attributes": [{"key": "retries", "value": {"intValue": "3"}}],
+             "events": [{"timeUnixNano": "1544712660500000000", "name": "paid",
+                         "attributes": [{"key": "level", "value": {"stringValue": "INFO"}}]}],
+             "status": {"code": 2}}
+        ]}]
+    }]}"#;
+    let request = parse_export_trace_service_request(json.as_bytes()).unwrap();
+    let span = &request.resource_spans[0].scope_spans[0].spans[0];
+    assert_eq!(
+        span.span_id,
+        vec![0xee, 0xe1, 0x9b, 0x7e, 0xc3, 0xc1, 0xb1, 0x74]
+    );
+    assert!(span.parent_span_id.is_empty());
+    assert_eq!(
+        span.end_time_unix_nano - span.start_time_unix_nano,
+        1_000_000_000
+    );
+    assert_eq!(
+        span.attributes[0].value,
+        Some(AnyValue {
+            value: Some(any_value::Value::IntValue(3))
+        })
+    );
+    assert_eq!(span.events[0].time_unix_nano, 1544712660500000000);
+    assert_eq!(span.status.as_ref().unwrap().code, 2);
+    assert!(parse_export_trace_service_request(b"{\"resourceSpans\": 1}").is_err());
+}
@@ -0,0 +1,290 @@
+use crate::otel_trace_processing::{DbReadyTraceData, Error, Level};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::collections::BTreeMap;
+use tracing::{error, info, info_span, instrument, Instrument};
+
+pub const NANOS_IN_1_MINUTE: i64 = 60_000_000_000;
+pub const ROLLUP_RETENTION_DAYS: i64 = 30;
+/// Bucket 0 holds everything under 2^10ns (~1us), every following bucket is half an octave wide,
+/// so the last one starts at 2^42ns (~73min)
+pub const DURATION_HISTOGRAM_BUCKETS: usize = 66;
+const FIRST_BUCKET_BOUNDARY_LOG2: f64 = 10.;
+/// Postgres limits a statement to 65535 bind parameters
+const ROLLUP_ROWS_PER_INSERT: usize = 1000;
+
+pub fn duration_bucket(duration_ns: i64) -> usize {
+    if duration_ns < 1 << (FIRST_BUCKET_BOUNDARY_LOG2 as i64) {
+        return 0;
+    }
+    let half_octaves = ((duration_ns as f64).log2() - FIRST_BUCKET_BOUNDARY_LOG2) * 2.;
+    (half_octaves.floor() as usize + 1).min(DURATION_HISTOGRAM_BUCKETS - 1)
+}
+
+/// Geometric middle of the bucket, errs by at most ~19% for durations within the bucket
+pub fn bucket_representative_duration(bucket: usize) -> u64 {
+    let log2 = if bucket == 0 {
+        FIRST_BUCKET_BOUNDARY_LOG2 - 1.
+    } else {
+        FIRST_BUCKET_BOUNDARY_LOG2 + (bucket as f64 - 0.5) / 2.
+    };
+    2f64.powf(log2).round() as u64
+}
+
+/// Approximate percentile (between 0 and 1) of the durations counted by the histogram
+pub fn histogram_percentile(histogram: &[i64], percentile: f64) -> u64 {
+    let total: i64 = histogram.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+    let rank = ((percentile * total as f64).ceil() as i64).clamp(1, total);
+    let mut seen = 0;
+    for (bucket, count) in histogram.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return bucket_representative_duration(bucket);
+        }
+    }
+    bucket_representative_duration(histogram.len() - 1)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct TraceRollupKey {
+    minute: i64,
+    service_name: String,
+    top_level_span_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct SpanRollupKey {
+    minute: i64,
+    service_name: String,
+    top_level_span_name: String,
+    span_name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct RollupStats {
+    count: i64,
+    error_count: i64,
+    warning_count: i64,
+    count_with_warnings: i64,
+    max_duration: i64,
+    longest_trace_id: i64,
+    duration_histogram: Vec<i64>,
+}
+
+impl RollupStats {
+    fn add(&mut self, trace_id: i64, duration: i64, has_errors: bool, warning_count: i64) {
+        self.count += 1;
+        self.error_count += i64::from(has_errors);
+        self.warning_count += warning_count;
+        self.count_with_warnings += i64::from(warning_count > 0);
+        if self.count == 1 || duration > self.max_duration {
+            self.max_duration = duration;
+            self.longest_trace_id = trace_id;
+        }
+        let bucket = duration_bucket(duration);
+        if self.duration_histogram.len() <= bucket {
+            self.duration_histogram.resize(bucket + 1, 0);
+        }
+        self.duration_histogram[bucket] += 1;
+    }
+}
+
+fn minute_of(timestamp: i64) -> i64 {
+    timestamp - timestamp.rem_euclid(NANOS_IN_1_MINUTE)
+}
+
+/// Per minute statistics of a batch of stored traces, merged into the rollup tables in one go
+/// so concurrent trace inserts don't fight over the same rollup rows
+#[derive(Debug, Default)]
+pub struct Rollups {
+    traces: BTreeMap<TraceRollupKey, RollupStats>,
+    spans: BTreeMap<SpanRollupKey, RollupStats>,
+}
+
+impl Rollups {
+    pub fn add_trace(&mut self, trace_id: i64, trace: &DbReadyTraceData) {
+        self.traces
+            .entry(TraceRollupKey {
+                minute: minute_of(trace.timestamp),
+                service_name: trace.service_name.clone(),
+                top_level_span_name: trace.top_level_span_name.clone(),
+            })
+            .or_default()
+            .add(
+                trace_id,
+                trace.duration,
+                trace.has_errors,
+                i64::from(trace.warning_count),
+            );
+        for span in &trace.spans {
+            let warning_count = span
+                .events
+                .iter()
+                .filter(|e| matches!(e.severity, Level::Warn))
+                .count();
+            self.spans
+                .entry(SpanRollupKey {
+                    minute: minute_of(span.timestamp),
+                    service_name: trace.service_name.clone(),
+                    top_level_span_name: trace.top_level_span_name.clone(),
+                    span_name: span.name.clone(),
+                })
+                .or_default()
+                .add(
+                    trace_id,
+                    span.duration,
+                    span.has_errors,
+                    i64::try_from(warning_count).expect("warning count to fit i64"),
+                );
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+}
+
+fn push_stats_values(mut row: sqlx::query_builder::Separated<Postgres, &str>, stats: RollupStats) {
+    row.push_bind(stats.count)
+        .push_bind(stats.error_count)
+        .push_bind(stats.warning_count)
+        .push_bind(stats.count_with_warnings)
+        .push_bind(stats.max_duration)
+        .push_bind(stats.longest_trace_id)
+        .push_bind(stats.duration_histogram);
+}
+
+const STATS_CONFLICT_UPDATE: &str = "
+    count = rollup.count + excluded.count,
+    error_count = rollup.error_count + excluded.error_count,
+    warning_count = rollup.warning_count + excluded.warning_count,
+    count_with_warnings = rollup.count_with_warnings + excluded.count_with_warnings,
+    longest_trace_id = case when excluded.max_duration > rollup.max_duration
+        then excluded.longest_trace_id else rollup.longest_trace_id end,
+    max_duration = greatest(rollup.max_duration, excluded.max_duration),
+    duration_histogram = add_histograms(rollup.duration_histogram, excluded.duration_histogram)";
+
+#[instrument(skip_all)]
+pub async fn store_rollups(con: &PgPool, rollups: Rollups) -> Result<(), Error> {
+    info!(
+        trace_rollups = rollups.traces.len(),
+        span_rollups = rollups.spans.len(),
+        "Storing rollups"
+    );
+    let mut trans = con.begin().await?;
+    let traces: Vec<(TraceRollupKey, RollupStats)> = rollups.traces.into_iter().collect();
+    for chunk in traces.chunks(ROLLUP_ROWS_PER_INSERT) {
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(
+            "insert into trace_rollup_minute as rollup (minute, service_name, top_level_span_name, \
+            count, error_count, warning_count, count_with_warnings, max_duration, longest_trace_id, duration_histogram) ",
+        );
+        query.push_values(chunk.iter().cloned(), |mut row, (key, stats)| {
+            row.push_bind(key.minute)
+                .push_bind(key.service_name)
+                .push_bind(key.top_level_span_name);
+            push_stats_values(row, stats);
+        });
+        query.push(" on conflict (service_name, top_level_span_name, minute) do update set ");
+        query.push(STATS_CONFLICT_UPDATE);
+        query
+            .build()
+            .execute(&mut trans)
+            .instrument(info_span!("Upserting trace rollups"))
+            .await?;
+    }
+    let spans: Vec<(SpanRollupKey, RollupStats)> = rollups.spans.into_iter().collect();
+    for chunk in spans.chunks(ROLLUP_ROWS_PER_INSERT) {
+        let mut query: QueryBuilder<Postgres> = QueryBuilder::new(
+            "insert into span_rollup_minute as rollup (minute, service_name, top_level_span_name, span_name, \
+            count, error_count, warning_count, count_with_warnings, max_duration, longest_trace_id, duration_histogram) ",
+        );
+        query.push_values(chunk.iter().cloned(), |mut row, (key, stats)| {
+            row.push_bind(key.minute)
+                .push_bind(key.service_name)
+                .push_bind(key.top_level_span_name)
+                .push_bind(key.span_name);
+            push_stats_values(row, stats);
+        });
+        query.push(
+            " on conflict (service_name, top_level_span_name, span_name, minute) do update set ",
+        );
+        query.push(STATS_CONFLICT_UPDATE);
+        query
+            .build()
+            .execute(&mut trans)
+            .instrument(info_span!("Upserting span rollups"))
+            .await?;
+    }
+    trans.commit().await?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub async fn store_rollups_logging_errors(con: &PgPool, rollups: Rollups) {
+    if rollups.is_empty() {
+        return;
+    }
+    if let Err(e) = store_rollups(con, rollups).await {
+        error!("Error storing rollups: {:#?}", e);
+    }
+}
+
+#[instrument(skip_all)]
+pub async fn delete_old_rollups(con: &PgPool) -> Result<(), Error> {
+    let older_than =
+        chrono::Utc::now().timestamp_nanos() - ROLLUP_RETENTION_DAYS * 24 * 60 * NANOS_IN_1_MINUTE;
+    let traces = sqlx::query!(
+        "delete from trace_rollup_minute where minute < $1::BIGINT;",
+        older_than
+    )
+    .execute(con)
+    .instrument(info_span!("deleting_old_trace_rollups"))
+    .await?;
+    let spans = sqlx::query!(
+        "delete from span_rollup_minute where minute < $1::BIGINT;",
+        older_than
+    )
+    .execute(con)
+    .instrument(info_span!("deleting_old_span_rollups"))
+    .await?;
+    info!(
+        "Deleted {} trace and {} span rollups",
+        traces.rows_affected(),
+        spans.rows_affected()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+#[test]
+fn durations_land_in_half_octave_buckets() {
+    assert_eq!(duration_bucket(0), 0);
+    assert_eq!(duration_bucket(1023), 0);
+    assert_eq!(duration_bucket(1024), 1);
+    assert_eq!(duration_bucket(1448), 1);
+    assert_eq!(duration_bucket(1449), 2);
+    assert_eq!(duration_bucket(2048), 3);
+    assert_eq!(duration_bucket(i64::MAX), DURATION_HISTOGRAM_BUCKETS - 1);
+    for duration in [5_000, 1_000_000, 250_000_000, 3_000_000_000] {
+        let representative = bucket_representative_duration(duration_bucket(duration));
+        let error = (representative as f64 - duration as f64).abs() / duration as f64;
+        assert!(error < 0.2, "{duration} -> {representative}");
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn percentiles_from_histogram() {
+    let mut histogram = vec![0; DURATION_HISTOGRAM_BUCKETS];
+    histogram[duration_bucket(1_000_000)] = 90;
+    histogram[duration_bucket(1_000_000_000)] = 10;
+    let fast = bucket_representative_duration(duration_bucket(1_000_000));
+    let slow = bucket_representative_duration(duration_bucket(1_000_000_000));
+    assert_eq!(histogram_percentile(&histogram, 0.5), fast);
+    assert_eq!(histogram_percentile(&histogram, 0.9), fast);
+    assert_eq!(histogram_percentile(&histogram, 0.91), slow);
+    assert_eq!(histogram_percentile(&histogram, 0.99), slow);
+    assert_eq!(histogram_percentile(&[], 0.5), 0);
+}
@@ -0,0 +1,179 @@
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{CompareTracesRequest, ComparedSpan, SpanComparison, TraceComparison};
+use leptos::*;
+use leptos_router::use_query_map;
+
+fn ms(nanos: u64) -> String {
+    format!("{:.2}", nanos as f64 / 1_000_000.)
+}
+
+fn side_cells(cx: Scope, side: &Option<ComparedSpan>) -> impl IntoView {
+    match side {
+        Some(span) => view! {cx,
+            <>
+                <td class="trace-table__cell">{ms(span.duration)}</td>
+                <td class="trace-table__cell">{span.event_count}</td>
+            </>
+        },
+        None => view! {cx,
+            <>
+                <td class="trace-table__cell">"-"</td>
+                <td class="trace-table__cell">"-"</td>
+            </>
+        },
+    }
+}
+
+fn comparison_row(cx: Scope, span: SpanComparison) -> impl IntoView {
+    let row_class = match (&span.left, &span.right) {
+        (Some(_), None) => "row-container trace-comparison__left-only",
+        (None, Some(_)) => "row-container trace-comparison__right-only",
+        _ => "row-container",
+    };
+    let delta = span
+        .duration_delta
+        .map(|delta| {
+            let sign = if delta > 0 { "+" } else { "" };
+            format!("{sign}{:.2}", delta as f64 / 1_000_000.)
+        })
+        .unwrap_or_else(|| "-".to_string());
+    let delta_class = match span.duration_delta {
+        Some(delta) if delta > 0 => "trace-table__cell trace-comparison__slower",
+        Some(delta) if delta < 0 => "trace-table__cell trace-comparison__faster",
+        _ => "trace-table__cell",
+    };
+    let attribute_differences = span
+        .attribute_differences
+        .iter()
+        .map(|d| {
+            format!(
+                "{}: {} → {}",
+                d.key,
+                d.left.as_deref().unwrap_or("∅"),
+                d.right.as_deref().unwrap_or("∅")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    view! {cx,
+        <tr class=row_class>
+            <td class="trace-table__cell" style=format!("padding-left: {}px", 8 + span.depth * 16)>{span.name.clone()}</td>
+            {side_cells(cx, &span.left)}
+            {side_cells(cx, &span.right)}
+            <td class=delta_class>{delta}</td>
+            <td class="trace-table__cell">{attribute_differences}</td>
+        </tr>
+    }
+}
+
+/// Two traces side by side, spans matched by name path. Rows only in the left trace are
+/// red, only in the right one green.
+#[component]
+pub fn TraceComparisonView(cx: Scope, root_path: String) -> impl IntoView {
+    let query = use_query_map(cx);
+    let request = create_memo(cx, move |_| {
+        query.with(|q| {
+            Some(CompareTracesRequest {
+                left_trace_id: q.get("left_trace_id")?.parse().ok()?,
+                right_trace_id: q.get("right_trace_id")?.parse().ok()?,
+            })
+        })
+    });
+    let (left_input_r, left_input_w) = create_signal(cx, String::new());
+    let (right_input_r, right_input_w) = create_signal(cx, String::new());
+    create_effect(cx, move |_| {
+        if let Some(request) = request.get() {
+            left_input_w.set(request.left_trace_id.to_string());
+            right_input_w.set(request.right_trace_id.to_string());
+        }
+    });
+    let (comparison_r, comparison_w) = create_signal(cx, TraceComparison::default());
+    let _api_request_sender = create_local_resource(
+        cx,
+        move || request.get(),
+        move |request| async move {
+            if let Some(request) = request {
+                get_comparison(request, comparison_w).await;
+            }
+        },
+    );
+    let rows = move || {
+        comparison_r
+            .get()
+            .spans
+            .into_iter()
+            .map(|span| comparison_row(cx, span))
+            .collect::<Vec<_>>()
+    };
+    let compare_root_path = root_path.clone();
+    let trace_link = move |trace_id: Option<i64>| {
+        let root_path = root_path.clone();
+        trace_id.map(|trace_id| {
+            view! {cx,
+                <a href=format!("{root_path}trace?trace_id={trace_id}")>{trace_id}</a>
+            }
+        })
+    };
+    let left_trace_link = trace_link.clone();
+    let right_trace_link = trace_link;
+    let compare_href = move || {
+        format!(
+            "{compare_root_path}compare?left_trace_id={}&right_trace_id={}",
+            left_input_r.get().trim(),
+            right_input_r.get().trim()
+        )
+    };
+    view! {cx,
+        <div class="main-grid">
+            <div class="main">
+                <table class="trace-table">
+                    <tr class="row-container">
+                        <th class="trace-table__cell">"Span"</th>
+                        <th class="trace-table__cell">
+                            "Left (ms) "{move || left_trace_link(request.get().map(|r| r.left_trace_id))}
+                        </th>
+                        <th class="trace-table__cell">"Left Events"</th>
+                        <th class="trace-table__cell">
+                            "Right (ms) "{move || right_trace_link(request.get().map(|r| r.right_trace_id))}
+                        </th>
+                        <th class="trace-table__cell">"Right Events"</th>
+                        <th class="trace-table__cell">"Δ (ms)"</th>
+                        <th class="trace-table__cell">"Attribute Differences"</th>
+                    </tr>
+                    {rows}
+                </table>
+            </div>
+            <div class="search-panel">
+                <label class="search-panel__label">
+                    "Left trace id:"
+                    <input class="search-panel__input" type="text" size="20"
+                        prop:value=move || left_input_r.get()
+                        on:input=move |ev| left_input_w.set(event_target_value(&ev))
+                    />
+                </label>
+                <label class="search-panel__label">
+                    "Right trace id:"
+                    <input class="search-panel__input" type="text" size="20"
+                        prop:value=move || right_input_r.get()
+                        on:input=move |ev| right_input_w.set(event_target_value(&ev))
+                    />
+                </label>
+                <a class="search-panel__label" href=compare_href>"Compare"</a>
+            </div>
+        </div>
+    }
+}
+
+async fn get_comparison(request: CompareTracesRequest, w: WriteSignal<TraceComparison>) {
+    let comparison: TraceComparison = gloo_net::http::Request::get(&format!(
+        "{}/api/trace/compare?left_trace_id={}&right_trace_id={}",
+        API_SERVER_URL_NO_TRAILING_SLASH, request.left_trace_id, request.right_trace_id
+    ))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    w.set(comparison);
+}
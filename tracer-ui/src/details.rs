@@ -1,16 +1,28 @@
+use crate::self_time::SpanSelfTimeTable;
 use crate::API_SERVER_URL_NO_TRAILING_SLASH;
-use api_structs::{Severity, Span};
+use api_structs::{CriticalPathSpan, ExportFormat, Severity, Span};
 use leptos::ev::MouseEvent;
 use leptos::{
-    component, create_signal, log, view, Fragment, IntoView, Scope, Signal, SignalGet, SignalSet,
-    WriteSignal,
+    component, create_effect, create_memo, create_signal, document, event_target_checked,
+    event_target_value, log, request_animation_frame, view, Fragment, IntoView, Scope, Signal,
+    SignalGet, SignalSet, SignalWith, WriteSignal,
 };
 use leptos_router::ParamsMap;
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::rc::Rc;
 
-fn span_detail(cx: Scope, trace_spans_r: Signal<Vec<Span>>) -> Fragment {
+/// Element id of the span that matched the search the trace was opened from
+const MATCHED_SPAN_ELEMENT_ID: &str = "matched-span";
+
+/// `critical_path` maps the id of the spans to highlight to their contribution to the trace duration,
+/// `matched_span_id` is the span that matched the search the trace was opened from
+fn span_detail(
+    cx: Scope,
+    trace_spans_r: Signal<Vec<Span>>,
+    critical_path: Signal<HashMap<u64, u64>>,
+    matched_span_id: Option<u64>,
+) -> Fragment {
     let spans = trace_spans_r.get();
     if spans.is_empty() {
         return view! {cx, <><p style="color: white">{format!("Empty, crashed or still loading trace 😅. Check the network tab.")}</p></>};
@@ -62,15 +74,19 @@ fn span_detail(cx: Scope, trace_spans_r: Signal<Vec<Span>>) -> Fragment {
         let new_root_start_offset =
             ((root_duration_micros as f64) * (start_percentage / 100.)) as u64;
         let new_root_start_micros = root_start_time_unix_micros + new_root_start_offset;
-        create_html_span_and_children(
-            cx,
-            new_root_start_micros,
-            new_root_duration,
-            &root,
-            Rc::clone(&spans_by_parent_id),
-            0,
-            &mut html_span_and_children_fragments,
-        );
+        critical_path.with(|critical_path| {
+            create_html_span_and_children(
+                cx,
+                new_root_start_micros,
+                new_root_duration,
+                &root,
+                Rc::clone(&spans_by_parent_id),
+                critical_path,
+                matched_span_id,
+                0,
+                &mut html_span_and_children_fragments,
+            )
+        });
         html_span_and_children_fragments
     };
 
@@ -113,22 +129,103 @@ fn span_detail(cx: Scope, trace_spans_r: Signal<Vec<Span>>) -> Fragment {
 }
 
 #[component]
-pub fn TraceDetails(cx: Scope) -> impl IntoView {
+pub fn TraceDetails(cx: Scope, root_path: String) -> impl IntoView {
     let query_parameters = leptos_router::use_query_map(cx).get();
+    let trace_id = query_parameters
+        .get("trace_id")
+        .cloned()
+        .unwrap_or_default();
+    let matched_span_id: Option<u64> = query_parameters
+        .get("span_id")
+        .and_then(|span_id| span_id.parse().ok());
+    let downloads = ExportFormat::ALL
+        .into_iter()
+        .map(|format| {
+            let href = format!(
+                "{}/api/trace?trace_id={trace_id}&format={}",
+                API_SERVER_URL_NO_TRAILING_SLASH,
+                format.query_value()
+            );
+            view! {cx,
+                <a class="search-panel__label" href=href download="">{format!("⬇ {}", format.label())}</a>
+            }
+        })
+        .collect::<Vec<_>>();
+    let (compare_with_r, compare_with_w) = create_signal(cx, String::new());
+    let compare_href = move || {
+        format!(
+            "{root_path}compare?left_trace_id={trace_id}&right_trace_id={}",
+            compare_with_r.get().trim()
+        )
+    };
+    let critical_path_query_parameters = query_parameters.clone();
+    let self_time_query_parameters = query_parameters.clone();
     let (trace_spans_r, trace_spans_w) = leptos::create_signal(cx, Vec::new());
     let _api_request_sender =
         leptos::create_local_resource(cx, move || query_parameters.clone(), {
             move |qp| get_single_trace(qp, trace_spans_w)
         });
-    let html_spans = move || span_detail(cx, Signal::from(trace_spans_r));
+    let (critical_path_r, critical_path_w) = create_signal(cx, Vec::new());
+    let _critical_path_request_sender = leptos::create_local_resource(
+        cx,
+        move || critical_path_query_parameters.clone(),
+        move |qp| get_critical_path(qp, critical_path_w),
+    );
+    let (highlight_critical_path_r, highlight_critical_path_w) = create_signal(cx, true);
+    let critical_path = create_memo(cx, move |_| {
+        if !highlight_critical_path_r.get() {
+            return HashMap::new();
+        }
+        critical_path_r.with(|path: &Vec<CriticalPathSpan>| {
+            path.iter()
+                .map(|s| (s.span_id, s.contribution))
+                .collect::<HashMap<u64, u64>>()
+        })
+    });
+    let html_spans = move || {
+        span_detail(
+            cx,
+            Signal::from(trace_spans_r),
+            Signal::from(critical_path),
+            matched_span_id,
+        )
+    };
+    create_effect(cx, move |_| {
+        let loaded = trace_spans_r.with(|spans| !spans.is_empty());
+        if loaded && matched_span_id.is_some() {
+            // wait for the spans to be rendered before scrolling to the matched one
+            request_animation_frame(|| {
+                if let Some(matched_span) = document().get_element_by_id(MATCHED_SPAN_ELEMENT_ID) {
+                    matched_span.scroll_into_view();
+                }
+            });
+        }
+    });
     view! {cx,
         <div class="main-grid">
             <div class="main">
+                <SpanSelfTimeTable trace_query_parameters=self_time_query_parameters/>
                 <div class="trace-details">
                     {html_spans}
                 </div>
             </div>
             <div class="search-panel">
+                <label class="search-panel__label">
+                    "Highlight critical path:"
+                    <input class="search-panel__input search-panel__input__inline" type="checkbox"
+                        prop:checked=move || highlight_critical_path_r.get()
+                        on:click=move |ev| highlight_critical_path_w.set(event_target_checked(&ev))
+                    />
+                </label>
+                <label class="search-panel__label">
+                    "Compare with trace id:"
+                    <input class="search-panel__input" type="text" size="20"
+                        on:input=move |ev| compare_with_w.set(event_target_value(&ev))
+                    />
+                </label>
+                <a class="search-panel__label" href=compare_href>"Compare"</a>
+                <p class="search-panel__label">"Download as:"</p>
+                {downloads}
                 <label class="search-panel__label">
                     "TODO (span/event details):"
                     <input
@@ -169,12 +266,30 @@ async fn get_single_trace(id: ParamsMap, w: WriteSignal<Vec<Span>>) {
     w.set(traces);
 }
 
+async fn get_critical_path(id: ParamsMap, w: WriteSignal<Vec<CriticalPathSpan>>) {
+    let critical_path: Vec<CriticalPathSpan> = gloo_net::http::Request::get(&format!(
+        "{}/api/trace/critical-path{}",
+        API_SERVER_URL_NO_TRAILING_SLASH,
+        id.to_query_string()
+    ))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    w.set(critical_path);
+}
+
+#[allow(clippy::too_many_arguments)]
 fn create_html_span_and_children(
     cx: Scope,
     root_start_time_unix_micros: u64,
     root_duration_micros: u64,
     span: &Span,
     spans_by_parent_id: Rc<HashMap<u64, Vec<Span>>>,
+    critical_path: &HashMap<u64, u64>,
+    matched_span_id: Option<u64>,
     depth: i32,
     html_span_and_children_fragments: &mut Vec<Fragment>,
 ) {
@@ -186,6 +301,8 @@ fn create_html_span_and_children(
         root_start_time_unix_micros,
         root_duration_micros,
         span,
+        critical_path.get(&span.id).copied(),
+        matched_span_id == Some(span.id),
         depth,
     ) {
         html_span_and_children_fragments.push(e);
@@ -197,6 +314,8 @@ fn create_html_span_and_children(
             root_duration_micros,
             c,
             Rc::clone(&spans_by_parent_id),
+            critical_path,
+            matched_span_id,
             depth + 1,
             &mut *html_span_and_children_fragments,
         );
@@ -330,6 +449,8 @@ fn create_html_span(
     root_timestamp: u64,
     root_duration: u64,
     span: &Span,
+    critical_path_contribution: Option<u64>,
+    matched: bool,
     depth: i32,
 ) -> Option<Fragment> {
     let mut span_start = span.timestamp;
@@ -444,10 +565,23 @@ fn create_html_span(
     } else {
         span.name.to_string()
     };
+    let (critical_path_class, critical_path_label) = match critical_path_contribution {
+        Some(contribution) => (
+            "trace-details__span--critical",
+            format!(" - critical path {}ms", contribution / 1_000_000),
+        ),
+        None => ("", "".to_string()),
+    };
+    let matched_class = if matched {
+        "trace-details__span--matched"
+    } else {
+        ""
+    };
+    let matched_id = matched.then_some(MATCHED_SPAN_ELEMENT_ID);
     let span_html = view! {cx,
         <>
-        <p class="trace-details__span-name">{format!("{} - {}ms{span_k_v}", span_with_code_namespace, span.duration/1000_000)}</p>
-        <div style={format!("margin-left: {start_offset_percentage}%; width: {duration_percentage}%; {}", span_style)}></div>
+        <p id=matched_id class=format!("trace-details__span-name {critical_path_class} {matched_class}")>{format!("{} - {}ms{critical_path_label}{span_k_v}", span_with_code_namespace, span.duration/1000_000)}</p>
+        <div class=critical_path_class style={format!("margin-left: {start_offset_percentage}%; width: {duration_percentage}%; {}", span_style)}></div>
             {events}
         </>
     };
@@ -0,0 +1,152 @@
+use crate::grid::{debounced_api, unix_nanos_to_local_date};
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{ErrorGroup, ErrorGroupsRequest, ResolveErrorGroupRequest};
+use leptos::ev::{Event, MouseEvent};
+use leptos::*;
+
+async fn get_error_groups(request: ErrorGroupsRequest, w: WriteSignal<Vec<ErrorGroup>>) {
+    let groups =
+        gloo_net::http::Request::post(&format!("{}/api/errors", API_SERVER_URL_NO_TRAILING_SLASH))
+            .json(&request)
+            .unwrap()
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    w.set(groups);
+}
+
+async fn resolve_error_group(request: ResolveErrorGroupRequest) -> ErrorGroup {
+    gloo_net::http::Request::post(&format!(
+        "{}/api/errors/resolve",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap()
+}
+
+fn format_date(timestamp: u64) -> String {
+    unix_nanos_to_local_date(timestamp)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string()
+}
+
+/// Error groups, most recently seen first, which can be resolved until they happen again
+#[component]
+pub fn ErrorsInbox(cx: Scope, root_path: String) -> impl IntoView {
+    let (request_r, request_w) = create_signal(
+        cx,
+        ErrorGroupsRequest {
+            include_resolved: false,
+            service_name: String::new(),
+        },
+    );
+    let (groups_r, groups_w) = create_signal(cx, Vec::<ErrorGroup>::new());
+    let _request_state = debounced_api(
+        cx,
+        move || request_r.get(),
+        move |request| get_error_groups(request, groups_w),
+    );
+    let service_name_changed =
+        move |ev: Event| request_w.update(|r| r.service_name = event_target_value(&ev));
+    let include_resolved_changed =
+        move |ev: Event| request_w.update(|r| r.include_resolved = event_target_checked(&ev));
+
+    let set_resolved = move |fingerprint: String, resolved: bool| {
+        spawn_local(async move {
+            let updated = resolve_error_group(ResolveErrorGroupRequest {
+                fingerprint,
+                resolved,
+            })
+            .await;
+            let include_resolved = request_r.with_untracked(|r| r.include_resolved);
+            groups_w.update(|groups| {
+                if updated.resolved_at.is_some() && !include_resolved {
+                    groups.retain(|g| g.fingerprint != updated.fingerprint);
+                } else if let Some(group) = groups
+                    .iter_mut()
+                    .find(|g| g.fingerprint == updated.fingerprint)
+                {
+                    *group = updated;
+                }
+            });
+        });
+    };
+
+    let rows = move || {
+        groups_r.with(|groups| {
+            groups
+                .iter()
+                .cloned()
+                .map(|group| {
+                    let fingerprint = group.fingerprint.clone();
+                    let resolved = group.resolved_at.is_some();
+                    let toggle_resolved =
+                        move |_click: MouseEvent| set_resolved(fingerprint.clone(), !resolved);
+                    let samples = group
+                        .sample_trace_ids
+                        .iter()
+                        .map(|trace_id| {
+                            view! {cx,
+                                <a href=format!("{root_path}trace?trace_id={trace_id}")>{trace_id.to_string()}</a>
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    view! {cx,
+                        <tr class="row-container" class:errors-inbox__resolved=resolved>
+                            <td class="trace-table__cell">{group.service_name}</td>
+                            <td class="trace-table__cell">{group.span_name}</td>
+                            <td class="trace-table__cell errors-inbox__message">{group.message}</td>
+                            <td class="trace-table__cell errors-inbox__occurrences">{group.occurrences}</td>
+                            <td class="trace-table__cell">{format_date(group.first_seen)}</td>
+                            <td class="trace-table__cell">{format_date(group.last_seen)}</td>
+                            <td class="trace-table__cell errors-inbox__samples">{samples}</td>
+                            <td class="trace-table__cell">
+                                <button class="filter-builder__button" on:click=toggle_resolved>
+                                    {if resolved { "Reopen" } else { "Resolve" }}
+                                </button>
+                            </td>
+                        </tr>
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+    view! {cx,
+        <div class="main-grid">
+            <div class="main">
+                <table class="trace-table errors-inbox">
+                    <tr class="row-container">
+                        <th class="trace-table__cell">"Service"</th>
+                        <th class="trace-table__cell">"Span"</th>
+                        <th class="trace-table__cell">"Message"</th>
+                        <th class="trace-table__cell">"Occurrences"</th>
+                        <th class="trace-table__cell">"First Seen (local)"</th>
+                        <th class="trace-table__cell">"Last Seen (local)"</th>
+                        <th class="trace-table__cell">"Sample Traces"</th>
+                        <th class="trace-table__cell"></th>
+                    </tr>
+                    {rows}
+                </table>
+            </div>
+            <div class="search-panel">
+                <label class="search-panel__label">
+                    "Service Name:"
+                    <input on:input=service_name_changed class="search-panel__input" type="text" maxlength="50"/>
+                </label>
+                <label class="search-panel__label">
+                    "Show Resolved:"
+                    <input on:change=include_resolved_changed class="search-panel__input" type="checkbox"/>
+                </label>
+            </div>
+        </div>
+    }
+}
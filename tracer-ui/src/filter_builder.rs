@@ -0,0 +1,410 @@
+use api_structs::{AttributeFilter, Filter, SpanFilter, ValueComparison};
+use leptos::ev::{Event, MouseEvent};
+use leptos::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterKind {
+    Attribute,
+    Span,
+    /// A single span with a duration and optionally an attribute
+    SpanDuration,
+    Log,
+}
+
+impl FilterKind {
+    const ALL: [FilterKind; 4] = [
+        FilterKind::Attribute,
+        FilterKind::Span,
+        FilterKind::SpanDuration,
+        FilterKind::Log,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            FilterKind::Attribute => "Attribute",
+            FilterKind::Span => "Span",
+            FilterKind::SpanDuration => "Span duration",
+            FilterKind::Log => "Log",
+        }
+    }
+}
+
+/// One line of the filter builder, rows are ANDed together unless the row starts with OR,
+/// in which case it starts a new group: `a AND b OR c` means `(a AND b) OR c`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterRow {
+    pub id: u64,
+    pub or: bool,
+    pub negated: bool,
+    pub kind: FilterKind,
+    pub key: String,
+    pub comparison: ValueComparison,
+    pub value: String,
+    /// Only used by [FilterKind::SpanDuration], `value` is the span name and `key`, `comparison`
+    /// and `attribute_value` its optional attribute
+    pub min_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    pub attribute_value: String,
+}
+
+impl FilterRow {
+    pub fn new(id: u64) -> Self {
+        Self {
+            id,
+            or: false,
+            negated: false,
+            kind: FilterKind::Attribute,
+            key: "".to_string(),
+            comparison: ValueComparison::Contains,
+            value: "".to_string(),
+            min_duration_ms: None,
+            max_duration_ms: None,
+            attribute_value: "".to_string(),
+        }
+    }
+
+    /// Row building the filter, None for filters rows can't build, like nested groups
+    fn from_filter(id: u64, filter: &Filter) -> Option<Self> {
+        let mut row = Self::new(id);
+        match filter {
+            Filter::Not(filter) if !matches!(filter.as_ref(), Filter::Not(_)) => {
+                row = Self::from_filter(id, filter)?;
+                row.negated = true;
+            }
+            Filter::Attribute(attribute) => {
+                row.key = attribute.key.clone();
+                row.comparison = attribute.comparison;
+                row.value = attribute.value.clone();
+            }
+            Filter::SpanName(name) => {
+                row.kind = FilterKind::Span;
+                row.value = name.clone();
+            }
+            Filter::Span(span) if span.attributes.len() <= 1 => {
+                row.kind = FilterKind::SpanDuration;
+                row.value = span.name.clone();
+                row.min_duration_ms = span.min_duration.map(|ns| ns / 1_000_000);
+                row.max_duration_ms = span.max_duration.map(|ns| ns / 1_000_000);
+                if let Some(attribute) = span.attributes.first() {
+                    row.key = attribute.key.clone();
+                    row.comparison = attribute.comparison;
+                    row.attribute_value = attribute.value.clone();
+                }
+            }
+            Filter::EventName(words) => {
+                row.kind = FilterKind::Log;
+                row.value = words.clone();
+            }
+            _ => return None,
+        }
+        Some(row)
+    }
+
+    /// Key and value of the attribute the row compares, if any
+    pub fn attribute(&self) -> Option<(&str, &str)> {
+        match self.kind {
+            FilterKind::Attribute if !self.key.is_empty() => Some((&self.key, &self.value)),
+            FilterKind::SpanDuration if !self.key.is_empty() => {
+                Some((&self.key, &self.attribute_value))
+            }
+            _ => None,
+        }
+    }
+
+    /// None while the row is still incomplete
+    fn to_filter(&self) -> Option<Filter> {
+        let filter = match self.kind {
+            FilterKind::Attribute if !self.key.is_empty() => Filter::Attribute(AttributeFilter {
+                key: self.key.clone(),
+                comparison: self.comparison,
+                value: self.value.clone(),
+            }),
+            FilterKind::Span if !self.value.is_empty() => Filter::SpanName(self.value.clone()),
+            FilterKind::SpanDuration
+                if self.min_duration_ms.is_some() || self.max_duration_ms.is_some() =>
+            {
+                Filter::Span(SpanFilter {
+                    name: self.value.clone(),
+                    min_duration: self.min_duration_ms.map(|ms| ms * 1_000_000),
+                    max_duration: self.max_duration_ms.map(|ms| ms * 1_000_000),
+                    attributes: (!self.key.is_empty())
+                        .then(|| AttributeFilter {
+                            key: self.key.clone(),
+                            comparison: self.comparison,
+                            value: self.attribute_value.clone(),
+                        })
+                        .into_iter()
+                        .collect(),
+                })
+            }
+            FilterKind::Log if !self.value.is_empty() => Filter::EventName(self.value.clone()),
+            _ => return None,
+        };
+        if self.negated {
+            Some(Filter::Not(Box::new(filter)))
+        } else {
+            Some(filter)
+        }
+    }
+}
+
+pub fn filter_rows_to_filters(rows: &[FilterRow]) -> Vec<Filter> {
+    let mut groups: Vec<Vec<Filter>> = vec![vec![]];
+    for (idx, row) in rows.iter().enumerate() {
+        if row.or && idx > 0 {
+            groups.push(vec![]);
+        }
+        if let Some(filter) = row.to_filter() {
+            groups.last_mut().expect("at least one group").push(filter);
+        }
+    }
+    let mut groups: Vec<Vec<Filter>> = groups.into_iter().filter(|g| !g.is_empty()).collect();
+    if groups.len() <= 1 {
+        return groups.pop().unwrap_or_default();
+    }
+    vec![Filter::Or(
+        groups
+            .into_iter()
+            .map(|mut group| {
+                if group.len() == 1 {
+                    group.pop().expect("group to have one filter")
+                } else {
+                    Filter::And(group)
+                }
+            })
+            .collect(),
+    )]
+}
+
+/// Rows building these filters, the inverse of [filter_rows_to_filters].
+/// Filters it can't build rows for are skipped.
+pub fn filters_to_filter_rows(filters: &[Filter]) -> Vec<FilterRow> {
+    let groups: Vec<Vec<&Filter>> = match filters {
+        [Filter::Or(groups)] => groups
+            .iter()
+            .map(|group| match group {
+                Filter::And(filters) => filters.iter().collect(),
+                filter => vec![filter],
+            })
+            .collect(),
+        filters => vec![filters.iter().collect()],
+    };
+    let mut rows: Vec<FilterRow> = vec![];
+    for group in groups {
+        let mut starts_group = !rows.is_empty();
+        for filter in group {
+            let Some(mut row) = FilterRow::from_filter(rows.len() as u64, filter) else {
+                log!("Skipping filter without a row: {:?}", filter);
+                continue;
+            };
+            row.or = starts_group;
+            starts_group = false;
+            rows.push(row);
+        }
+    }
+    if rows.is_empty() {
+        rows.push(FilterRow::new(0));
+    }
+    rows
+}
+
+#[component]
+pub fn FilterBuilder(
+    cx: Scope,
+    rows: Signal<Vec<FilterRow>>,
+    on_change: Box<dyn Fn(Vec<FilterRow>)>,
+) -> impl IntoView {
+    let on_change = store_value(cx, on_change);
+    let update_row = move |id: u64, update: &dyn Fn(&mut FilterRow)| {
+        let mut new_rows = rows.get_untracked();
+        if let Some(row) = new_rows.iter_mut().find(|r| r.id == id) {
+            update(row);
+        }
+        on_change.with_value(|on_change| on_change(new_rows));
+    };
+    let add_row = move |_click: MouseEvent| {
+        let mut new_rows = rows.get_untracked();
+        let next_id = new_rows.iter().map(|r| r.id + 1).max().unwrap_or(0);
+        new_rows.push(FilterRow::new(next_id));
+        on_change.with_value(|on_change| on_change(new_rows));
+    };
+    let row_view = move |cx: Scope, row: FilterRow| {
+        let id = row.id;
+        let current = create_memo(cx, move |_| {
+            rows.with(|rows| rows.iter().find(|r| r.id == id).cloned())
+                .unwrap_or_else(|| FilterRow::new(id))
+        });
+        let is_first = Signal::derive(cx, move || {
+            rows.with(|rows| rows.first().map(|r| r.id) == Some(id))
+        });
+        let kind = create_memo(cx, move |_| current.with(|r| r.kind));
+        let combinator_changed =
+            move |ev: Event| update_row(id, &|r| r.or = event_target_value(&ev) == "OR");
+        let negated_changed = move |_click: MouseEvent| update_row(id, &|r| r.negated = !r.negated);
+        let kind_changed = move |ev: Event| {
+            let val = event_target_value(&ev);
+            if let Some(kind) = FilterKind::ALL.into_iter().find(|k| k.label() == val) {
+                update_row(id, &|r| r.kind = kind);
+            } else {
+                log!("Invalid Filter Kind");
+            }
+        };
+        let key_changed = move |ev: Event| update_row(id, &|r| r.key = event_target_value(&ev));
+        let comparison_changed = move |ev: Event| {
+            let val = event_target_value(&ev);
+            if let Some(comparison) = ValueComparison::ALL
+                .into_iter()
+                .find(|comparison| comparison.symbol() == val)
+            {
+                update_row(id, &|r| r.comparison = comparison);
+            } else {
+                log!("Invalid Value Comparison");
+            }
+        };
+        let value_changed = move |ev: Event| update_row(id, &|r| r.value = event_target_value(&ev));
+        let min_duration_changed = move |ev: Event| {
+            update_row(id, &|r| {
+                r.min_duration_ms = event_target_value(&ev).parse().ok()
+            })
+        };
+        let max_duration_changed = move |ev: Event| {
+            update_row(id, &|r| {
+                r.max_duration_ms = event_target_value(&ev).parse().ok()
+            })
+        };
+        let attribute_value_changed =
+            move |ev: Event| update_row(id, &|r| r.attribute_value = event_target_value(&ev));
+        let comparison_options = move || {
+            ValueComparison::ALL
+                .into_iter()
+                .map(|comparison| {
+                    view! {cx,
+                        <option
+                            value=comparison.symbol()
+                            selected=move || current.with(|r| r.comparison == comparison)
+                        >
+                            {comparison.symbol()}
+                        </option>
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let remove = move |_click: MouseEvent| {
+            let new_rows = rows
+                .get_untracked()
+                .into_iter()
+                .filter(|r| r.id != id)
+                .collect();
+            on_change.with_value(|on_change| on_change(new_rows));
+        };
+        view! {cx,
+            <div class="filter-builder__row">
+                <div class="search-panel__input-flex-container">
+                    <select on:change=combinator_changed class="search-panel__input"
+                        style:visibility=move || if is_first.get() { "hidden" } else { "visible" }
+                    >
+                        <option value="AND" selected=move || !current.with(|r| r.or)>"AND"</option>
+                        <option value="OR" selected=move || current.with(|r| r.or)>"OR"</option>
+                    </select>
+                    <label class="filter-builder__not">
+                        "NOT"
+                        <input type="checkbox" prop:checked=move || current.with(|r| r.negated) on:click=negated_changed/>
+                    </label>
+                    <select on:change=kind_changed class="search-panel__input">
+                        {
+                            FilterKind::ALL.into_iter().map(|k|{
+                                view!{cx,
+                                    <option value=k.label() selected=move || kind.get() == k>{k.label()}</option>
+                                }
+                            }).collect::<Vec<_>>()
+                        }
+                    </select>
+                    <button class="filter-builder__button" on:click=remove>"✕"</button>
+                </div>
+                <div class="search-panel__input-flex-container">
+                    {move || match kind.get() {
+                        FilterKind::Attribute => view!{cx,
+                            <>
+                                <input on:input=key_changed
+                                    prop:value=move || current.with(|r| r.key.clone())
+                                    placeholder="Key" list="key-list"
+                                    class="search-panel__input" type="text" maxlength="50" size="10"
+                                />
+                                <select on:change=comparison_changed class="search-panel__input">
+                                    {comparison_options}
+                                </select>
+                                <input on:input=value_changed
+                                    prop:value=move || current.with(|r| r.value.clone())
+                                    placeholder="Value" list="value-list"
+                                    class="search-panel__input" type="text" maxlength="50" size="10"
+                                />
+                            </>
+                        },
+                        FilterKind::Span => view!{cx,
+                            <>
+                                <input on:input=value_changed
+                                    prop:value=move || current.with(|r| r.value.clone())
+                                    placeholder="Span name" list="span-list"
+                                    class="search-panel__input" type="text" maxlength="50" size="20"
+                                />
+                            </>
+                        },
+                        FilterKind::SpanDuration => view!{cx,
+                            <>
+                                <div class="search-panel__input-flex-container">
+                                    <input on:input=value_changed
+                                        prop:value=move || current.with(|r| r.value.clone())
+                                        placeholder="Any span" list="span-list"
+                                        class="search-panel__input" type="text" maxlength="50" size="10"
+                                    />
+                                    <p>"took"</p>
+                                    <input on:input=min_duration_changed
+                                        prop:value=move || current.with(|r| r.min_duration_ms.map(|ms| ms.to_string()).unwrap_or_default())
+                                        placeholder="min" class="search-panel__input" type="text" maxlength="7" size="3"
+                                    />
+                                    <p>"to"</p>
+                                    <input on:input=max_duration_changed
+                                        prop:value=move || current.with(|r| r.max_duration_ms.map(|ms| ms.to_string()).unwrap_or_default())
+                                        placeholder="max" class="search-panel__input" type="text" maxlength="7" size="3"
+                                    />
+                                    <p>"ms"</p>
+                                </div>
+                                <div class="search-panel__input-flex-container">
+                                    <input on:input=key_changed
+                                        prop:value=move || current.with(|r| r.key.clone())
+                                        placeholder="With key" list="key-list"
+                                        class="search-panel__input" type="text" maxlength="50" size="10"
+                                    />
+                                    <select on:change=comparison_changed class="search-panel__input">
+                                        {comparison_options}
+                                    </select>
+                                    <input on:input=attribute_value_changed
+                                        prop:value=move || current.with(|r| r.attribute_value.clone())
+                                        placeholder="Value" list="value-list"
+                                        class="search-panel__input" type="text" maxlength="50" size="10"
+                                    />
+                                </div>
+                            </>
+                        },
+                        FilterKind::Log => view!{cx,
+                            <>
+                                <input on:input=value_changed
+                                    prop:value=move || current.with(|r| r.value.clone())
+                                    placeholder="Log words, \"a phrase\", prefix*" list="event-name-list"
+                                    class="search-panel__input" type="text" maxlength="50" size="20"
+                                />
+                            </>
+                        },
+                    }}
+                </div>
+            </div>
+        }
+    };
+    view! {cx,
+        <div class="search-panel__label">
+            "Filters:"
+            <For each=move || rows.get() key=|row| row.id view=row_view/>
+            <button class="filter-builder__button" on:click=add_row>"Add filter"</button>
+        </div>
+    }
+}
@@ -1,10 +1,21 @@
+use crate::filter_builder::{
+    filter_rows_to_filters, filters_to_filter_rows, FilterBuilder, FilterRow,
+};
+use crate::heatmap::{HeatmapSelection, TraceHeatmap};
+use crate::saved_searches::SavedSearches;
 use crate::API_SERVER_URL_NO_TRAILING_SLASH;
 use chrono::{Duration, NaiveDateTime};
 use js_sys::Date;
 use leptos::ev::{Event, MouseEvent};
 use leptos::*;
 
-use api_structs::{ApiTraceGridRow, KeySpans, KeyValue, SearchFor};
+use api_structs::{
+    ApiTraceGrid, ApiTraceGridRow, AttributeValuesRequest, Filter, GridSearchState, KeySpans,
+    KeyValue, OrderBy, SearchFor, TextMatch, TimeRange, TraceGridCursor, HIGHLIGHT_END,
+    HIGHLIGHT_START,
+};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
 
 #[derive(PartialEq, Clone, Debug)]
 pub struct TraceGridRow {
@@ -17,74 +28,158 @@ pub struct TraceGridRow {
     sample_log: Option<String>,
     key_value: Option<KeyValue>,
     span: Option<String>,
+    span_id: Option<u64>,
     timestamp: u64,
+    imported: bool,
+}
+
+impl From<ApiTraceGridRow> for TraceGridRow {
+    fn from(e: ApiTraceGridRow) -> Self {
+        TraceGridRow {
+            id: e.id,
+            duration: e.duration_ns,
+            service_name: e.service_name,
+            has_errors: e.has_errors,
+            warning_count: e.warning_count,
+            top_level_span_name: e.top_level_span_name,
+            sample_log: e.event,
+            key_value: if let (Some(key), Some(value)) = (e.key, e.value) {
+                Some(KeyValue {
+                    key,
+                    user_generated: true,
+                    value,
+                })
+            } else {
+                None
+            },
+            span: e.span,
+            span_id: e.span_id,
+            timestamp: e.timestamp,
+            imported: e.imported,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct UserSearchInput {
     search_for: SearchFor,
+    filter_rows: Vec<FilterRow>,
+    time_range: TimeRange,
+}
+
+/// Relative time ranges offered besides picking the dates
+const RELATIVE_TIME_RANGES: [(&str, u64); 5] = [
+    ("Last 15 minutes", 15 * 60 * 1_000_000_000),
+    ("Last hour", 3600 * 1_000_000_000),
+    ("Last 6 hours", 6 * 3600 * 1_000_000_000),
+    ("Last day", 24 * 3600 * 1_000_000_000),
+    ("Last 7 days", 7 * 24 * 3600 * 1_000_000_000),
+];
+const CUSTOM_TIME_RANGE_LABEL: &str = "Custom";
+
+fn now_unix_nanos() -> u64 {
+    Date::now().round() as u64 * 1_000_000
+}
+
+impl UserSearchInput {
+    fn from_state(state: GridSearchState) -> Self {
+        let mut search_for = state.search_for;
+        (search_for.from_date_unix, search_for.to_date_unix) =
+            state.time_range.resolve(now_unix_nanos());
+        search_for.cursor = None;
+        Self {
+            filter_rows: filters_to_filter_rows(&search_for.filters),
+            search_for,
+            time_range: state.time_range,
+        }
+    }
+
+    fn state(&self) -> GridSearchState {
+        GridSearchState {
+            search_for: SearchFor {
+                cursor: None,
+                ..self.search_for.clone()
+            },
+            time_range: self.time_range,
+        }
+    }
+
+    /// Dates picked by hand, they stay the same when the search is reopened
+    fn set_dates(&mut self, from_date_unix: u64, to_date_unix: u64) {
+        self.search_for.from_date_unix = from_date_unix;
+        self.search_for.to_date_unix = to_date_unix;
+        self.time_range = TimeRange::Between {
+            from_date_unix,
+            to_date_unix,
+        };
+    }
+}
+
+/// Link reopening the trace grid with this search
+pub fn grid_search_url(root_path: &str, state: &GridSearchState) -> String {
+    let json = serde_json::to_string(state).expect("search state to serialize");
+    format!("{root_path}?search={}", js_sys::encode_uri_component(&json))
 }
 
 impl Default for UserSearchInput {
     fn default() -> Self {
         let now = NaiveDateTime::from_timestamp_millis(Date::now().round() as i64).unwrap();
         Self {
+            time_range: TimeRange::Last(3600 * 1_000_000_000),
             search_for: SearchFor {
                 service_name: "".to_string(),
+                service_name_match: TextMatch::Exact,
                 top_level_span: "".to_string(),
-                span: "".to_string(),
+                top_level_span_match: TextMatch::Exact,
                 min_duration: 1000_000,
                 max_duration: None,
                 min_warns: 0,
-                key: "".to_string(),
-                value: "".to_string(),
-                event_name: "".to_string(),
+                filters: vec![],
+                span_name_match: TextMatch::Exact,
+                event_match: None,
+                attribute_value_match: TextMatch::Contains,
                 from_date_unix: u64::try_from((now - Duration::hours(1)).timestamp_nanos())
                     .expect("timestamp to fit u64"),
                 to_date_unix: u64::try_from((now + Duration::days(1)).timestamp_nanos())
                     .expect("timestamp to fit u64"),
                 only_errors: false,
+                order_by: OrderBy::DateDesc,
+                cursor: None,
             },
+            filter_rows: vec![FilterRow::new(0)],
         }
     }
 }
 
-async fn get_grid_data(search_data: SearchFor, api_response_w: WriteSignal<Vec<TraceGridRow>>) {
+/// Rows of all pages loaded so far
+#[derive(Debug, Clone, Default)]
+struct GridPages {
+    rows: Vec<TraceGridRow>,
+    total_count: Option<u64>,
+    next_cursor: Option<TraceGridCursor>,
+}
+
+async fn fetch_grid_page(search_data: &SearchFor) -> ApiTraceGrid {
     let url = format!("{}/api/traces-grid", API_SERVER_URL_NO_TRAILING_SLASH);
     log!("URL = {}", url);
-    let resp: Vec<ApiTraceGridRow> = gloo_net::http::Request::post(&url)
-        .json(&search_data)
+    gloo_net::http::Request::post(&url)
+        .json(search_data)
         .unwrap()
         .send()
         .await
         .unwrap()
         .json()
         .await
-        .unwrap();
-    let trace_grid: Vec<TraceGridRow> = resp
-        .into_iter()
-        .map(|e| TraceGridRow {
-            id: e.id,
-            duration: e.duration_ns,
-            service_name: e.service_name,
-            has_errors: e.has_errors,
-            warning_count: e.warning_count,
-            top_level_span_name: e.top_level_span_name,
-            sample_log: e.event,
-            key_value: if let (Some(key), Some(value)) = (e.key, e.value) {
-                Some(KeyValue {
-                    key,
-                    user_generated: true,
-                    value,
-                })
-            } else {
-                None
-            },
-            span: e.span,
-            timestamp: e.timestamp,
-        })
-        .collect();
-    api_response_w.set(trace_grid);
+        .unwrap()
+}
+
+async fn get_grid_data(search_data: SearchFor, api_response_w: WriteSignal<GridPages>) {
+    let resp = fetch_grid_page(&search_data).await;
+    api_response_w.set(GridPages {
+        rows: resp.rows.into_iter().map(TraceGridRow::from).collect(),
+        total_count: resp.total_count,
+        next_cursor: resp.next_cursor,
+    });
 }
 
 async fn get_autocomplete_data(search_data: SearchFor, api_response_w: WriteSignal<KeySpans>) {
@@ -101,6 +196,86 @@ async fn get_autocomplete_data(search_data: SearchFor, api_response_w: WriteSign
     api_response_w.set(resp);
 }
 
+async fn get_attribute_values(
+    request: Option<AttributeValuesRequest>,
+    api_response_w: WriteSignal<Vec<String>>,
+) {
+    let Some(request) = request else {
+        api_response_w.set(vec![]);
+        return;
+    };
+    let url = format!(
+        "{}/api/autocomplete-data/values",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    );
+    let resp: Vec<String> = gloo_net::http::Request::post(&url)
+        .json(&request)
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    api_response_w.set(resp);
+}
+
+/// Open live tail connection, closed when dropped
+struct LiveTail {
+    event_source: web_sys::EventSource,
+    _on_traces: Closure<dyn Fn(web_sys::MessageEvent)>,
+}
+
+impl Drop for LiveTail {
+    fn drop(&mut self) {
+        self.event_source.close();
+    }
+}
+
+/// Prepends the newly stored traces matching the search to the grid as they come in
+fn start_live_tail(search_for: &SearchFor, api_response_w: WriteSignal<GridPages>) -> LiveTail {
+    let search = serde_json::to_string(search_for).expect("search to serialize");
+    let url = format!(
+        "{}/api/live-tail?search={}",
+        API_SERVER_URL_NO_TRAILING_SLASH,
+        js_sys::encode_uri_component(&search)
+    );
+    let event_source = web_sys::EventSource::new(&url).unwrap();
+    let on_traces =
+        Closure::<dyn Fn(web_sys::MessageEvent)>::new(move |event: web_sys::MessageEvent| {
+            let Some(data) = event.data().as_string() else {
+                return;
+            };
+            let rows: Vec<ApiTraceGridRow> = match serde_json::from_str(&data) {
+                Ok(rows) => rows,
+                Err(e) => {
+                    log!("Invalid live tail rows: {:?}", e);
+                    return;
+                }
+            };
+            api_response_w.update(|r| {
+                // a trace stored while the first page loaded can be in both
+                let mut new_rows: Vec<TraceGridRow> = rows
+                    .into_iter()
+                    .map(TraceGridRow::from)
+                    .filter(|new_row| !r.rows.iter().any(|row| row.id == new_row.id))
+                    .collect();
+                if let Some(total_count) = &mut r.total_count {
+                    *total_count += new_rows.len() as u64;
+                }
+                new_rows.append(&mut r.rows);
+                r.rows = new_rows;
+            });
+        });
+    event_source
+        .add_event_listener_with_callback("traces", on_traces.as_ref().unchecked_ref())
+        .unwrap();
+    LiveTail {
+        event_source,
+        _on_traces: on_traces,
+    }
+}
+
 fn utc_to_local_date(utc: NaiveDateTime, offset_minutes: i64) -> NaiveDateTime {
     utc - Duration::minutes(offset_minutes)
 }
@@ -108,14 +283,32 @@ fn local_date_to_utc(local: NaiveDateTime, offset_minutes: i64) -> NaiveDateTime
     local + Duration::minutes(offset_minutes)
 }
 
+pub fn unix_nanos_to_local_date(timestamp_nanos: u64) -> NaiveDateTime {
+    let offset_minutes = js_sys::Date::new_0().get_timezone_offset() as i64;
+    let timestamp = i64::try_from(timestamp_nanos).expect("timestamp to fit i64");
+    utc_to_local_date(
+        NaiveDateTime::from_timestamp_opt(
+            timestamp / 1_000_000_000,
+            (timestamp % 1_000_000_000) as u32,
+        )
+        .unwrap(),
+        offset_minutes,
+    )
+}
+
+pub fn local_date_to_unix_nanos(local: NaiveDateTime) -> Option<u64> {
+    let offset_minutes = js_sys::Date::new_0().get_timezone_offset() as i64;
+    u64::try_from(local_date_to_utc(local, offset_minutes).timestamp_nanos()).ok()
+}
+
 #[derive(Clone)]
-enum RequestState {
+pub enum RequestState {
     Idle,
     Running,
     RunningBehind,
 }
 
-fn debounced_api<S, T, Fu>(
+pub fn debounced_api<S, T, Fu>(
     cx: Scope,
     source: impl Fn() -> S + 'static,
     fetcher: impl Fn(S) -> Fu + 'static,
@@ -159,8 +352,38 @@ where
 
 #[component]
 pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
-    let (user_search_input_r, user_search_input_w) = create_signal(cx, UserSearchInput::default());
-    let (api_response_r, api_response_w) = create_signal(cx, Vec::<TraceGridRow>::new());
+    let initial_search_input = leptos_router::use_query_map(cx)
+        .get_untracked()
+        .get("search")
+        .and_then(|json| match serde_json::from_str::<GridSearchState>(json) {
+            Ok(state) => Some(UserSearchInput::from_state(state)),
+            Err(e) => {
+                log!("Invalid search in the URL: {:?}", e);
+                None
+            }
+        })
+        .unwrap_or_default();
+    let (user_search_input_r, user_search_input_w) = create_signal(cx, initial_search_input);
+    let search_state = create_memo(cx, move |_| {
+        user_search_input_r.with(UserSearchInput::state)
+    });
+    // keeps the URL reopening the same grid, so it can be shared
+    create_effect(cx, {
+        let root_path = root_path.clone();
+        move |_| {
+            let url = grid_search_url(&root_path, &search_state.get());
+            if let Err(e) = window().history().and_then(|history| {
+                history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url))
+            }) {
+                log!("Failed to update the URL: {:?}", e);
+            }
+        }
+    });
+    let open_saved_search = move |state: GridSearchState| {
+        user_search_input_w.set(UserSearchInput::from_state(state));
+    };
+    let (api_response_r, api_response_w) = create_signal(cx, GridPages::default());
+    let (loading_more_r, loading_more_w) = create_signal(cx, false);
     let (api_autocomplete_r, api_autocomplete_w) = create_signal(cx, KeySpans::default());
     let search_data: Memo<SearchFor> = create_memo(cx, move |_prev: Option<&SearchFor>| {
         user_search_input_r.with(|v| v.search_for.clone())
@@ -175,14 +398,99 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
         move || search_data.get(),
         move |search_for| get_autocomplete_data(search_for, api_autocomplete_w),
     );
+    // values are suggested for the attribute of the last edited filter row
+    let (value_row_id_r, value_row_id_w) = create_signal(cx, None::<u64>);
+    let (value_suggestions_r, value_suggestions_w) = create_signal(cx, Vec::<String>::new());
+    let values_request = create_memo(cx, move |_prev| {
+        let row_id = value_row_id_r.get()?;
+        user_search_input_r.with(|input| {
+            let row = input.filter_rows.iter().find(|r| r.id == row_id)?;
+            let (key, value) = row.attribute()?;
+            // the edited row would only suggest the value already typed
+            let other_rows: Vec<FilterRow> = input
+                .filter_rows
+                .iter()
+                .filter(|r| r.id != row_id)
+                .cloned()
+                .collect();
+            let mut search_for = input.search_for.clone();
+            search_for.filters = filter_rows_to_filters(&other_rows);
+            search_for.cursor = None;
+            Some(AttributeValuesRequest {
+                search_for,
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+        })
+    });
+    let values_request_state = debounced_api(
+        cx,
+        move || values_request.get(),
+        move |request| get_attribute_values(request, value_suggestions_w),
+    );
     let request_in_progress = Signal::derive(cx, move || {
-        match (grid_request_state.get(), autocomplete_request_state.get()) {
-            (RequestState::Idle, RequestState::Idle) => false,
-            (_, _) => true,
+        match (
+            grid_request_state.get(),
+            autocomplete_request_state.get(),
+            values_request_state.get(),
+        ) {
+            (RequestState::Idle, RequestState::Idle, RequestState::Idle) => false,
+            (_, _, _) => true,
         }
     });
+    let heatmap_search = create_memo(cx, move |_prev: Option<&SearchFor>| {
+        // the heatmap counts every matching trace, the grid sort doesn't change it
+        let mut search_for = search_data.get();
+        search_for.order_by = OrderBy::default();
+        search_for
+    });
+    let heatmap_selected = move |selection: HeatmapSelection| {
+        log!("Heatmap selection: {:?}", selection);
+        user_search_input_w.update(|v| {
+            v.set_dates(selection.from_date_unix, selection.to_date_unix);
+            v.search_for.min_duration = selection.min_duration;
+            v.search_for.max_duration = Some(selection.max_duration);
+        });
+    };
     let api_response_with_search_data = Signal::derive(cx, move || {
-        (api_response_r.get(), user_search_input_r.get_untracked())
+        (
+            api_response_r.with(|r| r.rows.clone()),
+            user_search_input_r.get_untracked(),
+        )
+    });
+    let load_more = move || {
+        if loading_more_r.get_untracked() || request_in_progress.get_untracked() {
+            return;
+        }
+        let Some(cursor) = api_response_r.with_untracked(|r| r.next_cursor) else {
+            return;
+        };
+        let first_page_search = search_data.get_untracked();
+        let mut search_for = first_page_search.clone();
+        search_for.cursor = Some(cursor);
+        loading_more_w.set(true);
+        spawn_local(async move {
+            let resp = fetch_grid_page(&search_for).await;
+            // the search might have changed while the page was loading
+            if search_data.with_untracked(|s| *s == first_page_search) {
+                api_response_w.update(|r| {
+                    if r.next_cursor == Some(cursor) {
+                        r.rows.extend(resp.rows.into_iter().map(TraceGridRow::from));
+                        r.next_cursor = resp.next_cursor;
+                    }
+                });
+            }
+            loading_more_w.set(false);
+        });
+    };
+
+    let (live_r, live_w) = create_signal(cx, false);
+    let live_tail: StoredValue<Option<LiveTail>> = store_value(cx, None);
+    create_effect(cx, move |_| {
+        let live = live_r.get();
+        let search_for = search_data.get();
+        // replacing the previous one closes it
+        live_tail.set_value(live.then(|| start_live_tail(&search_for, api_response_w)));
     });
 
     let service_name_changed = move |ev: Event| {
@@ -197,25 +505,33 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
         user_search_input_w.update(|v| v.search_for.top_level_span = val);
     };
 
-    let span_name_changed = move |ev: Event| {
-        let val = event_target_value(&ev);
-        log!("Span changed to: {}", val);
-        user_search_input_w.update(|v| v.search_for.span = val);
-    };
-    let span_key_changed = move |ev: Event| {
-        let val = event_target_value(&ev);
-        log!("Span Key changed to: {}", val);
-        user_search_input_w.update(|v| v.search_for.key = val);
-    };
-    let span_value_changed = move |ev: Event| {
-        let val = event_target_value(&ev);
-        log!("Span Value changed to: {}", val);
-        user_search_input_w.update(|v| v.search_for.value = val);
+    let filter_rows_changed = move |filter_rows: Vec<FilterRow>| {
+        log!("Filters changed to: {:?}", filter_rows);
+        let edited_row = user_search_input_r.with_untracked(|v| {
+            filter_rows
+                .iter()
+                .find(|row| !v.filter_rows.contains(row))
+                .map(|row| row.id)
+        });
+        if edited_row.is_some() {
+            value_row_id_w.set(edited_row);
+        }
+        user_search_input_w.update(|v| {
+            v.search_for.filters = filter_rows_to_filters(&filter_rows);
+            v.filter_rows = filter_rows;
+        });
     };
-    let event_name_changed = move |ev: Event| {
+    let filter_rows = Signal::derive(cx, move || {
+        user_search_input_r.with(|r| r.filter_rows.clone())
+    });
+    let order_by_changed = move |ev: Event| {
         let val = event_target_value(&ev);
-        log!("Event Name changed to: {}", val);
-        user_search_input_w.update(|v| v.search_for.event_name = val);
+        log!("Order By changed to: {}", val);
+        if let Some(order_by) = OrderBy::ALL.into_iter().find(|o| o.label() == val) {
+            user_search_input_w.update(|v| v.search_for.order_by = order_by);
+        } else {
+            log!("Invalid Order By");
+        }
     };
     let min_duration_changed = move |ev: Event| {
         let val = event_target_value(&ev);
@@ -260,7 +576,8 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
             if let Ok(timestamp_nanos) =
                 u64::try_from(local_date_to_utc(new_datetime, offset_minutes).timestamp_nanos())
             {
-                v.search_for.from_date_unix = timestamp_nanos;
+                let to_date_unix = v.search_for.to_date_unix;
+                v.set_dates(timestamp_nanos, to_date_unix);
             } else {
                 log!("From date out of bounds!")
             }
@@ -272,7 +589,8 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
             if let Ok(timestamp_nanos) =
                 u64::try_from(local_date_to_utc(new_datetime, offset_minutes).timestamp_nanos())
             {
-                v.search_for.to_date_unix = timestamp_nanos;
+                let from_date_unix = v.search_for.from_date_unix;
+                v.set_dates(from_date_unix, timestamp_nanos);
             } else {
                 log!("From date out of bounds!")
             }
@@ -308,6 +626,26 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
             )
         })
     });
+    let time_range_changed = move |ev: Event| {
+        let val = event_target_value(&ev);
+        if let Some((_label, duration)) = RELATIVE_TIME_RANGES
+            .into_iter()
+            .find(|(label, _duration)| *label == val)
+        {
+            user_search_input_w.update(|v| {
+                v.time_range = TimeRange::Last(duration);
+                (v.search_for.from_date_unix, v.search_for.to_date_unix) =
+                    v.time_range.resolve(now_unix_nanos());
+            });
+        } else {
+            // the dates stay, but are no longer moved along when reopened
+            user_search_input_w.update(|v| {
+                let (from_date_unix, to_date_unix) =
+                    (v.search_for.from_date_unix, v.search_for.to_date_unix);
+                v.set_dates(from_date_unix, to_date_unix);
+            });
+        }
+    };
     let only_errors_checkbox_ref = create_node_ref::<leptos::html::Input>(cx);
     let only_errors_changed = move |_click: MouseEvent| {
         user_search_input_w.update(|v| {
@@ -320,10 +658,11 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
     };
 
     let tracer_counter = move || {
-        let number_traces = api_response_r.get().len();
+        let (number_traces, total_count) =
+            api_response_r.with(|r| (r.rows.len(), r.total_count.unwrap_or_default()));
         let request_in_progress = request_in_progress.get();
-        let text = if number_traces >= 100 {
-            "99+ traces".to_string()
+        let text = if (number_traces as u64) < total_count {
+            format!("{} of {} traces", number_traces, total_count)
         } else {
             format!("{} traces", number_traces)
         };
@@ -337,10 +676,42 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
     view! { cx,
         <div class="main-grid">
             <div class="main">
-                <TraceTable rows={api_response_with_search_data} root_path=root_path/>
+                <TraceHeatmap search=heatmap_search.into() on_select=Box::new(heatmap_selected)/>
+                <TraceTable rows={api_response_with_search_data} root_path=root_path.clone() load_more=Box::new(load_more)/>
+                {move || api_response_r.with(|r| r.next_cursor.is_some()).then(|| view! {cx,
+                    <button class="trace-table__load-more" on:click=move |_| load_more()
+                        disabled=move || loading_more_r.get()
+                    >
+                        {move || if loading_more_r.get() { "Loading..." } else { "Load more" }}
+                    </button>
+                })}
             </div>
             <div class="search-panel">
                 <h1 class="traces-counter">{tracer_counter}</h1>
+                <SavedSearches root_path=root_path state=search_state.into() on_open=Box::new(open_saved_search)/>
+                <label class="search-panel__label">
+                    "Time Range:"
+                    <select on:change=time_range_changed class="search-panel__input">
+                        {
+                            RELATIVE_TIME_RANGES.into_iter().map(|(label, duration)|{
+                                view!{cx,
+                                    <option
+                                        value=label
+                                        selected=move || user_search_input_r.with(|r| r.time_range == TimeRange::Last(duration))
+                                    >
+                                        {label}
+                                    </option>
+                                }
+                            }).collect::<Vec<_>>()
+                        }
+                        <option
+                            value=CUSTOM_TIME_RANGE_LABEL
+                            selected=move || user_search_input_r.with(|r| matches!(r.time_range, TimeRange::Between{..}))
+                        >
+                            {CUSTOM_TIME_RANGE_LABEL}
+                        </option>
+                    </select>
+                </label>
                 <DatePicker
                     label="From (local):".to_string()
                     date_to_display=current_from_datetime
@@ -351,14 +722,39 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
                     date_to_display=current_to_datetime
                     on_change=Box::new(to_changed)
                 />
+                <label class="search-panel__label">
+                    "Sort:"
+                    <select on:change=order_by_changed class="search-panel__input">
+                        {
+                            OrderBy::ALL.into_iter().map(|order_by|{
+                                view!{cx,
+                                    <option
+                                        value=order_by.label()
+                                        selected=move || user_search_input_r.with(|r| r.search_for.order_by == order_by)
+                                    >
+                                        {order_by.label()}
+                                    </option>
+                                }
+                            }).collect::<Vec<_>>()
+                        }
+                    </select>
+                </label>
                 <label class="search-panel__label">
                     "Service Name:"
-                    <input on:input=service_name_changed
-                        prop:value={move || user_search_input_r.with(|r| r.search_for.service_name.to_string())}
-                        class="search-panel__input" type="text"  minlength="3" maxlength="50" size="20"
-                        list="service-name-list"
+                    <div class="search-panel__input-flex-container">
+                        <input on:input=service_name_changed
+                            prop:value={move || user_search_input_r.with(|r| r.search_for.service_name.to_string())}
+                            class="search-panel__input" type="text"  minlength="3" maxlength="50" size="20"
+                            list="service-name-list"
 
-                    />
+                        />
+                        {text_match_select(
+                            cx,
+                            move || user_search_input_r.with(|r| Some(r.search_for.service_name_match)),
+                            false,
+                            move |text_match| user_search_input_w.update(|v| v.search_for.service_name_match = text_match.unwrap_or_default()),
+                        )}
+                    </div>
                 </label>
                 {
                     move || {
@@ -377,12 +773,20 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
                 }
                 <label class="search-panel__label">
                     "Top Level Span:"
-                    <input on:input=top_level_span_changed
-                        prop:value={move || user_search_input_r.with(|r| r.search_for.top_level_span.to_string())}
-                        class="search-panel__input" type="text"  minlength="3" maxlength="50" size="20"
-                        list="top-level-span-list"
+                    <div class="search-panel__input-flex-container">
+                        <input on:input=top_level_span_changed
+                            prop:value={move || user_search_input_r.with(|r| r.search_for.top_level_span.to_string())}
+                            class="search-panel__input" type="text"  minlength="3" maxlength="50" size="20"
+                            list="top-level-span-list"
 
-                    />
+                        />
+                        {text_match_select(
+                            cx,
+                            move || user_search_input_r.with(|r| Some(r.search_for.top_level_span_match)),
+                            false,
+                            move |text_match| user_search_input_w.update(|v| v.search_for.top_level_span_match = text_match.unwrap_or_default()),
+                        )}
+                    </div>
                 </label>
                 {
                     move || {
@@ -419,11 +823,19 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
                 </label>
                 <label class="search-panel__label">
                     "Errors Only:"
-                    <input class="search-panel__input search-panel__input__inline" type="checkbox" checked=false
+                    <input class="search-panel__input search-panel__input__inline" type="checkbox"
                         _ref=only_errors_checkbox_ref
+                        prop:checked=move || user_search_input_r.with(|r| r.search_for.only_errors)
                         on:click=only_errors_changed
                     />
                 </label>
+                <label class="search-panel__label" title="Prepend new traces matching the search as they are stored">
+                    "Live:"
+                    <input class="search-panel__input search-panel__input__inline" type="checkbox"
+                        prop:checked=move || live_r.get()
+                        on:change=move |ev| live_w.set(event_target_checked(&ev))
+                    />
+                </label>
                 <label class="search-panel__label">
                     "Min Warns:"
                     <input on:input=min_warns_changed
@@ -431,14 +843,33 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
                             class="search-panel__input search-panel__input__inline" type="text" maxlength="5" size="2"
                         />
                 </label>
-                <label class="search-panel__label">
-                    "Span:"
-                    <input on:input=span_name_changed
-                        prop:value={move || user_search_input_r.with(|r| r.search_for.span.to_string())}
-                        list="span-list"
-                        class="search-panel__input" type="text"  minlength="3" maxlength="50" size="20"
-                    />
-                </label>
+                <FilterBuilder rows=filter_rows on_change=Box::new(filter_rows_changed)/>
+                <div class="search-panel__label">
+                    "Filters Match:"
+                    <div class="search-panel__input-flex-container">
+                        <p>"Span"</p>
+                        {text_match_select(
+                            cx,
+                            move || user_search_input_r.with(|r| Some(r.search_for.span_name_match)),
+                            false,
+                            move |text_match| user_search_input_w.update(|v| v.search_for.span_name_match = text_match.unwrap_or_default()),
+                        )}
+                        <p>"Log"</p>
+                        {text_match_select(
+                            cx,
+                            move || user_search_input_r.with(|r| r.search_for.event_match),
+                            true,
+                            move |text_match| user_search_input_w.update(|v| v.search_for.event_match = text_match),
+                        )}
+                        <p>"Value"</p>
+                        {text_match_select(
+                            cx,
+                            move || user_search_input_r.with(|r| Some(r.search_for.attribute_value_match)),
+                            false,
+                            move |text_match| user_search_input_w.update(|v| v.search_for.attribute_value_match = text_match.unwrap_or(TextMatch::Contains)),
+                        )}
+                    </div>
+                </div>
                 {
                     move || {
                         let auto_complete_data = api_autocomplete_r.get();
@@ -454,14 +885,6 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
                         }
                     }
                 }
-                <label class="search-panel__label">
-                    "Key:"
-                    <input on:input=span_key_changed
-                        prop:value={move || user_search_input_r.with(|r| r.search_for.key.to_string())}
-                        list="key-list"
-                        class="search-panel__input" type="text" maxlength="50" size="20"
-                    />
-                </label>
                 {
                     move || {
                         let auto_complete_data = api_autocomplete_r.get();
@@ -477,19 +900,35 @@ pub fn TraceGrid(cx: Scope, root_path: String) -> impl IntoView {
                         }
                     }
                 }
-                <label class="search-panel__label">
-                    "Value:"
-                    <input on:input=span_value_changed
-                        prop:value={move || user_search_input_r.with(|r| r.search_for.value.to_string())}
-                        class="search-panel__input" type="text" maxlength="50" size="20"
-                    />
-                </label>
-                <label class="search-panel__label">
-                    "Log:"
-                    <input on:input=event_name_changed
-                        prop:value={move || user_search_input_r.with(|r| r.search_for.event_name.to_string())}
-                        class="search-panel__input" type="text" maxlength="50" size="20"/>
-                </label>
+                {
+                    move || {
+                        let values: Vec<_> = value_suggestions_r.get().into_iter().map(|v|{
+                            view!{cx,
+                                <option value={v}></option>
+                            }
+                        }).collect();
+                        view!{cx,
+                            <datalist id="value-list">
+                              {values}
+                            </datalist>
+                        }
+                    }
+                }
+                {
+                    move || {
+                        let auto_complete_data = api_autocomplete_r.get();
+                        let event_names: Vec<_> = auto_complete_data.event_names.iter().map(|n|{
+                            view!{cx,
+                                <option value={n}></option>
+                            }
+                        }).collect();
+                        view!{cx,
+                            <datalist id="event-name-list">
+                              {event_names}
+                            </datalist>
+                        }
+                    }
+                }
             </div>
         </div>
     }
@@ -562,12 +1001,89 @@ fn highlight(cx: Scope, original: String, term: String) -> Fragment {
         }
     };
 }
+/// Select for how a search field compares its text, the `words` option (None) is the full-text
+/// word search, only available for events
+fn text_match_select(
+    cx: Scope,
+    current: impl Fn() -> Option<TextMatch> + Copy + 'static,
+    with_words: bool,
+    on_change: impl Fn(Option<TextMatch>) + 'static,
+) -> impl IntoView {
+    let changed = move |ev: Event| {
+        let val = event_target_value(&ev);
+        on_change(TextMatch::ALL.into_iter().find(|m| m.label() == val));
+    };
+    view! {cx,
+        <select on:change=changed class="search-panel__input">
+            {with_words.then(|| view! {cx,
+                <option value="words" selected=move || current().is_none()>"words"</option>
+            })}
+            {
+                TextMatch::ALL.into_iter().map(|text_match|{
+                    view!{cx,
+                        <option value=text_match.label() selected=move || current() == Some(text_match)>
+                            {text_match.label()}
+                        </option>
+                    }
+                }).collect::<Vec<_>>()
+            }
+        </select>
+    }
+}
+
+/// Shows the words the backend wrapped in [HIGHLIGHT_START] and [HIGHLIGHT_END] like [highlight]
+fn highlight_snippet(cx: Scope, snippet: String) -> Fragment {
+    let parts = snippet
+        .split(HIGHLIGHT_START)
+        .enumerate()
+        .map(|(idx, part)| match part.split_once(HIGHLIGHT_END) {
+            Some((highlighted, rest)) if idx > 0 => view! {cx,
+                <>
+                <span style="color: red">{highlighted.to_string()}</span>
+                {rest.to_string()}
+                </>
+            },
+            _ => view! {cx, <>{part.to_string()}</>},
+        })
+        .collect::<Vec<_>>();
+    view! {cx, <>{parts}</>}
+}
+
+/// How close to the bottom of the page, in pixels, the next page starts loading
+const LOAD_MORE_THRESHOLD_PX: f64 = 300.;
+
 #[component]
 pub fn TraceTable(
     cx: Scope,
     root_path: String,
     rows: Signal<(Vec<TraceGridRow>, UserSearchInput)>,
+    /// Called when the page is scrolled close to the end of the table
+    #[prop(optional)]
+    load_more: Option<Box<dyn Fn()>>,
 ) -> impl IntoView {
+    if let Some(load_more) = load_more {
+        let on_scroll = Closure::<dyn Fn()>::new(move || {
+            let window = window();
+            let scrolled_to = window.scroll_y().unwrap_or(0.)
+                + window
+                    .inner_height()
+                    .ok()
+                    .and_then(|h| h.as_f64())
+                    .unwrap_or(0.);
+            let page_height = document()
+                .document_element()
+                .map(|e| e.scroll_height())
+                .unwrap_or(0) as f64;
+            if page_height - scrolled_to < LOAD_MORE_THRESHOLD_PX {
+                load_more();
+            }
+        });
+        _ = window().add_event_listener_with_callback("scroll", on_scroll.as_ref().unchecked_ref());
+        on_cleanup(cx, move || {
+            _ = window()
+                .remove_event_listener_with_callback("scroll", on_scroll.as_ref().unchecked_ref());
+        });
+    }
     let headers = [
         view! {cx,
             <th class="trace-table__cell">
@@ -619,6 +1135,27 @@ pub fn TraceTable(
     let html_rows = {
         move |rows: (Vec<TraceGridRow>, UserSearchInput)| {
             let user_search = rows.1;
+            let positive_conditions: Vec<&Filter> = user_search
+                .search_for
+                .filters
+                .iter()
+                .flat_map(|f| f.positive_conditions())
+                .collect();
+            let searched_span = positive_conditions
+                .iter()
+                .find_map(|f| match f {
+                    Filter::SpanName(name) => Some(name.clone()),
+                    Filter::Span(span) if !span.name.is_empty() => Some(span.name.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            let searched_key = positive_conditions
+                .iter()
+                .find_map(|f| match f {
+                    Filter::Attribute(attribute) => Some(attribute.key.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default();
             rows.0
                 .into_iter()
                 .map(|row| {
@@ -633,11 +1170,14 @@ pub fn TraceTable(
                 cx,
                 <tr class={row_container_class}>
                         <td class="trace-table__cell">{highlight(cx, row.service_name.clone(), user_search.search_for.service_name.clone())}</td>
-                        <td class="trace-table__cell">{row.top_level_span_name.to_string()}</td>
+                        <td class="trace-table__cell">
+                            {row.top_level_span_name.to_string()}
+                            {row.imported.then(|| view! {cx, <span class="trace-table__imported" title="Uploaded from a file">"imported"</span>})}
+                        </td>
                         <td class="trace-table__cell">{(row.duration/1000_000).to_string()}</td>
-                        <td class="trace-table__cell">{highlight(cx, row.span.unwrap_or_default(), user_search.search_for.span.clone())}</td>
-                        <td class="trace-table__cell">{row.sample_log.map(|sl| highlight(cx, sl, user_search.search_for.event_name.clone()))}</td>
-                        <td class="trace-table__cell">{highlight(cx, kv.unwrap_or_default(), user_search.search_for.key.clone())}</td>
+                        <td class="trace-table__cell">{highlight(cx, row.span.unwrap_or_default(), searched_span.clone())}</td>
+                        <td class="trace-table__cell">{row.sample_log.map(|sl| highlight_snippet(cx, sl))}</td>
+                        <td class="trace-table__cell">{highlight(cx, kv.unwrap_or_default(), searched_key.clone())}</td>
                         <td class="trace-table__cell">
                             {
                                 let timestamp = i64::try_from(row.timestamp).expect("created at timestamp to fit i64");
@@ -648,7 +1188,10 @@ pub fn TraceTable(
                         </td>
                         <td class="trace-table__cell">{row.warning_count}</td>
                         <td class="trace-table__cell">
-                            <a href={format!("{}trace/?trace_id={}", root_path, row.id)}>{"➔"}</a>
+                            <a href={
+                                let span_param = row.span_id.map(|span_id| format!("&span_id={span_id}")).unwrap_or_default();
+                                format!("{}trace/?trace_id={}{span_param}", root_path, row.id)
+                            }>{"➔"}</a>
                         </td>
                 </tr>
             };
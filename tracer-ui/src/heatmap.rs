@@ -0,0 +1,220 @@
+use crate::grid::{debounced_api, unix_nanos_to_local_date};
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{Heatmap, SearchFor};
+use leptos::ev::MouseEvent;
+use leptos::*;
+use std::ops::Deref;
+
+/// Traces inside the selected cells, the bounds are inclusive like the grid search ones
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeatmapSelection {
+    pub from_date_unix: u64,
+    pub to_date_unix: u64,
+    pub min_duration: u64,
+    pub max_duration: u64,
+}
+
+type Cell = (u32, u32);
+
+fn selection_from_cells(heatmap: &Heatmap, start: Cell, end: Cell) -> Option<HeatmapSelection> {
+    let (first_time, last_time) = (start.0.min(end.0), start.0.max(end.0));
+    let (first_duration, last_duration) = (start.1.min(end.1), start.1.max(end.1));
+    let bounds = &heatmap.duration_bucket_bounds;
+    let min_duration = *bounds.get(first_duration as usize)?;
+    let max_duration = bounds.get(last_duration as usize + 1)? - 1;
+    let time_bucket_start = |bucket: u32| {
+        heatmap.first_time_bucket_unix + u64::from(bucket) * heatmap.time_bucket_size_nanos
+    };
+    Some(HeatmapSelection {
+        from_date_unix: time_bucket_start(first_time),
+        to_date_unix: time_bucket_start(last_time + 1) - 1,
+        min_duration,
+        max_duration,
+    })
+}
+
+fn format_duration(nanos: u64) -> String {
+    if nanos >= 1_000_000_000 {
+        format!("{:.1}s", nanos as f64 / 1_000_000_000.)
+    } else if nanos >= 1_000_000 {
+        format!("{:.1}ms", nanos as f64 / 1_000_000.)
+    } else {
+        format!("{:.1}us", nanos as f64 / 1_000.)
+    }
+}
+
+/// Duration vs time of the traces matching the search, dragging over it (or clicking a cell)
+/// calls `on_select` with the time and duration range of the selected cells
+#[component]
+pub fn TraceHeatmap(
+    cx: Scope,
+    search: Signal<SearchFor>,
+    on_select: Box<dyn Fn(HeatmapSelection)>,
+) -> impl IntoView {
+    let (heatmap_r, heatmap_w) = create_signal(cx, Heatmap::default());
+    let (drag_start_r, drag_start_w) = create_signal(cx, Option::<Cell>::None);
+    let (drag_end_r, drag_end_w) = create_signal(cx, Option::<Cell>::None);
+    let on_select = store_value(cx, on_select);
+    let _heatmap_request_state = debounced_api(
+        cx,
+        move || search.get(),
+        move |search| get_heatmap(search, heatmap_w),
+    );
+    let container_ref = create_node_ref::<leptos::html::Div>(cx);
+    let cell_under_mouse = move |ev: &MouseEvent| -> Option<Cell> {
+        let container = container_ref.get()?;
+        let dom_rect = web_sys::Element::from(container.deref().clone()).get_bounding_client_rect();
+        let x = (ev.client_x() as f64 - dom_rect.x()) / dom_rect.width();
+        // duration grows upwards
+        let y = 1. - (ev.client_y() as f64 - dom_rect.y()) / dom_rect.height();
+        heatmap_r.with_untracked(|heatmap| {
+            let duration_buckets = heatmap.duration_bucket_bounds.len().saturating_sub(1);
+            if heatmap.time_buckets == 0 || duration_buckets == 0 {
+                return None;
+            }
+            let time_bucket = (x * heatmap.time_buckets as f64).floor();
+            let duration_bucket = (y * duration_buckets as f64).floor();
+            Some((
+                time_bucket.clamp(0., heatmap.time_buckets as f64 - 1.) as u32,
+                duration_bucket.clamp(0., duration_buckets as f64 - 1.) as u32,
+            ))
+        })
+    };
+    let mouse_down = move |ev: MouseEvent| {
+        ev.prevent_default();
+        let cell = cell_under_mouse(&ev);
+        drag_start_w.set(cell);
+        drag_end_w.set(cell);
+    };
+    let mouse_move = move |ev: MouseEvent| {
+        if drag_start_r.get_untracked().is_some() {
+            drag_end_w.set(cell_under_mouse(&ev));
+        }
+    };
+    let mouse_up = move |ev: MouseEvent| {
+        let start = drag_start_r.get_untracked();
+        let end = cell_under_mouse(&ev).or(drag_end_r.get_untracked());
+        drag_start_w.set(None);
+        drag_end_w.set(None);
+        let (Some(start), Some(end)) = (start, end) else {
+            return;
+        };
+        if let Some(selection) =
+            heatmap_r.with_untracked(|heatmap| selection_from_cells(heatmap, start, end))
+        {
+            on_select.with_value(|on_select| on_select(selection));
+        }
+    };
+    let mouse_leave = move |_ev: MouseEvent| {
+        drag_start_w.set(None);
+        drag_end_w.set(None);
+    };
+
+    let cells = move || {
+        heatmap_r.with(|heatmap| {
+            let duration_buckets = heatmap.duration_bucket_bounds.len().saturating_sub(1).max(1);
+            let time_buckets = heatmap.time_buckets.max(1);
+            let max_count = heatmap.cells.iter().map(|c| c.count).max().unwrap_or(1);
+            let width = 100. / time_buckets as f64;
+            let height = 100. / duration_buckets as f64;
+            heatmap
+                .cells
+                .iter()
+                .map(|cell| {
+                    let left = width * cell.time_bucket as f64;
+                    let bottom = height * cell.duration_bucket as f64;
+                    // log scale so a few outliers are still visible next to the bulk
+                    let alpha =
+                        0.2 + 0.8 * (cell.count as f64).ln_1p() / (max_count as f64).ln_1p();
+                    let title = selection_from_cells(
+                        heatmap,
+                        (cell.time_bucket, cell.duration_bucket),
+                        (cell.time_bucket, cell.duration_bucket),
+                    )
+                    .map(|s| {
+                        format!(
+                            "{} traces, {} - {}, starting {}",
+                            cell.count,
+                            format_duration(s.min_duration),
+                            format_duration(s.max_duration),
+                            unix_nanos_to_local_date(s.from_date_unix).format("%Y-%m-%d %H:%M:%S")
+                        )
+                    })
+                    .unwrap_or_default();
+                    view! {cx,
+                        <div class="heatmap__cell" title=title
+                            style=format!("left: {left:.2}%; bottom: {bottom:.2}%; width: {width:.2}%; height: {height:.2}%; opacity: {alpha:.2};")>
+                        </div>
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+    let selection_overlay = move || {
+        let (Some(start), Some(end)) = (drag_start_r.get(), drag_end_r.get()) else {
+            return None;
+        };
+        heatmap_r.with(|heatmap| {
+            let duration_buckets = heatmap.duration_bucket_bounds.len().saturating_sub(1).max(1);
+            let time_buckets = heatmap.time_buckets.max(1);
+            let width = 100. / time_buckets as f64;
+            let height = 100. / duration_buckets as f64;
+            let left = width * start.0.min(end.0) as f64;
+            let bottom = height * start.1.min(end.1) as f64;
+            let selection_width = width * (start.0.abs_diff(end.0) + 1) as f64;
+            let selection_height = height * (start.1.abs_diff(end.1) + 1) as f64;
+            Some(view! {cx,
+                <div class="heatmap__selection"
+                    style=format!("left: {left:.2}%; bottom: {bottom:.2}%; width: {selection_width:.2}%; height: {selection_height:.2}%;")>
+                </div>
+            })
+        })
+    };
+    let axis_labels = move || {
+        heatmap_r.with(|heatmap| {
+            let (Some(min_duration), Some(max_duration)) = (
+                heatmap.duration_bucket_bounds.first(),
+                heatmap.duration_bucket_bounds.last(),
+            ) else {
+                return None;
+            };
+            let end = heatmap.first_time_bucket_unix
+                + u64::from(heatmap.time_buckets) * heatmap.time_bucket_size_nanos;
+            Some(view! {cx,
+                <div class="heatmap__axis">
+                    <span>{format!("{} → {}", format_duration(*min_duration), format_duration(*max_duration))}</span>
+                    <span>{format!("{} → {}",
+                        unix_nanos_to_local_date(heatmap.first_time_bucket_unix).format("%Y-%m-%d %H:%M:%S"),
+                        unix_nanos_to_local_date(end).format("%Y-%m-%d %H:%M:%S"))}
+                    </span>
+                </div>
+            })
+        })
+    };
+
+    view! {cx,
+        <div class="heatmap">
+            <div class="heatmap__area" _ref=container_ref
+                on:mousedown=mouse_down on:mousemove=mouse_move on:mouseup=mouse_up on:mouseleave=mouse_leave
+            >
+                {cells}
+                {selection_overlay}
+            </div>
+            {axis_labels}
+        </div>
+    }
+}
+
+async fn get_heatmap(search: SearchFor, heatmap_w: WriteSignal<Heatmap>) {
+    let heatmap: Heatmap =
+        gloo_net::http::Request::post(&format!("{}/api/heatmap", API_SERVER_URL_NO_TRAILING_SLASH))
+            .json(&search)
+            .unwrap()
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    heatmap_w.set(heatmap);
+}
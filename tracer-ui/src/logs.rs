@@ -0,0 +1,301 @@
+use crate::grid::{debounced_api, local_date_to_unix_nanos, unix_nanos_to_local_date, DatePicker};
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{AttributeFilter, LogLine, LogPage, LogSearchRequest, Severity, ValueComparison};
+use chrono::{Duration, NaiveDateTime};
+use js_sys::Date;
+use leptos::ev::{Event, MouseEvent};
+use leptos::*;
+
+#[derive(Debug, Clone, PartialEq)]
+struct AttributeRow {
+    id: u64,
+    filter: AttributeFilter,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LogSearchInput {
+    request: LogSearchRequest,
+    attribute_rows: Vec<AttributeRow>,
+}
+
+impl Default for LogSearchInput {
+    fn default() -> Self {
+        let now = NaiveDateTime::from_timestamp_millis(Date::now().round() as i64).unwrap();
+        Self {
+            request: LogSearchRequest {
+                from_date_unix: u64::try_from((now - Duration::hours(1)).timestamp_nanos())
+                    .expect("timestamp to fit u64"),
+                to_date_unix: u64::try_from(now.timestamp_nanos()).expect("timestamp to fit u64"),
+                service_name: String::new(),
+                min_severity: None,
+                message: String::new(),
+                attributes: vec![],
+                cursor: None,
+            },
+            attribute_rows: vec![],
+        }
+    }
+}
+
+impl LogSearchInput {
+    /// Rows still missing the key are left out instead of failing the whole search
+    fn update_attributes(&mut self) {
+        self.request.attributes = self
+            .attribute_rows
+            .iter()
+            .filter(|row| !row.filter.key.is_empty())
+            .map(|row| row.filter.clone())
+            .collect();
+    }
+}
+
+async fn fetch_log_page(request: &LogSearchRequest) -> LogPage {
+    gloo_net::http::Request::post(&format!("{}/api/logs", API_SERVER_URL_NO_TRAILING_SLASH))
+        .json(request)
+        .unwrap()
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn get_logs(request: LogSearchRequest, w: WriteSignal<LogPage>) {
+    w.set(fetch_log_page(&request).await);
+}
+
+fn log_line_row(cx: Scope, root_path: &str, line: LogLine) -> impl IntoView {
+    let severity = line.severity.db_name();
+    let attributes = line
+        .key_values
+        .iter()
+        .map(|kv| format!("{}={}", kv.key, kv.value))
+        .collect::<Vec<_>>()
+        .join(" ");
+    view! {cx,
+        <tr class="row-container">
+            <td class="trace-table__cell log-viewer__time">
+                {unix_nanos_to_local_date(line.timestamp).format("%Y-%m-%d %H:%M:%S%.3f").to_string()}
+            </td>
+            <td class=format!("trace-table__cell log-viewer__severity--{severity}")>{severity.to_uppercase()}</td>
+            <td class="trace-table__cell">{line.service_name}</td>
+            <td class="trace-table__cell" title=format!("top level span: {}", line.top_level_span_name)>{line.span_name}</td>
+            <td class="trace-table__cell log-viewer__message">{line.message}</td>
+            <td class="trace-table__cell log-viewer__attributes">{attributes}</td>
+            <td class="trace-table__cell">
+                <a href=format!("{root_path}trace?trace_id={}", line.trace_id) title="Open the trace">{"➔"}</a>
+            </td>
+        </tr>
+    }
+}
+
+/// Events of all traces as log lines, newest first
+#[component]
+pub fn LogViewer(cx: Scope, root_path: String) -> impl IntoView {
+    let (input_r, input_w) = create_signal(cx, LogSearchInput::default());
+    let (page_r, page_w) = create_signal(cx, LogPage::default());
+    let (loading_more_r, loading_more_w) = create_signal(cx, false);
+    let request = create_memo(cx, move |_| input_r.with(|i| i.request.clone()));
+    let _request_state = debounced_api(
+        cx,
+        move || request.get(),
+        move |request| get_logs(request, page_w),
+    );
+    let load_more = move |_click: MouseEvent| {
+        let Some(cursor) = page_r.with_untracked(|p| p.next_cursor) else {
+            return;
+        };
+        let first_page_request = request.get_untracked();
+        let mut next_page_request = first_page_request.clone();
+        next_page_request.cursor = Some(cursor);
+        loading_more_w.set(true);
+        spawn_local(async move {
+            let next_page = fetch_log_page(&next_page_request).await;
+            // the search might have changed while the page was loading
+            if request.with_untracked(|r| *r == first_page_request) {
+                page_w.update(|p| {
+                    if p.next_cursor == Some(cursor) {
+                        p.lines.extend(next_page.lines);
+                        p.next_cursor = next_page.next_cursor;
+                    }
+                });
+            }
+            loading_more_w.set(false);
+        });
+    };
+
+    let current_from_datetime = Signal::derive(cx, move || {
+        input_r.with(|i| unix_nanos_to_local_date(i.request.from_date_unix))
+    });
+    let current_to_datetime = Signal::derive(cx, move || {
+        input_r.with(|i| unix_nanos_to_local_date(i.request.to_date_unix))
+    });
+    let from_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            input_w.update(|i| i.request.from_date_unix = timestamp_nanos);
+        } else {
+            log!("From date out of bounds!")
+        }
+    };
+    let to_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            input_w.update(|i| i.request.to_date_unix = timestamp_nanos);
+        } else {
+            log!("To date out of bounds!")
+        }
+    };
+    let service_name_changed =
+        move |ev: Event| input_w.update(|i| i.request.service_name = event_target_value(&ev));
+    let message_changed =
+        move |ev: Event| input_w.update(|i| i.request.message = event_target_value(&ev));
+    let min_severity_changed = move |ev: Event| {
+        let val = event_target_value(&ev);
+        input_w.update(|i| {
+            i.request.min_severity = Severity::ALL.into_iter().find(|s| s.db_name() == val)
+        });
+    };
+
+    let update_attribute_row = move |id: u64, update: &dyn Fn(&mut AttributeFilter)| {
+        input_w.update(|i| {
+            if let Some(row) = i.attribute_rows.iter_mut().find(|r| r.id == id) {
+                update(&mut row.filter);
+            }
+            i.update_attributes();
+        });
+    };
+    let add_attribute_row = move |_click: MouseEvent| {
+        input_w.update(|i| {
+            let id = i.attribute_rows.iter().map(|r| r.id + 1).max().unwrap_or(0);
+            i.attribute_rows.push(AttributeRow {
+                id,
+                filter: AttributeFilter {
+                    key: String::new(),
+                    comparison: ValueComparison::default(),
+                    value: String::new(),
+                },
+            });
+        });
+    };
+    let attribute_row_view = move |cx: Scope, row: AttributeRow| {
+        let id = row.id;
+        let key_changed =
+            move |ev: Event| update_attribute_row(id, &|f| f.key = event_target_value(&ev));
+        let value_changed =
+            move |ev: Event| update_attribute_row(id, &|f| f.value = event_target_value(&ev));
+        let comparison_changed = move |ev: Event| {
+            let val = event_target_value(&ev);
+            if let Some(comparison) = ValueComparison::ALL
+                .into_iter()
+                .find(|comparison| comparison.symbol() == val)
+            {
+                update_attribute_row(id, &|f| f.comparison = comparison);
+            }
+        };
+        let remove = move |_click: MouseEvent| {
+            input_w.update(|i| {
+                i.attribute_rows.retain(|r| r.id != id);
+                i.update_attributes();
+            });
+        };
+        view! {cx,
+            <div class="search-panel__input-flex-container">
+                <input on:input=key_changed prop:value=row.filter.key.clone()
+                    placeholder="Key" class="search-panel__input" type="text" maxlength="50" size="10"
+                />
+                <select on:change=comparison_changed class="search-panel__input">
+                    {
+                        ValueComparison::ALL.into_iter().map(|comparison|{
+                            view!{cx,
+                                <option value=comparison.symbol() selected=row.filter.comparison == comparison>
+                                    {comparison.symbol()}
+                                </option>
+                            }
+                        }).collect::<Vec<_>>()
+                    }
+                </select>
+                <input on:input=value_changed prop:value=row.filter.value.clone()
+                    placeholder="Value" class="search-panel__input" type="text" maxlength="50" size="10"
+                />
+                <button class="filter-builder__button" on:click=remove>"✕"</button>
+            </div>
+        }
+    };
+
+    let rows = move || {
+        page_r.with(|p| {
+            p.lines
+                .iter()
+                .cloned()
+                .map(|line| log_line_row(cx, &root_path, line))
+                .collect::<Vec<_>>()
+        })
+    };
+    view! {cx,
+        <div class="main-grid">
+            <div class="main">
+                <table class="trace-table log-viewer">
+                    <tr class="row-container">
+                        <th class="trace-table__cell">"Time (local)"</th>
+                        <th class="trace-table__cell">"Level"</th>
+                        <th class="trace-table__cell">"Service"</th>
+                        <th class="trace-table__cell">"Span"</th>
+                        <th class="trace-table__cell">"Message"</th>
+                        <th class="trace-table__cell">"Attributes"</th>
+                        <th class="trace-table__cell">"Trace"</th>
+                    </tr>
+                    {rows}
+                </table>
+                {move || page_r.with(|p| p.next_cursor.is_some()).then(|| view! {cx,
+                    <button class="trace-table__load-more" on:click=load_more
+                        disabled=move || loading_more_r.get()
+                    >
+                        {move || if loading_more_r.get() { "Loading..." } else { "Load older" }}
+                    </button>
+                })}
+            </div>
+            <div class="search-panel">
+                <DatePicker
+                    label="From (local):".to_string()
+                    date_to_display=current_from_datetime
+                    on_change=Box::new(from_changed)
+                />
+                <DatePicker
+                    label="To (local):".to_string()
+                    date_to_display=current_to_datetime
+                    on_change=Box::new(to_changed)
+                />
+                <label class="search-panel__label">
+                    "Service Name:"
+                    <input on:input=service_name_changed class="search-panel__input" type="text" maxlength="50"/>
+                </label>
+                <label class="search-panel__label">
+                    "Min Level:"
+                    <select on:change=min_severity_changed class="search-panel__input">
+                        <option value="">"Any"</option>
+                        {
+                            Severity::ALL.into_iter().map(|severity|{
+                                view!{cx,
+                                    <option value=severity.db_name()>{severity.db_name().to_uppercase()}</option>
+                                }
+                            }).collect::<Vec<_>>()
+                        }
+                    </select>
+                </label>
+                <label class="search-panel__label">
+                    "Message Contains:"
+                    <input on:input=message_changed class="search-panel__input" type="text"/>
+                </label>
+                <div class="search-panel__label">
+                    "Attributes:"
+                    <For
+                        each=move || input_r.with(|i| i.attribute_rows.clone())
+                        key=|row| row.id
+                        view=attribute_row_view
+                    />
+                    <button class="filter-builder__button" on:click=add_attribute_row>"+ Attribute"</button>
+                </div>
+            </div>
+        </div>
+    }
+}
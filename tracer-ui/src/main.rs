@@ -1,11 +1,27 @@
 use leptos::*;
 
 mod grid;
+mod heatmap;
+mod profile;
 use grid::TraceGrid;
+mod compare;
 mod details;
+mod errors;
+mod filter_builder;
+mod logs;
+mod query;
+mod saved_searches;
+mod self_time;
+mod storage;
 mod summary;
+mod time_series;
+use compare::TraceComparisonView;
 use details::TraceDetails;
+use errors::ErrorsInbox;
 use leptos_router::*;
+use logs::LogViewer;
+use query::TraceQuery;
+use storage::StorageUsageSummary;
 use summary::TracesSummary;
 const API_SERVER_URL_NO_TRAILING_SLASH: &str = env!("API_SERVER_URL_NO_TRAILING_SLASH");
 
@@ -25,7 +41,11 @@ pub fn App(cx: Scope) -> impl IntoView {
                 <nav class="navigation">
                     <div class="navigation__button"></div>
                     <a class="navigation__button" href={&root_path}>"Home"</a>
+                    <a class="navigation__button" href=format!("{}query", root_path)>"Query"</a>
+                    <a class="navigation__button" href=format!("{}logs", root_path)>"Logs"</a>
+                    <a class="navigation__button" href=format!("{}errors", root_path)>"Errors"</a>
                     <a class="navigation__button" href=format!("{}summary", root_path)>"Summary"</a>
+                    <a class="navigation__button" href=format!("{}storage", root_path)>"Storage"</a>
                 </nav>
             </header>
                 <Router>
@@ -43,10 +63,53 @@ pub fn App(cx: Scope) -> impl IntoView {
                             />
                         <Route
                               path=format!("{}trace", root_path)
-                              view=move |cx| view! {
+                              view={
+                                let root_path= root_path.to_string();
+                                move |cx| view! {
+                                    cx,
+                                    <TraceDetails root_path=root_path.clone()/>
+                                }
+                              }
+                            />
+                        <Route
+                              path=format!("{}compare", root_path)
+                              view={
+                                let root_path= root_path.to_string();
+                                move |cx| view! {
+                                    cx,
+                                    <TraceComparisonView root_path=root_path.clone()/>
+                                }
+                              }
+                            />
+                        <Route
+                              path=format!("{}query", root_path)
+                              view={
+                                let root_path= root_path.to_string();
+                                move |cx| view! {
+                                    cx,
+                                    <TraceQuery root_path=root_path.clone()/>
+                                }
+                              }
+                            />
+                        <Route
+                              path=format!("{}logs", root_path)
+                              view={
+                                let root_path= root_path.to_string();
+                                move |cx| view! {
                                     cx,
-                                    <TraceDetails/>
+                                    <LogViewer root_path=root_path.clone()/>
                                 }
+                              }
+                            />
+                        <Route
+                              path=format!("{}errors", root_path)
+                              view={
+                                let root_path= root_path.to_string();
+                                move |cx| view! {
+                                    cx,
+                                    <ErrorsInbox root_path=root_path.clone()/>
+                                }
+                              }
                             />
                         <Route
                               path=format!("{}summary", root_path)
@@ -58,6 +121,13 @@ pub fn App(cx: Scope) -> impl IntoView {
                                 }
                               }
                             />
+                        <Route
+                              path=format!("{}storage", root_path)
+                              view=move |cx| view! {
+                                    cx,
+                                    <StorageUsageSummary/>
+                                }
+                            />
                     </Routes>
                 </Router>
         </>
@@ -0,0 +1,155 @@
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{Profile, ProfileNode, ProfileRequest};
+use leptos::*;
+
+const ROW_HEIGHT_PX: u32 = 20;
+
+fn ms(nanos: u64) -> String {
+    format!("{:.2}ms", nanos as f64 / 1_000_000.)
+}
+
+fn node_title(node: &ProfileNode, traces_profiled: u64) -> String {
+    format!(
+        "{}\n{} calls in {} of {} traces, {} per trace\ntotal: avg {}, p50 {}, p90 {}\nself: avg {}, p50 {}, p90 {}",
+        node.name,
+        node.calls,
+        node.traces,
+        traces_profiled,
+        ms(node.total_duration_per_trace),
+        ms(node.avg_total_duration),
+        ms(node.p50_total_duration),
+        ms(node.p90_total_duration),
+        ms(node.avg_self_duration),
+        ms(node.p50_self_duration),
+        ms(node.p90_self_duration),
+    )
+}
+
+/// Stable color per span name so the same span is easy to follow across the graph
+fn node_color(name: &str) -> String {
+    let hash = name.bytes().fold(0u32, |acc, b| {
+        acc.wrapping_mul(31).wrapping_add(u32::from(b))
+    });
+    format!("hsl({}, 70%, 45%)", 10 + hash % 40)
+}
+
+/// Left offset and width of each node in % of the root width. Children are laid out one after
+/// the other from their parent's start, scaled down to fit in it when they ran concurrently.
+fn node_positions(nodes: &[ProfileNode]) -> Vec<(f64, f64)> {
+    let mut children_total = vec![0u64; nodes.len()];
+    for node in nodes {
+        if let Some(parent) = node.parent {
+            children_total[parent as usize] += node.total_duration_per_trace;
+        }
+    }
+    let mut positions: Vec<(f64, f64)> = Vec::with_capacity(nodes.len());
+    // where the next child of each node starts
+    let mut next_child_left = vec![0f64; nodes.len()];
+    for (idx, node) in nodes.iter().enumerate() {
+        let (left, width) = match node.parent {
+            None => (0., 100.),
+            Some(parent) => {
+                let parent = parent as usize;
+                let parent_width = positions[parent].1;
+                let parent_total =
+                    children_total[parent].max(nodes[parent].total_duration_per_trace);
+                let width = parent_width * node.total_duration_per_trace as f64
+                    / parent_total.max(1) as f64;
+                let left = next_child_left[parent];
+                next_child_left[parent] += width;
+                (left, width)
+            }
+        };
+        next_child_left[idx] = left;
+        positions.push((left, width));
+    }
+    positions
+}
+
+/// Icicle graph of the selected (service name, top level span name) traces over the window,
+/// the root on top and each span name path below its parent, widths are time per trace
+#[component]
+pub fn ProfileFlameGraph(
+    cx: Scope,
+    selected: Signal<Option<(String, String)>>,
+    window: Signal<(u64, u64)>,
+) -> impl IntoView {
+    let (profile_r, profile_w) = create_signal(cx, Profile::default());
+    let request = create_memo(cx, move |_| {
+        let (service_name, top_level_span_name) = selected.get()?;
+        let (from_date_unix, to_date_unix) = window.get();
+        Some(ProfileRequest {
+            service_name,
+            top_level_span_name,
+            from_date_unix,
+            to_date_unix,
+        })
+    });
+    let _api_request_sender = create_local_resource(
+        cx,
+        move || request.get(),
+        move |request| async move {
+            if let Some(request) = request {
+                get_profile(request, profile_w).await;
+            }
+        },
+    );
+    let graph = move || {
+        profile_r.with(|profile| {
+            let max_depth = profile.nodes.iter().map(|n| n.depth).max().unwrap_or(0);
+            let height = (max_depth + 1) * ROW_HEIGHT_PX;
+            let cells = profile
+                .nodes
+                .iter()
+                .zip(node_positions(&profile.nodes))
+                .filter(|(_node, (_left, width))| *width >= 0.05)
+                .map(|(node, (left, width))| {
+                    let top = node.depth * ROW_HEIGHT_PX;
+                    let label = (width >= node.name.len() as f64 / 2.).then(|| node.name.clone());
+                    view! {cx,
+                        <div class="flame-graph__node" title=node_title(node, profile.traces_profiled)
+                            style=format!("left: {left:.3}%; width: {width:.3}%; top: {top}px; background-color: {};", node_color(&node.name))>
+                            {label}
+                        </div>
+                    }
+                })
+                .collect::<Vec<_>>();
+            view! {cx,
+                <div class="flame-graph__area" style=format!("height: {height}px;")>{cells}</div>
+            }
+        })
+    };
+    view! {cx,
+        {move || match selected.get() {
+            None => view! {cx,
+                <div class="flame-graph">
+                    <p class="flame-graph__title">"Pick a row's 🔥 to see where its time typically goes"</p>
+                </div>
+            },
+            Some((service_name, top_level_span_name)) => view! {cx,
+                <div class="flame-graph">
+                    <p class="flame-graph__title">
+                        {move || profile_r.with(|p| format!(
+                            "{service_name} - {top_level_span_name}, merged the {} most recent of {} traces",
+                            p.traces_profiled, p.traces_in_window))}
+                    </p>
+                    {graph}
+                </div>
+            },
+        }}
+    }
+}
+
+async fn get_profile(request: ProfileRequest, w: WriteSignal<Profile>) {
+    let profile: Profile =
+        gloo_net::http::Request::post(&format!("{}/api/profile", API_SERVER_URL_NO_TRAILING_SLASH))
+            .json(&request)
+            .unwrap()
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    w.set(profile);
+}
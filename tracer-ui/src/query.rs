@@ -0,0 +1,247 @@
+use crate::grid::{
+    debounced_api, local_date_to_unix_nanos, unix_nanos_to_local_date, DatePicker, RequestState,
+    TraceGridRow, TraceTable, UserSearchInput,
+};
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{ApiTraceGrid, QuerySyntaxError, TraceGridCursor, TraceQueryRequest};
+use chrono::{Duration, NaiveDateTime};
+use js_sys::Date;
+use leptos::ev::Event;
+use leptos::*;
+
+const QUERY_EXAMPLES: [&str; 4] = [
+    r#"service = "checkout" and status = error"#,
+    r#".http.status_code >= 500 or event ~ "timeout""#,
+    r#"span(name = "db_query") >> span(name = "fetch_row", duration > 200ms)"#,
+    r#"duration > 1s and not root ~ "health""#,
+];
+
+fn default_request() -> TraceQueryRequest {
+    let now = NaiveDateTime::from_timestamp_millis(Date::now().round() as i64).unwrap();
+    TraceQueryRequest {
+        query: "".to_string(),
+        from_date_unix: u64::try_from((now - Duration::hours(1)).timestamp_nanos())
+            .expect("timestamp to fit u64"),
+        to_date_unix: u64::try_from((now + Duration::days(1)).timestamp_nanos())
+            .expect("timestamp to fit u64"),
+        cursor: None,
+    }
+}
+
+/// Rows of all pages loaded so far
+#[derive(Debug, Clone, Default)]
+struct QueryPages {
+    rows: Vec<TraceGridRow>,
+    total_count: Option<u64>,
+    next_cursor: Option<TraceGridCursor>,
+}
+
+/// 1 based line and column of the char offset
+fn line_and_column(query: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for c in query.chars().take(offset) {
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+fn syntax_error_view(cx: Scope, query: String, error: QuerySyntaxError) -> impl IntoView {
+    let chars: Vec<char> = query.chars().collect();
+    let start = error.start.min(chars.len());
+    let end = error.end.clamp(start, chars.len());
+    let before: String = chars[..start].iter().collect();
+    let mut highlighted: String = chars[start..end].iter().collect();
+    if highlighted.is_empty() {
+        // the error is at the end of the query, give the underline something to show
+        highlighted = " ".to_string();
+    }
+    let after: String = chars[end..].iter().collect();
+    let (line, column) = line_and_column(&query, start);
+    view! {cx,
+        <div class="query-box__error">
+            <p class="query-box__error-message">
+                {format!("Line {line}, column {column}: {}", error.message)}
+            </p>
+            <pre class="query-box__error-context">
+                {before}
+                <span class="query-box__error-highlight">{highlighted}</span>
+                {after}
+            </pre>
+        </div>
+    }
+}
+
+#[component]
+pub fn TraceQuery(cx: Scope, root_path: String) -> impl IntoView {
+    let (request_r, request_w) = create_signal(cx, default_request());
+    let (pages_r, pages_w) = create_signal(cx, QueryPages::default());
+    let (error_r, error_w) = create_signal(cx, Option::<QuerySyntaxError>::None);
+    let request_state = debounced_api(
+        cx,
+        move || request_r.get(),
+        move |request| run_query(request, pages_w, error_w),
+    );
+    let (loading_more_r, loading_more_w) = create_signal(cx, false);
+    let load_more = move || {
+        if loading_more_r.get_untracked()
+            || !matches!(request_state.get_untracked(), RequestState::Idle)
+        {
+            return;
+        }
+        let Some(cursor) = pages_r.with_untracked(|p| p.next_cursor) else {
+            return;
+        };
+        let first_page_request = request_r.get_untracked();
+        let mut request = first_page_request.clone();
+        request.cursor = Some(cursor);
+        loading_more_w.set(true);
+        spawn_local(async move {
+            // a failed page keeps the rows loaded so far, the error is shown as for the first page
+            if let Ok(resp) = fetch_query_page(&request, error_w).await {
+                // the query might have changed while the page was loading
+                if request_r.with_untracked(|r| *r == first_page_request) {
+                    pages_w.update(|p| {
+                        if p.next_cursor == Some(cursor) {
+                            p.rows.extend(resp.rows.into_iter().map(TraceGridRow::from));
+                            p.next_cursor = resp.next_cursor;
+                        }
+                    });
+                }
+            }
+            loading_more_w.set(false);
+        });
+    };
+    let rows_with_search = Signal::derive(cx, move || {
+        (pages_r.with(|p| p.rows.clone()), UserSearchInput::default())
+    });
+    let query_changed = move |ev: Event| {
+        let val = event_target_value(&ev);
+        request_w.update(|r| r.query = val);
+    };
+    let current_from_datetime = Signal::derive(cx, move || {
+        request_r.with(|r| unix_nanos_to_local_date(r.from_date_unix))
+    });
+    let current_to_datetime = Signal::derive(cx, move || {
+        request_r.with(|r| unix_nanos_to_local_date(r.to_date_unix))
+    });
+    let from_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            request_w.update(|r| r.from_date_unix = timestamp_nanos);
+        } else {
+            log!("From date out of bounds!")
+        }
+    };
+    let to_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            request_w.update(|r| r.to_date_unix = timestamp_nanos);
+        } else {
+            log!("To date out of bounds!")
+        }
+    };
+    let tracer_counter = move || {
+        let number_traces = pages_r.with(|p| p.total_count.unwrap_or(p.rows.len() as u64));
+        if !matches!(request_state.get(), RequestState::Idle) {
+            view! { cx, <p style="margin: 0; background-color: yellow">{"Updating..."}</p>}
+        } else {
+            view! { cx, <p style="margin: 0">{format!("{} traces", number_traces)}</p>}
+        }
+    };
+    let examples = QUERY_EXAMPLES
+        .into_iter()
+        .map(|example| view! {cx, <li><code>{example}</code></li>})
+        .collect::<Vec<_>>();
+
+    view! {cx,
+        <div class="main-grid">
+            <div class="main">
+                <div class="query-box">
+                    <textarea on:input=query_changed
+                        prop:value=move || request_r.with(|r| r.query.clone())
+                        class="query-box__input" rows="3" spellcheck="false"
+                        placeholder=QUERY_EXAMPLES[0]
+                    />
+                    {move || error_r.get().map(|error| syntax_error_view(cx, request_r.with_untracked(|r| r.query.clone()), error))}
+                </div>
+                <TraceTable rows=rows_with_search root_path=root_path load_more=Box::new(load_more)/>
+                {move || pages_r.with(|p| p.next_cursor.is_some()).then(|| view! {cx,
+                    <button class="trace-table__load-more" on:click=move |_| load_more()
+                        disabled=move || loading_more_r.get()
+                    >
+                        {move || if loading_more_r.get() { "Loading..." } else { "Load more" }}
+                    </button>
+                })}
+            </div>
+            <div class="search-panel">
+                <h1 class="traces-counter">{tracer_counter}</h1>
+                <DatePicker
+                    label="From (local):".to_string()
+                    date_to_display=current_from_datetime
+                    on_change=Box::new(from_changed)
+                />
+                <DatePicker
+                    label="To (local):".to_string()
+                    date_to_display=current_to_datetime
+                    on_change=Box::new(to_changed)
+                />
+                <div class="search-panel__label">
+                    "Examples:"
+                    <ul class="query-box__examples">{examples}</ul>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+async fn run_query(
+    request: TraceQueryRequest,
+    pages_w: WriteSignal<QueryPages>,
+    error_w: WriteSignal<Option<QuerySyntaxError>>,
+) {
+    if let Ok(resp) = fetch_query_page(&request, error_w).await {
+        pages_w.set(QueryPages {
+            rows: resp.rows.into_iter().map(TraceGridRow::from).collect(),
+            total_count: resp.total_count,
+            next_cursor: resp.next_cursor,
+        });
+    }
+}
+
+/// Sets or clears the syntax error, Err when the query failed
+async fn fetch_query_page(
+    request: &TraceQueryRequest,
+    error_w: WriteSignal<Option<QuerySyntaxError>>,
+) -> Result<ApiTraceGrid, ()> {
+    let resp =
+        gloo_net::http::Request::post(&format!("{}/api/query", API_SERVER_URL_NO_TRAILING_SLASH))
+            .json(request)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+    if resp.ok() {
+        error_w.set(None);
+        return Ok(resp.json().await.unwrap());
+    }
+    let is_json = resp
+        .headers()
+        .get("content-type")
+        .is_some_and(|content_type| content_type.contains("application/json"));
+    let error = if is_json {
+        resp.json().await.unwrap()
+    } else {
+        QuerySyntaxError {
+            message: resp.text().await.unwrap_or_default(),
+            start: 0,
+            end: 0,
+        }
+    };
+    log!("Query failed: {:?}", error);
+    error_w.set(Some(error));
+    Err(())
+}
@@ -0,0 +1,240 @@
+use crate::grid::grid_search_url;
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{
+    DeleteSavedSearchRequest, DeleteSearchSubscriptionRequest, GridSearchState, NewSavedSearch,
+    NewSearchSubscription, SavedSearch, SearchSubscription,
+};
+use leptos::ev::{Event, MouseEvent};
+use leptos::*;
+
+async fn get_saved_searches(w: WriteSignal<Vec<SavedSearch>>) {
+    let searches = gloo_net::http::Request::get(&format!(
+        "{}/api/saved-searches",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    w.set(searches);
+}
+
+/// The error message of the API when it refuses the search
+async fn save_search(request: NewSavedSearch) -> Result<(), String> {
+    let resp = gloo_net::http::Request::post(&format!(
+        "{}/api/saved-searches",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+    if resp.ok() {
+        Ok(())
+    } else {
+        Err(resp.text().await.unwrap_or_default())
+    }
+}
+
+async fn delete_saved_search(request: DeleteSavedSearchRequest) {
+    gloo_net::http::Request::post(&format!(
+        "{}/api/saved-searches/delete",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+}
+
+async fn get_subscriptions(w: WriteSignal<Vec<SearchSubscription>>) {
+    let subscriptions = gloo_net::http::Request::get(&format!(
+        "{}/api/subscriptions",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    w.set(subscriptions);
+}
+
+/// The error message of the API when it refuses the subscription
+async fn subscribe(request: NewSearchSubscription) -> Result<(), String> {
+    let resp = gloo_net::http::Request::post(&format!(
+        "{}/api/subscriptions",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+    if resp.ok() {
+        Ok(())
+    } else {
+        Err(resp.text().await.unwrap_or_default())
+    }
+}
+
+async fn unsubscribe(request: DeleteSearchSubscriptionRequest) {
+    gloo_net::http::Request::post(&format!(
+        "{}/api/subscriptions/delete",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap();
+}
+
+/// Searches saved by the team, opening one replaces the current search. `state` is what gets
+/// saved, saving under the name of one of the owner's searches replaces it. Subscribing to a
+/// search notifies about new traces matching it.
+#[component]
+pub fn SavedSearches(
+    cx: Scope,
+    root_path: String,
+    state: Signal<GridSearchState>,
+    on_open: Box<dyn Fn(GridSearchState)>,
+) -> impl IntoView {
+    let on_open = store_value(cx, on_open);
+    let (searches_r, searches_w) = create_signal(cx, Vec::<SavedSearch>::new());
+    let (subscriptions_r, subscriptions_w) = create_signal(cx, Vec::<SearchSubscription>::new());
+    let refresh = move || {
+        spawn_local(get_saved_searches(searches_w));
+        spawn_local(get_subscriptions(subscriptions_w));
+    };
+    refresh();
+    let (interval_minutes_r, interval_minutes_w) = create_signal(cx, 15u64);
+    let (name_r, name_w) = create_signal(cx, String::new());
+    let (owner_r, owner_w) = create_signal(cx, String::new());
+    let (description_r, description_w) = create_signal(cx, String::new());
+    let (error_r, error_w) = create_signal(cx, None::<String>);
+
+    let save = move |_click: MouseEvent| {
+        let request = NewSavedSearch {
+            name: name_r.get_untracked(),
+            owner: owner_r.get_untracked(),
+            description: Some(description_r.get_untracked()).filter(|d| !d.is_empty()),
+            state: state.get_untracked(),
+        };
+        spawn_local(async move {
+            let result = save_search(request).await;
+            error_w.set(result.err());
+            refresh();
+        });
+    };
+
+    let rows = move || {
+        searches_r.with(|searches| {
+            searches
+                .iter()
+                .cloned()
+                .map(|search| {
+                    let href = grid_search_url(&root_path, &search.state);
+                    let open = {
+                        let state = search.state.clone();
+                        move |ev: MouseEvent| {
+                            ev.prevent_default();
+                            on_open.with_value(|on_open| on_open(state.clone()));
+                        }
+                    };
+                    let id = search.id;
+                    let delete = move |_click: MouseEvent| {
+                        spawn_local(async move {
+                            delete_saved_search(DeleteSavedSearchRequest { id }).await;
+                            refresh();
+                        });
+                    };
+                    let subscription = subscriptions_r.with(|subscriptions| {
+                        subscriptions
+                            .iter()
+                            .find(|subscription| subscription.saved_search_id == id)
+                            .cloned()
+                    });
+                    let subscribed = subscription.is_some();
+                    let subscription_title = match &subscription {
+                        Some(subscription) => format!(
+                            "Notifying at most every {} minutes, click to unsubscribe",
+                            subscription.min_interval_seconds / 60
+                        ),
+                        None => "Notify about new matching traces".to_string(),
+                    };
+                    let toggle_subscription = move |_click: MouseEvent| {
+                        let subscription_id = subscription.as_ref().map(|s| s.id);
+                        let min_interval_seconds = interval_minutes_r.get_untracked() * 60;
+                        spawn_local(async move {
+                            match subscription_id {
+                                Some(id) => {
+                                    unsubscribe(DeleteSearchSubscriptionRequest { id }).await;
+                                }
+                                None => {
+                                    let result = subscribe(NewSearchSubscription {
+                                        saved_search_id: id,
+                                        min_interval_seconds,
+                                    })
+                                    .await;
+                                    error_w.set(result.err());
+                                }
+                            }
+                            refresh();
+                        });
+                    };
+                    view! {cx,
+                        <div class="saved-searches__search" title=search.description.unwrap_or_default()>
+                            <a href=href on:click=open>{search.name}</a>
+                            <span class="saved-searches__owner">{search.owner}</span>
+                            <button class="filter-builder__button" title=subscription_title
+                                class:saved-searches__subscribed=subscribed on:click=toggle_subscription
+                            >
+                                {if subscribed { "🔔" } else { "🔕" }}
+                            </button>
+                            <button class="filter-builder__button" on:click=delete>"✕"</button>
+                        </div>
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+    view! {cx,
+        <div class="search-panel__label saved-searches">
+            "Saved Searches:"
+            {rows}
+            <div class="search-panel__input-flex-container">
+                <input on:input=move |ev: Event| name_w.set(event_target_value(&ev))
+                    placeholder="Name" class="search-panel__input" type="text" maxlength="100" size="10"
+                />
+                <input on:input=move |ev: Event| owner_w.set(event_target_value(&ev))
+                    placeholder="Owner" class="search-panel__input" type="text" maxlength="50" size="8"
+                />
+            </div>
+            <div class="search-panel__input-flex-container">
+                <input on:input=move |ev: Event| description_w.set(event_target_value(&ev))
+                    placeholder="Description (optional)" class="search-panel__input" type="text" size="20"
+                />
+                <button class="filter-builder__button" on:click=save>"Save"</button>
+            </div>
+            <div class="search-panel__input-flex-container">
+                <p>"Notify at most every"</p>
+                <input on:input=move |ev: Event| {
+                        if let Ok(minutes) = event_target_value(&ev).parse() {
+                            interval_minutes_w.set(minutes);
+                        }
+                    }
+                    prop:value=move || interval_minutes_r.get().to_string()
+                    class="search-panel__input" type="text" maxlength="5" size="2"
+                />
+                <p>"min"</p>
+            </div>
+            {move || error_r.get().map(|error| view! {cx, <p class="saved-searches__error">{error}</p>})}
+        </div>
+    }
+}
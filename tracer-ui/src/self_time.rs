@@ -0,0 +1,65 @@
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::SpanSelfTime;
+use leptos::*;
+use leptos_router::ParamsMap;
+
+fn format_millis(nanos: u64) -> String {
+    format!("{:.2}", nanos as f64 / 1_000_000.)
+}
+
+/// Where the time of the trace went, spans grouped by name with the most self time first
+#[component]
+pub fn SpanSelfTimeTable(cx: Scope, trace_query_parameters: ParamsMap) -> impl IntoView {
+    let (self_times_r, self_times_w) = create_signal(cx, Vec::<SpanSelfTime>::new());
+    let _api_request_sender = create_local_resource(
+        cx,
+        move || trace_query_parameters.clone(),
+        move |qp| get_self_time(qp, self_times_w),
+    );
+    let rows = move || {
+        self_times_r
+            .get()
+            .into_iter()
+            .map(|s| {
+                view! {cx,
+                    <tr class="row-container">
+                        <td class="trace-table__cell">{s.name}</td>
+                        <td class="trace-table__cell">{s.count}</td>
+                        <td class="trace-table__cell">{format_millis(s.total_duration)}</td>
+                        <td class="trace-table__cell">{format_millis(s.self_duration)}</td>
+                        <td class="trace-table__cell">{format!("{:.1}", s.trace_fraction * 100.)}</td>
+                        <td class="trace-table__cell">{format!("{:.1}", s.self_time_fraction * 100.)}</td>
+                    </tr>
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+    view! {cx,
+        <table class="trace-table self-time">
+            <tr class="row-container">
+                <th class="trace-table__cell">"Span Name"</th>
+                <th class="trace-table__cell">"Count"</th>
+                <th class="trace-table__cell">"Total Time (ms)"</th>
+                <th class="trace-table__cell">"Self Time (ms)"</th>
+                <th class="trace-table__cell" title="Self time over the trace duration, above 100 when spans run concurrently">"% of Trace Time"</th>
+                <th class="trace-table__cell" title="Self time over the self time of all the spans">"% of Self Time"</th>
+            </tr>
+            {rows}
+        </table>
+    }
+}
+
+async fn get_self_time(id: ParamsMap, w: WriteSignal<Vec<SpanSelfTime>>) {
+    let self_times: Vec<SpanSelfTime> = gloo_net::http::Request::get(&format!(
+        "{}/api/trace/self-time{}",
+        API_SERVER_URL_NO_TRAILING_SLASH,
+        id.to_query_string()
+    ))
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    w.set(self_times);
+}
@@ -0,0 +1,203 @@
+use crate::grid::{local_date_to_unix_nanos, unix_nanos_to_local_date, DatePicker};
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{StorageUsageReport, StorageUsageRequest, StorageUsageTrendPoint};
+use chrono::{Duration, NaiveDateTime};
+use js_sys::Date;
+use leptos::{
+    component, create_local_resource, create_signal, log, view, HtmlElement, IntoView, Scope,
+    Signal, SignalGet, SignalSet, SignalUpdate, SignalWith, WriteSignal,
+};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+struct StorageUsageRequestInput {
+    request: StorageUsageRequest,
+}
+
+impl Default for StorageUsageRequestInput {
+    fn default() -> Self {
+        let now = NaiveDateTime::from_timestamp_millis(Date::now().round() as i64).unwrap();
+        Self {
+            request: StorageUsageRequest {
+                from_date_unix: u64::try_from((now - Duration::days(1)).timestamp_nanos())
+                    .expect("timestamp to fit u64"),
+                to_date_unix: u64::try_from(now.timestamp_nanos()).expect("timestamp to fit u64"),
+            },
+        }
+    }
+}
+
+fn bytes_to_mb(bytes: i64) -> String {
+    format!("{:.2}", bytes as f64 / 1_000_000.)
+}
+
+#[component]
+pub fn StorageUsageSummary(cx: Scope) -> impl IntoView {
+    let (request_r, request_w) = create_signal(cx, StorageUsageRequestInput::default());
+    let (report_r, report_w) = create_signal(cx, StorageUsageReport::default());
+    let _api_request_sender = create_local_resource(
+        cx,
+        move || request_r.with(|r| r.request.clone()),
+        move |request| get_storage_usage(request, report_w),
+    );
+    let current_from_datetime = Signal::derive(cx, move || {
+        request_r.with(|r| unix_nanos_to_local_date(r.request.from_date_unix))
+    });
+    let current_to_datetime = Signal::derive(cx, move || {
+        request_r.with(|r| unix_nanos_to_local_date(r.request.to_date_unix))
+    });
+    let from_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            request_w.update(|r| r.request.from_date_unix = timestamp_nanos);
+        } else {
+            log!("From date out of bounds!")
+        }
+    };
+    let to_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            request_w.update(|r| r.request.to_date_unix = timestamp_nanos);
+        } else {
+            log!("To date out of bounds!")
+        }
+    };
+
+    let html_headers = [
+        "Service Name",
+        "Top Level Span",
+        "Traces",
+        "Spans",
+        "Events",
+        "Attributes",
+        "Estimated Size (MB)",
+        "Share (%)",
+    ]
+    .into_iter()
+    .map(|header| {
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>{header}</a>
+            </th>
+        }
+    })
+    .collect::<Vec<_>>();
+    let html_rows = move |report: StorageUsageReport| {
+        let total_bytes: i64 = report.usage.iter().map(|u| u.estimated_bytes).sum();
+        report
+            .usage
+            .into_iter()
+            .map(|u| {
+                let share = 100. * u.estimated_bytes as f64 / total_bytes.max(1) as f64;
+                view! {cx,
+                    <tr class="row-container">
+                        <td class="trace-table__cell">{u.service_name}</td>
+                        <td class="trace-table__cell">{u.top_level_span_name}</td>
+                        <td class="trace-table__cell">{u.traces}</td>
+                        <td class="trace-table__cell">{u.spans}</td>
+                        <td class="trace-table__cell">{u.events}</td>
+                        <td class="trace-table__cell">{u.attributes}</td>
+                        <td class="trace-table__cell">{bytes_to_mb(u.estimated_bytes)}</td>
+                        <td class="trace-table__cell">{format!("{share:.1}")}</td>
+                    </tr>
+                }
+            })
+            .collect::<Vec<HtmlElement<_>>>()
+    };
+    let html_trend = move |report: StorageUsageReport| {
+        let max_bucket_bytes = report
+            .trend
+            .iter()
+            .map(|t| t.estimated_bytes)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let bucket_size_nanos = report.bucket_size_nanos.max(1);
+        let (from_date_unix, to_date_unix) =
+            request_r.with(|r| (r.request.from_date_unix, r.request.to_date_unix));
+        let bucket_count = to_date_unix.saturating_sub(from_date_unix) / bucket_size_nanos + 1;
+        let trend_by_service: BTreeMap<String, Vec<StorageUsageTrendPoint>> = report
+            .trend
+            .into_iter()
+            .fold(BTreeMap::new(), |mut acc, curr| {
+                acc.entry(curr.service_name.clone()).or_default().push(curr);
+                acc
+            });
+        trend_by_service
+            .into_iter()
+            .map(|(service_name, points)| {
+                let bars: Vec<_> = points
+                    .into_iter()
+                    .map(|p| {
+                        let bucket_idx =
+                            p.bucket_start_unix.saturating_sub(from_date_unix) / bucket_size_nanos;
+                        let left = 100. * bucket_idx as f64 / bucket_count as f64;
+                        let width = 100. / bucket_count as f64;
+                        let height = 100. * p.estimated_bytes as f64 / max_bucket_bytes as f64;
+                        let title = format!(
+                            "{} - {} traces, {} spans, {} events, {} MB",
+                            unix_nanos_to_local_date(p.bucket_start_unix).format("%Y-%m-%d %H:%M"),
+                            p.traces,
+                            p.spans,
+                            p.events,
+                            bytes_to_mb(p.estimated_bytes)
+                        );
+                        view! {cx,
+                            <div title=title class="storage-trend__bar"
+                                style=format!("left: {left:.2}%; width: {width:.2}%; height: {height:.2}%;")>
+                            </div>
+                        }
+                    })
+                    .collect();
+                view! {cx,
+                    <div class="storage-trend">
+                        <p class="storage-trend__label">{service_name}</p>
+                        <div class="storage-trend__bars">{bars}</div>
+                    </div>
+                }
+            })
+            .collect::<Vec<HtmlElement<_>>>()
+    };
+
+    view! {cx,
+        <div class="main-grid">
+            <div class="main">
+                <table class="trace-table">
+                    <tr class="row-container">
+                        {html_headers}
+                    </tr>
+                    {move || html_rows(report_r.get())}
+                </table>
+                {move || html_trend(report_r.get())}
+            </div>
+            <div class="search-panel">
+                <DatePicker
+                    label="From (local):".to_string()
+                    date_to_display=current_from_datetime
+                    on_change=Box::new(from_changed)
+                />
+                <DatePicker
+                    label="To (local):".to_string()
+                    date_to_display=current_to_datetime
+                    on_change=Box::new(to_changed)
+                />
+            </div>
+        </div>
+    }
+}
+
+async fn get_storage_usage(request: StorageUsageRequest, w: WriteSignal<StorageUsageReport>) {
+    log!("Sending req");
+    let report: StorageUsageReport = gloo_net::http::Request::post(&format!(
+        "{}/api/storage-usage",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    log!("Got storage usage back");
+    w.set(report);
+}
@@ -1,14 +1,60 @@
+use crate::grid::{local_date_to_unix_nanos, unix_nanos_to_local_date, DatePicker};
+use crate::profile::ProfileFlameGraph;
+use crate::time_series::TimeSeriesCharts;
 use crate::API_SERVER_URL_NO_TRAILING_SLASH;
 use api_structs::{Summary, SummaryRequest};
+use chrono::{Duration, NaiveDateTime};
+use js_sys::Date;
 use leptos::{
-    component, log, view, HtmlElement, IntoView, Scope, SignalGet, SignalSet, WriteSignal,
+    component, log, view, HtmlElement, IntoView, Scope, Signal, SignalGet, SignalSet, SignalUpdate,
+    SignalWith, WriteSignal,
 };
 
+fn default_summary_request() -> SummaryRequest {
+    let now = NaiveDateTime::from_timestamp_millis(Date::now().round() as i64).unwrap();
+    SummaryRequest {
+        from_date_unix: u64::try_from((now - Duration::days(1)).timestamp_nanos())
+            .expect("timestamp to fit u64"),
+        to_date_unix: u64::try_from(now.timestamp_nanos()).expect("timestamp to fit u64"),
+    }
+}
+
 #[component]
 pub fn TracesSummary(cx: Scope, root_path: String) -> impl IntoView {
+    let (request_r, request_w) = leptos::create_signal(cx, default_summary_request());
     let (trace_spans_r, trace_spans_w) = leptos::create_signal(cx, Vec::new());
-    let _api_request_sender =
-        leptos::create_local_resource(cx, move || (), move |_| get_summary(trace_spans_w));
+    let (charted_r, charted_w) = leptos::create_signal(cx, Option::<(String, String)>::None);
+    let charted = Signal::derive(cx, move || charted_r.get());
+    let (profiled_r, profiled_w) = leptos::create_signal(cx, Option::<(String, String)>::None);
+    let profiled = Signal::derive(cx, move || profiled_r.get());
+    let window = Signal::derive(cx, move || {
+        request_r.with(|r| (r.from_date_unix, r.to_date_unix))
+    });
+    let _api_request_sender = leptos::create_local_resource(
+        cx,
+        move || request_r.get(),
+        move |request| get_summary(request, trace_spans_w),
+    );
+    let current_from_datetime = Signal::derive(cx, move || {
+        request_r.with(|r| unix_nanos_to_local_date(r.from_date_unix))
+    });
+    let current_to_datetime = Signal::derive(cx, move || {
+        request_r.with(|r| unix_nanos_to_local_date(r.to_date_unix))
+    });
+    let from_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            request_w.update(|r| r.from_date_unix = timestamp_nanos);
+        } else {
+            log!("From date out of bounds!")
+        }
+    };
+    let to_changed = move |new_datetime: NaiveDateTime| {
+        if let Some(timestamp_nanos) = local_date_to_unix_nanos(new_datetime) {
+            request_w.update(|r| r.to_date_unix = timestamp_nanos);
+        } else {
+            log!("To date out of bounds!")
+        }
+    };
 
     let html_headers = [
         view! {cx,
@@ -31,6 +77,41 @@ pub fn TracesSummary(cx: Scope, root_path: String) -> impl IntoView {
                 <a>"Traces with Error"</a>
             </th>
         },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"Error Rate (%)"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"Traces with Warns"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"Warns"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"Traces/min"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"p50 (ms)"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"p90 (ms)"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"p99 (ms)"</a>
+            </th>
+        },
         view! {cx,
             <th class="trace-table__cell">
                 <a>"Longest Trace (ms)"</a>
@@ -41,10 +122,22 @@ pub fn TracesSummary(cx: Scope, root_path: String) -> impl IntoView {
                 <a>"➔"</a>
             </th>
         },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"Chart"</a>
+            </th>
+        },
+        view! {cx,
+            <th class="trace-table__cell">
+                <a>"Profile"</a>
+            </th>
+        },
     ]
     .to_vec();
     let html_rows = move |rows: Vec<Summary>| {
         let res: Vec<HtmlElement<_>> = rows.into_iter().map(|r|{
+                let chart_key = (r.service_name.clone(), r.top_level_span_name.clone());
+                let profile_key = chart_key.clone();
                 view! {
                 cx,
                 <tr class="row_container_class">
@@ -52,10 +145,23 @@ pub fn TracesSummary(cx: Scope, root_path: String) -> impl IntoView {
                         <td class="trace-table__cell">{r.top_level_span_name.clone()}</td>
                         <td class="trace-table__cell">{r.total_traces}</td>
                         <td class="trace-table__cell">{r.total_traces_with_error}</td>
+                        <td class="trace-table__cell">{format!("{:.1}", r.error_rate * 100.)}</td>
+                        <td class="trace-table__cell">{r.traces_with_warnings}</td>
+                        <td class="trace-table__cell">{r.total_warnings}</td>
+                        <td class="trace-table__cell">{format!("{:.2}", r.throughput_per_minute)}</td>
+                        <td class="trace-table__cell">{r.p50_duration/1_000_000}</td>
+                        <td class="trace-table__cell">{r.p90_duration/1_000_000}</td>
+                        <td class="trace-table__cell">{r.p99_duration/1_000_000}</td>
                         <td class="trace-table__cell">{r.longest_trace_duration/1000_000}</td>
                         <td class="trace-table__cell">
                     <a href={format!("{}trace/?trace_id={}", root_path, r.longest_trace_id)}>{"➔"}</a>
                         </td>
+                        <td class="trace-table__cell">
+                    <a style="cursor: pointer" on:click=move |_| charted_w.set(Some(chart_key.clone()))>{"📈"}</a>
+                        </td>
+                        <td class="trace-table__cell">
+                    <a style="cursor: pointer" on:click=move |_| profiled_w.set(Some(profile_key.clone()))>{"🔥"}</a>
+                        </td>
                 </tr>
                 }
             }).collect();
@@ -71,8 +177,20 @@ pub fn TracesSummary(cx: Scope, root_path: String) -> impl IntoView {
                     </tr>
                     {move || html_rows(trace_spans_r.get())}
                 </table>
+                <TimeSeriesCharts selected=charted window=window/>
+                <ProfileFlameGraph selected=profiled window=window/>
             </div>
             <div class="search-panel">
+                <DatePicker
+                    label="From (local):".to_string()
+                    date_to_display=current_from_datetime
+                    on_change=Box::new(from_changed)
+                />
+                <DatePicker
+                    label="To (local):".to_string()
+                    date_to_display=current_to_datetime
+                    on_change=Box::new(to_changed)
+                />
                 <label class="search-panel__label">
                     "Containing:"
                     <input
@@ -84,14 +202,11 @@ pub fn TracesSummary(cx: Scope, root_path: String) -> impl IntoView {
     }
 }
 
-async fn get_summary(w: WriteSignal<Vec<Summary>>) {
+async fn get_summary(request: SummaryRequest, w: WriteSignal<Vec<Summary>>) {
     log!("Sending req");
     let traces: Vec<Summary> =
         gloo_net::http::Request::post(&format!("{}/api/summary", API_SERVER_URL_NO_TRAILING_SLASH))
-            .json(&SummaryRequest {
-                from_date_unix_micros: 100,
-                to_date_unix_micros: 99999,
-            })
+            .json(&request)
             .unwrap()
             .send()
             .await
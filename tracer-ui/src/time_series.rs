@@ -0,0 +1,203 @@
+use crate::grid::unix_nanos_to_local_date;
+use crate::API_SERVER_URL_NO_TRAILING_SLASH;
+use api_structs::{TimeSeries, TimeSeriesPoint, TimeSeriesRequest};
+use leptos::ev::Event;
+use leptos::*;
+
+/// Width of the svg coordinate system, the chart is stretched to the container width
+const CHART_WIDTH: f64 = 1000.;
+const CHART_HEIGHT: f64 = 100.;
+
+fn point_title(p: &TimeSeriesPoint) -> String {
+    format!(
+        "{} - {} requests, {} errors, {} warns, p50 {}ms, p90 {}ms, p99 {}ms",
+        unix_nanos_to_local_date(p.bucket_start_unix).format("%Y-%m-%d %H:%M"),
+        p.count,
+        p.error_count,
+        p.warning_count,
+        p.p50_duration / 1_000_000,
+        p.p90_duration / 1_000_000,
+        p.p99_duration / 1_000_000
+    )
+}
+
+fn bar_chart(
+    cx: Scope,
+    label: String,
+    points: &[TimeSeriesPoint],
+    value: fn(&TimeSeriesPoint) -> i64,
+    highlighted_value: Option<fn(&TimeSeriesPoint) -> i64>,
+) -> impl IntoView {
+    let max = points.iter().map(value).max().unwrap_or(0).max(1);
+    let width = 100. / points.len().max(1) as f64;
+    let bars = points
+        .iter()
+        .enumerate()
+        .map(|(idx, p)| {
+            let left = width * idx as f64;
+            let height = 100. * value(p) as f64 / max as f64;
+            let highlighted_height =
+                highlighted_value.map(|highlighted| 100. * highlighted(p) as f64 / max as f64);
+            view! {cx,
+                <div title=point_title(p) class="time-series-chart__bar"
+                    style=format!("left: {left:.2}%; width: {width:.2}%; height: {height:.2}%;")>
+                    {highlighted_height.map(|highlighted_height| view! {cx,
+                        <div class="time-series-chart__bar-highlight"
+                            style=format!("height: {:.2}%;", 100. * highlighted_height / height.max(f64::MIN_POSITIVE))>
+                        </div>
+                    })}
+                </div>
+            }
+        })
+        .collect::<Vec<_>>();
+    view! {cx,
+        <div class="time-series-chart">
+            <p class="time-series-chart__label">{format!("{label} (max {max})")}</p>
+            <div class="time-series-chart__area">{bars}</div>
+        </div>
+    }
+}
+
+fn latency_chart(cx: Scope, points: &[TimeSeriesPoint]) -> impl IntoView {
+    let max = points
+        .iter()
+        .map(|p| p.p99_duration)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let step = CHART_WIDTH / points.len().max(1) as f64;
+    let line = |duration: fn(&TimeSeriesPoint) -> u64| {
+        points
+            .iter()
+            .enumerate()
+            .filter(|(_idx, p)| p.count > 0)
+            .map(|(idx, p)| {
+                let x = step * (idx as f64 + 0.5);
+                let y = CHART_HEIGHT * (1. - duration(p) as f64 / max as f64);
+                format!("{x:.1},{y:.1}")
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    view! {cx,
+        <div class="time-series-chart">
+            <p class="time-series-chart__label">
+                {format!("Latency (max {}ms): ", max / 1_000_000)}
+                <span class="time-series-chart__p50">"p50"</span>" "
+                <span class="time-series-chart__p90">"p90"</span>" "
+                <span class="time-series-chart__p99">"p99"</span>
+            </p>
+            <svg class="time-series-chart__area" viewBox=format!("0 0 {CHART_WIDTH} {CHART_HEIGHT}") preserveAspectRatio="none">
+                <polyline class="time-series-chart__p99" points=line(|p| p.p99_duration) vector-effect="non-scaling-stroke"/>
+                <polyline class="time-series-chart__p90" points=line(|p| p.p90_duration) vector-effect="non-scaling-stroke"/>
+                <polyline class="time-series-chart__p50" points=line(|p| p.p50_duration) vector-effect="non-scaling-stroke"/>
+            </svg>
+        </div>
+    }
+}
+
+/// Charts for the selected (service name, top level span name) over the window
+#[component]
+pub fn TimeSeriesCharts(
+    cx: Scope,
+    selected: Signal<Option<(String, String)>>,
+    window: Signal<(u64, u64)>,
+) -> impl IntoView {
+    // the span name is only kept while the same trace stays selected, since span names differ
+    let (picked_span_name_r, picked_span_name_w) =
+        create_signal(cx, Option::<((String, String), String)>::None);
+    let span_name = create_memo(cx, move |_| {
+        let selected = selected.get()?;
+        picked_span_name_r.with(|picked| match picked {
+            Some((picked_for, span_name)) if *picked_for == selected => Some(span_name.clone()),
+            _ => None,
+        })
+    });
+    let (series_r, series_w) = create_signal(cx, TimeSeries::default());
+    let request = create_memo(cx, move |_| {
+        let (service_name, top_level_span_name) = selected.get()?;
+        let (from_date_unix, to_date_unix) = window.get();
+        Some(TimeSeriesRequest {
+            service_name,
+            top_level_span_name,
+            span_name: span_name.get(),
+            from_date_unix,
+            to_date_unix,
+        })
+    });
+    let _api_request_sender = create_local_resource(
+        cx,
+        move || request.get(),
+        move |request| async move {
+            if let Some(request) = request {
+                get_time_series(request, series_w).await;
+            }
+        },
+    );
+    let span_name_changed = move |ev: Event| {
+        let val = event_target_value(&ev);
+        let picked = selected
+            .get_untracked()
+            .filter(|_| !val.is_empty())
+            .map(|selected| (selected, val));
+        picked_span_name_w.set(picked);
+    };
+    let charts = move || {
+        series_r.with(|series| {
+            view! {cx,
+                <div>
+                    {bar_chart(cx, "Requests, errors in red".to_string(), &series.points, |p| p.count, Some(|p| p.error_count))}
+                    {bar_chart(cx, "Warnings".to_string(), &series.points, |p| p.warning_count, None)}
+                    {latency_chart(cx, &series.points)}
+                </div>
+            }
+        })
+    };
+    view! {cx,
+        {move || match selected.get() {
+            None => view! {cx,
+                <div class="time-series">
+                    <p class="time-series__title">"Pick a row's 📈 to chart it over time"</p>
+                </div>
+            },
+            Some((service_name, top_level_span_name)) => view! {cx,
+                <div class="time-series">
+                    <p class="time-series__title">
+                        {move || format!("{service_name} - {top_level_span_name}, {} minute buckets",
+                            series_r.with(|s| s.bucket_size_nanos / 60_000_000_000))}
+                    </p>
+                    <select on:change=span_name_changed class="search-panel__input">
+                        <option value="" selected=move || span_name.with(|s| s.is_none())>"Whole trace"</option>
+                        {move || series_r.with(|s| s.span_names.clone()).into_iter().map(|name| {
+                            let is_selected = name.clone();
+                            view! {cx,
+                                <option value=name.clone()
+                                    selected=move || span_name.with(|s| s.as_ref() == Some(&is_selected))
+                                >
+                                    {format!("Span: {name}")}
+                                </option>
+                            }
+                        }).collect::<Vec<_>>()}
+                    </select>
+                    {charts}
+                </div>
+            },
+        }}
+    }
+}
+
+async fn get_time_series(request: TimeSeriesRequest, w: WriteSignal<TimeSeries>) {
+    let series: TimeSeries = gloo_net::http::Request::post(&format!(
+        "{}/api/time-series",
+        API_SERVER_URL_NO_TRAILING_SLASH
+    ))
+    .json(&request)
+    .unwrap()
+    .send()
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    w.set(series);
+}